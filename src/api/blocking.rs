@@ -0,0 +1,319 @@
+//! Blocking wrappers around the async data and trading APIs
+//!
+//! The types in this module drive the async `DataAPI` and `BrokerAPI` on a Tokio runtime
+//! shared by the whole process, for callers that are not themselves async. They must not
+//! be used from inside an async context; call the async APIs directly there instead.
+
+use std::future::Future;
+use std::collections::vec_deque;
+use std::ops::Range;
+use std::sync::{Arc, OnceLock};
+
+use chrono::{DateTime, Utc};
+
+use crate::api::auth::Authenticator;
+use crate::api::book::{OrderBook, Side};
+use crate::api::cache::Cache;
+use crate::api::candles::Granularity;
+use crate::api::data::{self, CoinData};
+use crate::api::money::Decimal;
+use crate::api::orders::{Order, OrderEvent, OrderPage, OrderPoller, OrderQuery};
+use crate::api::pair::Pair;
+use crate::api::retention::Retention;
+use crate::api::series::Window;
+use crate::api::source::{PriceSource, WebSource};
+use crate::api::validation::Validation;
+use crate::api::transactions::{self, OrderQuote, OrderReceipt};
+use crate::error::Result;
+use crate::historical::RawData;
+
+/// Handle to a Tokio runtime shared by blocking wrappers.
+///
+/// Cloning a `Runtime` gives another handle to the same runtime, so several wrappers can
+/// run on one set of worker threads. The wrappers' constructors all use `Runtime::shared`,
+/// so a process starts one runtime however many traders it creates.
+#[derive(Clone)]
+pub struct Runtime {
+    inner: Arc<tokio::runtime::Runtime>,
+}
+
+static SHARED: OnceLock<Runtime> = OnceLock::new();
+
+impl Runtime {
+    /// Start a new multi-threaded runtime, separate from the shared one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the runtime cannot be started.
+    pub fn new() -> Self {
+        let runtime = tokio::runtime::Builder::new()
+            .threaded_scheduler()
+            .enable_all()
+            .build()
+            .expect("Build runtime");
+
+        Runtime { inner: Arc::new(runtime) }
+    }
+
+    /// Get a handle to the runtime shared by the whole process, starting it on first use.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the runtime cannot be started.
+    pub fn shared() -> Self {
+        SHARED.get_or_init(Runtime::new).clone()
+    }
+
+    /// Run `future` to completion on the calling thread, with the runtime's worker threads
+    /// driving its timers and I/O, so several threads can block on one runtime at once.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.inner.handle().enter(|| futures::executor::block_on(future))
+    }
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Self::shared()
+    }
+}
+
+/// Blocking version of `api::DataAPI`.
+///
+/// # Example
+///
+/// ```no_run
+/// use seventh_core::api::blocking::DataAPI;
+///
+/// let mut data = DataAPI::new();
+/// data.update("BTC")?;
+/// println!("{:?}", data.last_ref());
+/// # Ok::<(), seventh_core::Error>(())
+/// ```
+pub struct DataAPI<S: PriceSource = WebSource> {
+    inner: data::DataAPI<S>,
+    runtime: Runtime,
+}
+
+impl DataAPI {
+    pub fn new() -> Self {
+        DataAPI::from_async(data::DataAPI::new(), Runtime::shared())
+    }
+
+    /// Get OHLCV candles for `pair` covering `range`, blocking until they arrive.
+    pub fn candles<P: Into<Pair>>(&self, pair: P, granularity: Granularity, range: Range<DateTime<Utc>>) -> Result<RawData> {
+        self.runtime.block_on(self.inner.candles(pair, granularity, range))
+    }
+
+    /// Get the current level 2 order book for `pair`, blocking until it arrives.
+    pub fn order_book<P: Into<Pair>>(&self, pair: P) -> Result<OrderBook> {
+        self.runtime.block_on(self.inner.order_book(pair))
+    }
+}
+
+impl Default for DataAPI {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: PriceSource> DataAPI<S> {
+    pub fn with_source(source: S) -> Self {
+        DataAPI::from_async(data::DataAPI::with_source(source), Runtime::shared())
+    }
+
+    /// Wrap an async `DataAPI`, driving it on `runtime`.
+    pub fn from_async(inner: data::DataAPI<S>, runtime: Runtime) -> Self {
+        DataAPI { inner, runtime }
+    }
+
+    /// Set the lookback and granularity of the history fetched by `update`.
+    pub fn with_window(mut self, window: Window) -> Self {
+        self.inner = self.inner.with_window(window);
+        self
+    }
+
+    /// Answer updates from `cache` where it holds fresh enough data.
+    pub fn with_cache(mut self, cache: Cache) -> Self {
+        self.inner = self.inner.with_cache(cache);
+        self
+    }
+
+    /// Set how many of the fetched snapshots are kept.
+    pub fn with_retention(mut self, retention: Retention) -> Self {
+        self.inner = self.inner.with_retention(retention);
+        self
+    }
+
+    /// Set the checks each fetched snapshot must pass.
+    pub fn with_validation(mut self, validation: Validation) -> Self {
+        self.inner = self.inner.with_validation(validation);
+        self
+    }
+
+    /// Set how many pairs `update_many` fetches at once.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.inner = self.inner.with_concurrency(concurrency);
+        self
+    }
+
+    /// Update the DataAPI with the data for `pair`, blocking until it arrives.
+    pub fn update<P: Into<Pair>>(&mut self, pair: P) -> Result<()> {
+        self.runtime.block_on(self.inner.update(pair))
+    }
+
+    /// Update the DataAPI with the data for each of `pairs`, blocking until all have
+    /// arrived or failed.
+    pub fn update_many<P: Into<Pair> + Clone>(&mut self, pairs: &[P]) -> Vec<(Pair, Result<()>)> {
+        self.runtime.block_on(self.inner.update_many(pairs))
+    }
+
+    pub fn last_ref(&self) -> Option<&CoinData> {
+        self.inner.last_ref()
+    }
+
+    pub fn latest<P: Into<Pair>>(&self, pair: P) -> Option<&CoinData> {
+        self.inner.latest(pair)
+    }
+
+    pub fn iter(&self) -> vec_deque::Iter<'_, CoinData> {
+        self.inner.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    #[deprecated(note = "clones every snapshot; use `iter` or `latest` instead")]
+    #[allow(deprecated)]
+    pub fn coins(&self) -> Vec<CoinData> {
+        self.inner.coins()
+    }
+
+    #[deprecated(note = "panics before the first update; use `last_ref` instead")]
+    #[allow(deprecated)]
+    pub fn last(&self) -> CoinData {
+        self.inner.last()
+    }
+
+    pub fn source(&self) -> &S {
+        self.inner.source()
+    }
+
+    /// Get the wrapped async `DataAPI`.
+    pub fn get_ref(&self) -> &data::DataAPI<S> {
+        &self.inner
+    }
+
+    /// Get the runtime this wrapper blocks on.
+    pub fn runtime(&self) -> &Runtime {
+        &self.runtime
+    }
+}
+
+/// Blocking version of `api::BrokerAPI`.
+pub struct BrokerAPI {
+    inner: transactions::BrokerAPI,
+    runtime: Runtime,
+}
+
+impl BrokerAPI {
+    pub fn new(auth: &str, account: &str, payment_method: &str) -> Self {
+        BrokerAPI::from_async(
+            transactions::BrokerAPI::new(auth, account, payment_method),
+            Runtime::shared(),
+        )
+    }
+
+    /// Wrap an async `BrokerAPI`, driving it on `runtime`.
+    pub fn from_async(inner: transactions::BrokerAPI, runtime: Runtime) -> Self {
+        BrokerAPI { inner, runtime }
+    }
+
+    /// Authenticate requests with `auth` instead of the bearer token given on creation.
+    pub fn with_authenticator<A: Authenticator + 'static>(mut self, auth: A) -> Self {
+        self.inner = self.inner.with_authenticator(auth);
+        self
+    }
+
+    /// Check the local clock is within `max_skew` of the exchange's, and return the skew.
+    pub fn check_clock(&self, max_skew: std::time::Duration) -> Result<chrono::Duration> {
+        self.runtime.block_on(self.inner.check_clock(max_skew))
+    }
+
+    pub fn sell(&mut self, pair: &Pair, amount: Decimal) -> Result<OrderReceipt> {
+        self.runtime.block_on(self.inner.sell(pair, amount))
+    }
+
+    pub fn buy(&mut self, pair: &Pair, amount: Decimal) -> Result<OrderReceipt> {
+        self.runtime.block_on(self.inner.buy(pair, amount))
+    }
+
+    pub fn place_order(&mut self, pair: &Pair, order: &Order) -> Result<OrderReceipt> {
+        self.runtime.block_on(self.inner.place_order(pair, order))
+    }
+
+    /// Let quotes be committed for up to `ttl` after they are made.
+    pub fn with_quote_ttl(mut self, ttl: std::time::Duration) -> Self {
+        self.inner = self.inner.with_quote_ttl(ttl);
+        self
+    }
+
+    pub fn quote_sell(&mut self, pair: &Pair, amount: Decimal) -> Result<OrderQuote> {
+        self.runtime.block_on(self.inner.quote_sell(pair, amount))
+    }
+
+    pub fn quote_buy(&mut self, pair: &Pair, amount: Decimal) -> Result<OrderQuote> {
+        self.runtime.block_on(self.inner.quote_buy(pair, amount))
+    }
+
+    pub fn commit(&mut self, order_id: &str) -> Result<OrderReceipt> {
+        self.runtime.block_on(self.inner.commit(order_id))
+    }
+
+    /// Forget every expired quote, returning them.
+    pub fn expire(&mut self) -> Vec<OrderQuote> {
+        self.inner.expire()
+    }
+
+    pub fn get_order(&self, side: Side, id: &str) -> Result<OrderReceipt> {
+        self.runtime.block_on(self.inner.get_order(side, id))
+    }
+
+    pub fn list_orders(&self, side: Side, query: &OrderQuery) -> Result<OrderPage> {
+        self.runtime.block_on(self.inner.list_orders(side, query))
+    }
+
+    pub fn cancel_order(&self, id: &str) -> Result<()> {
+        self.runtime.block_on(self.inner.cancel_order(id))
+    }
+
+    /// Look up every order in `history` that has not settled yet, and return what changed.
+    pub fn refresh(&self) -> Result<Vec<OrderEvent>> {
+        self.runtime.block_on(self.inner.refresh())
+    }
+
+    /// Look up unsettled orders every `interval` on this runtime, until the returned
+    /// poller is dropped.
+    pub fn poll(&self, interval: std::time::Duration) -> OrderPoller {
+        self.runtime.block_on(async { self.inner.poll(interval) })
+    }
+
+    /// Get the latest receipts for every order placed so far, oldest first.
+    pub fn history(&self) -> Vec<OrderReceipt> {
+        self.inner.history()
+    }
+
+    /// Get the latest receipt for the order placed as `id`, if it was placed here.
+    pub fn receipt(&self, id: &str) -> Option<OrderReceipt> {
+        self.inner.receipt(id)
+    }
+
+    /// Get the wrapped async `BrokerAPI`.
+    pub fn get_ref(&self) -> &transactions::BrokerAPI {
+        &self.inner
+    }
+}
@@ -0,0 +1,308 @@
+//! Level 2 order books from the Coinbase Exchange API and WebSocket feed
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+
+use crate::api::feed::Connection;
+use crate::api::money::Decimal;
+use crate::api::pair::Pair;
+use crate::error::{Error, Result};
+
+/// Which side of the book an order rests on, or which way an order trades.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    /// Bids, from buyers.
+    Buy,
+    /// Asks, from sellers.
+    Sell,
+}
+
+/// The total size resting at one price.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Level {
+    pub price: Decimal,
+    pub size: Decimal,
+}
+
+/// Coinbase Exchange `GET /products/:pair/book?level=2` response.
+#[derive(Deserialize, Debug)]
+pub(crate) struct Snapshot {
+    sequence: u64,
+    bids: Vec<(String, String, u64)>,
+    asks: Vec<(String, String, u64)>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BookMessage {
+    Snapshot {
+        product_id: String,
+        bids: Vec<(String, String)>,
+        asks: Vec<(String, String)>,
+    },
+    L2update {
+        product_id: String,
+        time: Option<DateTime<Utc>>,
+        changes: Vec<(String, String, String)>,
+    },
+    Error {
+        message: String,
+        reason: Option<String>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// The resting orders for a pair, aggregated by price.
+///
+/// A book is filled from a REST snapshot with `WebSource::order_book`, or kept current by
+/// a `BookFeed`. Sizes are in the base currency and prices in the quote currency.
+///
+/// # Example
+///
+/// ```
+/// use seventh_core::api::book::{OrderBook, Side};
+/// use seventh_core::api::Decimal;
+///
+/// let mut book = OrderBook::new("BTC-USD");
+/// book.update(Side::Buy, Decimal::from(99), Decimal::from(2));
+/// book.update(Side::Sell, Decimal::from(101), Decimal::from(1));
+/// book.update(Side::Sell, Decimal::from(102), Decimal::from(1));
+///
+/// assert_eq!(book.spread(), Some(Decimal::from(2)));
+/// assert_eq!(book.mid(), Some(Decimal::from(100)));
+/// // Buying 2 BTC takes the whole of both ask levels.
+/// assert_eq!(book.fill_price(Side::Buy, Decimal::from(2)), Some(Decimal::new(1015, 1)));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderBook {
+    pair: Pair,
+    bids: BTreeMap<Decimal, Decimal>,
+    asks: BTreeMap<Decimal, Decimal>,
+    sequence: Option<u64>,
+    time: Option<DateTime<Utc>>,
+}
+
+impl OrderBook {
+    /// Create an empty book for `pair`.
+    pub fn new<P: Into<Pair>>(pair: P) -> Self {
+        OrderBook { pair: pair.into(), bids: BTreeMap::new(), asks: BTreeMap::new(), sequence: None, time: None }
+    }
+
+    pub(crate) fn from_snapshot(pair: &Pair, snapshot: Snapshot) -> Result<Self> {
+        let mut book = OrderBook::new(pair.clone());
+        for (price, size, _) in snapshot.bids {
+            book.update(Side::Buy, price.parse()?, size.parse()?);
+        }
+        for (price, size, _) in snapshot.asks {
+            book.update(Side::Sell, price.parse()?, size.parse()?);
+        }
+        book.sequence = Some(snapshot.sequence);
+        book.time = Some(Utc::now());
+        Ok(book)
+    }
+
+    /// Set the size resting at `price` on `side`. A size of zero removes the level.
+    pub fn update(&mut self, side: Side, price: Decimal, size: Decimal) {
+        let levels = self.levels_mut(side);
+        if size > Decimal::ZERO {
+            levels.insert(price, size);
+        } else {
+            levels.remove(&price);
+        }
+    }
+
+    /// Remove every level from both sides.
+    pub fn clear(&mut self) {
+        self.bids.clear();
+        self.asks.clear();
+    }
+
+    /// Get the pair this book is for.
+    pub fn pair(&self) -> &Pair {
+        &self.pair
+    }
+
+    /// Get the sequence number of the REST snapshot the book was filled from, if it was.
+    pub fn sequence(&self) -> Option<u64> {
+        self.sequence
+    }
+
+    /// Get when the book last changed, if it has been filled.
+    pub fn time(&self) -> Option<DateTime<Utc>> {
+        self.time
+    }
+
+    /// Get the highest bid.
+    pub fn best_bid(&self) -> Option<Level> {
+        self.depth(Side::Buy, 1).pop()
+    }
+
+    /// Get the lowest ask.
+    pub fn best_ask(&self) -> Option<Level> {
+        self.depth(Side::Sell, 1).pop()
+    }
+
+    /// Get the difference between the lowest ask and the highest bid.
+    pub fn spread(&self) -> Option<Decimal> {
+        Some(self.best_ask()?.price - self.best_bid()?.price)
+    }
+
+    /// Get the price halfway between the highest bid and the lowest ask.
+    pub fn mid(&self) -> Option<Decimal> {
+        Some((self.best_ask()?.price + self.best_bid()?.price) / Decimal::TWO)
+    }
+
+    /// Get up to `levels` levels on `side`, best price first.
+    pub fn depth(&self, side: Side, levels: usize) -> Vec<Level> {
+        let level = |(price, size): (&Decimal, &Decimal)| Level { price: *price, size: *size };
+        match side {
+            Side::Buy => self.bids.iter().rev().take(levels).map(level).collect(),
+            Side::Sell => self.asks.iter().take(levels).map(level).collect(),
+        }
+    }
+
+    /// Get the average price a market order for `size` would fill at, walking the book.
+    ///
+    /// A buy takes asks and a sell takes bids. Returns `None` if the book is not deep
+    /// enough to fill all of `size`. Compare with `mid` to estimate slippage before
+    /// placing an order.
+    pub fn fill_price(&self, side: Side, size: Decimal) -> Option<Decimal> {
+        if size <= Decimal::ZERO {
+            return None;
+        }
+        let opposite = match side {
+            Side::Buy => Side::Sell,
+            Side::Sell => Side::Buy,
+        };
+
+        let mut remaining = size;
+        let mut cost = Decimal::ZERO;
+        for level in self.depth(opposite, usize::MAX) {
+            let taken = remaining.min(level.size);
+            cost += taken * level.price;
+            remaining -= taken;
+            if remaining <= Decimal::ZERO {
+                return Some(cost / size);
+            }
+        }
+        None
+    }
+
+    fn levels_mut(&mut self, side: Side) -> &mut BTreeMap<Decimal, Decimal> {
+        match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        }
+    }
+}
+
+/// Order books kept current from the Coinbase Exchange WebSocket feed.
+///
+/// A `BookFeed` subscribes to the level2 and heartbeat channels for its pairs. Each
+/// connection starts with a full snapshot of every book, then sends the changed levels
+/// as they happen, so a book is rebuilt whole whenever the connection is reopened.
+/// Connections are managed as for a `TickerFeed`.
+///
+/// # Example
+///
+/// ```no_run
+/// use seventh_core::api::{Decimal, Endpoints};
+/// use seventh_core::api::book::{BookFeed, Side};
+///
+/// # async fn run() {
+/// let feed = BookFeed::subscribe(&Endpoints::production().feed, &["BTC-USD"]);
+/// if let Some(book) = feed.book("BTC-USD") {
+///     println!("{:?}", book.fill_price(Side::Buy, Decimal::new(5, 1)));
+/// }
+/// # }
+/// ```
+pub struct BookFeed {
+    url: String,
+    pairs: Vec<Pair>,
+    books: Arc<Mutex<HashMap<Pair, OrderBook>>>,
+    connection: Connection,
+}
+
+impl BookFeed {
+    /// Subscribe to the books for `pairs` on the feed at `url`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside a Tokio runtime.
+    pub fn subscribe<P: Into<Pair> + Clone>(url: &str, pairs: &[P]) -> Self {
+        let pairs: Vec<Pair> = pairs.iter().cloned().map(Into::into).collect();
+        let books = Arc::new(Mutex::new(HashMap::new()));
+
+        let shared = books.clone();
+        let connection = Connection::open(url, &["level2", "heartbeat"], &pairs, move |text| {
+            apply(&shared, serde_json::from_str(text)?)
+        });
+
+        BookFeed { url: url.to_owned(), pairs, books, connection }
+    }
+
+    /// Get the feed URL this feed is subscribed to.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Get the pairs this feed is subscribed to.
+    pub fn pairs(&self) -> &[Pair] {
+        &self.pairs
+    }
+
+    /// Get a copy of the current book for `pair`, or `None` until its snapshot arrives.
+    pub fn book<P: Into<Pair>>(&self, pair: P) -> Option<OrderBook> {
+        self.books.lock().expect("Lock books").get(&pair.into()).cloned()
+    }
+
+    /// Get why the connection last failed, or the last message that could not be read.
+    pub fn last_error(&self) -> Option<String> {
+        self.connection.last_error()
+    }
+}
+
+/// Apply `message` to the book it is about, returning whether a book changed.
+fn apply(books: &Mutex<HashMap<Pair, OrderBook>>, message: BookMessage) -> Result<bool> {
+    let mut books = books.lock().expect("Lock books");
+
+    match message {
+        BookMessage::Snapshot { product_id, bids, asks } => {
+            let pair = Pair::from(product_id.as_str());
+            let mut book = OrderBook::new(pair.clone());
+            for (price, size) in bids {
+                book.update(Side::Buy, price.parse()?, size.parse()?);
+            }
+            for (price, size) in asks {
+                book.update(Side::Sell, price.parse()?, size.parse()?);
+            }
+            book.time = Some(Utc::now());
+            books.insert(pair, book);
+        }
+        BookMessage::L2update { product_id, time, changes } => {
+            // Changes before the snapshot cannot be applied to anything.
+            let book = match books.get_mut(&Pair::from(product_id.as_str())) {
+                Some(book) => book,
+                None => return Ok(false),
+            };
+            for (side, price, size) in changes {
+                let side = match side.as_str() {
+                    "buy" => Side::Buy,
+                    "sell" => Side::Sell,
+                    other => return Err(Error::Feed(format!("unknown side {}", other))),
+                };
+                book.update(side, price.parse()?, size.parse()?);
+            }
+            book.time = Some(time.unwrap_or_else(Utc::now));
+        }
+        BookMessage::Error { message, reason } => {
+            return Err(Error::Feed(format!("{}: {}", message, reason.unwrap_or_default())));
+        }
+        BookMessage::Other => return Ok(false),
+    }
+    Ok(true)
+}
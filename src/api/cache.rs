@@ -0,0 +1,289 @@
+//! Time-to-live cache for market data
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+
+use crate::api::pair::Pair;
+use crate::api::series::{History, Window};
+use crate::api::source::Quote;
+use crate::error::Result;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Entry<T> {
+    fetched: DateTime<Utc>,
+    value: T,
+}
+
+impl<T> Entry<T> {
+    fn age(&self) -> Duration {
+        (Utc::now() - self.fetched).to_std().unwrap_or_default()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct Entries {
+    quotes: BTreeMap<String, Entry<Quote>>,
+    history: BTreeMap<String, Entry<History>>,
+}
+
+/// When the entries were last saved to the backing store, whether they have changed
+/// since, and how many saves have been started.
+#[derive(Debug, Default)]
+struct Saved {
+    at: Option<Instant>,
+    dirty: bool,
+    generation: u64,
+}
+
+/// Entries serialized for one save, numbered so an older save never replaces a newer one.
+struct Snapshot {
+    path: PathBuf,
+    generation: u64,
+    contents: String,
+}
+
+/// Cache of quotes and price history for a `DataAPI`.
+///
+/// Quotes and history each have their own time to live; within it, updates are answered
+/// from the cache without contacting the source. Once an entry has expired it is fetched
+/// again, but if the source fails the expired entry is still served for up to
+/// `max_stale` past its expiry. A served quote keeps the time it was originally fetched,
+/// so `CoinData::time` shows how old it is.
+///
+/// A cache with a backing `store` is reloaded when opened, so it survives restarts. It is
+/// saved after a fetch at most once every `save_interval`, and when it is flushed or
+/// dropped; each save replaces the file whole, so a crash mid-write leaves the old one.
+/// Saves after a fetch happen off the async runtime, and one that fails does not fail
+/// the fetch: the entries are saved again with the next save, or `flush` reports it.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use seventh_core::api::DataAPI;
+/// use seventh_core::api::cache::Cache;
+///
+/// let cache = Cache::new()
+///     .history_ttl(Duration::from_secs(600))
+///     .store("market.json")?;
+/// let data = DataAPI::new().with_cache(cache);
+/// # Ok::<(), seventh_core::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct Cache {
+    quote_ttl: Duration,
+    history_ttl: Duration,
+    max_stale: Duration,
+    save_interval: Duration,
+    path: Option<PathBuf>,
+    entries: Mutex<Entries>,
+    saved: Mutex<Saved>,
+    /// The generation of the last snapshot written, held while writing one.
+    written: Arc<Mutex<u64>>,
+}
+
+impl Cache {
+    /// An in-memory cache keeping quotes for one second and history for five minutes,
+    /// serving either for up to an hour past expiry while the source is failing.
+    pub fn new() -> Self {
+        Cache {
+            quote_ttl: Duration::from_secs(1),
+            history_ttl: Duration::from_secs(300),
+            max_stale: Duration::from_secs(3600),
+            save_interval: Duration::from_secs(5),
+            path: None,
+            entries: Mutex::new(Entries::default()),
+            saved: Mutex::new(Saved::default()),
+            written: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    /// Set how long a quote is served before it is fetched again.
+    pub fn quote_ttl(mut self, ttl: Duration) -> Self {
+        self.quote_ttl = ttl;
+        self
+    }
+
+    /// Set how long price history is served before it is fetched again.
+    pub fn history_ttl(mut self, ttl: Duration) -> Self {
+        self.history_ttl = ttl;
+        self
+    }
+
+    /// Set how long past expiry an entry may be served while the source is failing.
+    pub fn max_stale(mut self, max_stale: Duration) -> Self {
+        self.max_stale = max_stale;
+        self
+    }
+
+    /// Set how often fetched entries are saved to the backing store, which is every five
+    /// seconds by default. Entries fetched in between are saved with the next save.
+    pub fn save_interval(mut self, interval: Duration) -> Self {
+        self.save_interval = interval;
+        self
+    }
+
+    /// Back the cache with the JSON file at `path`, loading any entries already in it.
+    pub fn store<P: AsRef<Path>>(mut self, path: P) -> Result<Self> {
+        let path = path.as_ref().to_owned();
+        if path.exists() {
+            self.entries = Mutex::new(serde_json::from_str(&fs::read_to_string(&path)?)?);
+        }
+        self.path = Some(path);
+        Ok(self)
+    }
+
+    /// Drop every cached entry.
+    pub fn clear(&self) -> Result<()> {
+        let snapshot = {
+            let mut entries = self.entries.lock().expect("Lock cache");
+            *entries = Entries::default();
+            self.changed(&entries, true)
+        };
+        self.write(snapshot)
+    }
+
+    /// Save any entries fetched since the last save to the backing store.
+    pub fn flush(&self) -> Result<()> {
+        let snapshot = {
+            let entries = self.entries.lock().expect("Lock cache");
+            let mut saved = self.saved.lock().expect("Lock cache");
+            self.snapshot(&entries, &mut saved)
+        };
+        self.write(snapshot)
+    }
+
+    pub(crate) async fn quote<F>(&self, pair: &Pair, fetch: F) -> Result<Quote>
+    where
+        F: Future<Output = Result<Quote>>,
+    {
+        self.get_or_fetch(pair.to_string(), self.quote_ttl, |e| &mut e.quotes, fetch).await
+    }
+
+    pub(crate) async fn history<F>(&self, pair: &Pair, window: &Window, fetch: F) -> Result<History>
+    where
+        F: Future<Output = Result<History>>,
+    {
+        let key = format!("{}/{}", pair, window.lookback.num_seconds());
+        self.get_or_fetch(key, self.history_ttl, |e| &mut e.history, fetch).await
+    }
+
+    async fn get_or_fetch<T, F>(
+        &self,
+        key: String,
+        ttl: Duration,
+        select: fn(&mut Entries) -> &mut BTreeMap<String, Entry<T>>,
+        fetch: F,
+    ) -> Result<T>
+    where
+        T: Clone,
+        F: Future<Output = Result<T>>,
+    {
+        {
+            let mut entries = self.entries.lock().expect("Lock cache");
+            if let Some(entry) = select(&mut entries).get(&key).filter(|e| e.age() <= ttl) {
+                return Ok(entry.value.clone());
+            }
+        }
+
+        let fetched = fetch.await;
+        let (result, snapshot) = {
+            let mut entries = self.entries.lock().expect("Lock cache");
+            match fetched {
+                Ok(value) => {
+                    let entry = Entry { fetched: Utc::now(), value: value.clone() };
+                    select(&mut entries).insert(key, entry);
+                    (Ok(value), self.changed(&entries, false))
+                }
+                Err(e) => match select(&mut entries).get(&key) {
+                    Some(entry) if entry.age() <= ttl + self.max_stale => (Ok(entry.value.clone()), None),
+                    _ => (Err(e), None),
+                },
+            }
+        };
+
+        if let Some(snapshot) = snapshot {
+            let written = self.written.clone();
+            let saved = tokio::task::spawn_blocking(move || write_snapshot(&written, snapshot)).await;
+            if !matches!(saved, Ok(Ok(()))) {
+                self.saved.lock().expect("Lock cache").dirty = true;
+            }
+        }
+        result
+    }
+
+    /// Mark `entries` as changed, and snapshot them if the save is `forced` or the last
+    /// one was long enough ago.
+    fn changed(&self, entries: &Entries, forced: bool) -> Option<Snapshot> {
+        let mut saved = self.saved.lock().expect("Lock cache");
+        saved.dirty = true;
+        if !forced && saved.at.is_some_and(|at| at.elapsed() < self.save_interval) {
+            return None;
+        }
+        self.snapshot(entries, &mut saved)
+    }
+
+    /// Snapshot `entries` for saving, if there is a backing store and they have changed
+    /// since the last save.
+    fn snapshot(&self, entries: &Entries, saved: &mut Saved) -> Option<Snapshot> {
+        let path = self.path.clone().filter(|_| saved.dirty)?;
+        let contents = serde_json::to_string(entries).ok()?;
+
+        saved.generation += 1;
+        saved.at = Some(Instant::now());
+        saved.dirty = false;
+        Some(Snapshot { path, generation: saved.generation, contents })
+    }
+
+    /// Write `snapshot`, if any, marking the entries as still changed if that fails.
+    fn write(&self, snapshot: Option<Snapshot>) -> Result<()> {
+        let result = snapshot.map_or(Ok(()), |snapshot| write_snapshot(&self.written, snapshot));
+        if result.is_err() {
+            self.saved.lock().expect("Lock cache").dirty = true;
+        }
+        result
+    }
+}
+
+/// Write `snapshot` to a temporary file beside the store, then move it into place, unless
+/// a newer snapshot has already been written.
+fn write_snapshot(written: &Mutex<u64>, snapshot: Snapshot) -> Result<()> {
+    let mut written = written.lock().expect("Lock cache");
+    if *written > snapshot.generation {
+        return Ok(());
+    }
+
+    let mut temp = snapshot.path.clone().into_os_string();
+    temp.push(".tmp");
+    fs::write(&temp, snapshot.contents)?;
+    fs::rename(&temp, &snapshot.path)?;
+
+    *written = snapshot.generation;
+    Ok(())
+}
+
+impl Drop for Cache {
+    fn drop(&mut self) {
+        // Save what is pending, unless a panic left the cache poisoned; nothing is left to
+        // report a failed save to.
+        let snapshot = match (self.entries.lock(), self.saved.lock()) {
+            (Ok(entries), Ok(mut saved)) => self.snapshot(&entries, &mut saved),
+            _ => None,
+        };
+        if let Some(snapshot) = snapshot {
+            write_snapshot(&self.written, snapshot).ok();
+        }
+    }
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,73 @@
+//! OHLCV candles from the Coinbase Exchange API
+
+use std::ops::Range;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::historical::RawData;
+
+/// Most candles Coinbase returns for a single request.
+pub const MAX_CANDLES: i64 = 300;
+
+/// Candle widths supported by the Coinbase candles endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Granularity {
+    OneMinute,
+    FiveMinutes,
+    FifteenMinutes,
+    OneHour,
+    SixHours,
+    OneDay,
+}
+
+impl Granularity {
+    /// Width of one candle in seconds.
+    pub fn seconds(self) -> i64 {
+        match self {
+            Granularity::OneMinute => 60,
+            Granularity::FiveMinutes => 300,
+            Granularity::FifteenMinutes => 900,
+            Granularity::OneHour => 3600,
+            Granularity::SixHours => 21600,
+            Granularity::OneDay => 86400,
+        }
+    }
+
+    /// Width of one candle.
+    pub fn duration(self) -> Duration {
+        Duration::seconds(self.seconds())
+    }
+}
+
+/// A candle as Coinbase sends it: `[time, low, high, open, close, volume]`.
+pub(crate) type Row = (i64, f32, f32, f32, f32, f32);
+
+/// Split `range` into spans of at most `MAX_CANDLES` candles each.
+pub(crate) fn pages(range: &Range<DateTime<Utc>>, granularity: Granularity) -> Vec<Range<DateTime<Utc>>> {
+    let span = granularity.duration() * MAX_CANDLES as i32;
+    let mut pages = vec![];
+    let mut start = range.start;
+    while start < range.end {
+        let end = std::cmp::min(start + span, range.end);
+        pages.push(start..end);
+        start = end;
+    }
+    pages
+}
+
+/// Collect candles from any number of pages into `RawData`, oldest first.
+///
+/// Coinbase sends each page newest first and may repeat the candle on a page boundary, so
+/// rows are sorted, deduplicated and trimmed to `range`.
+pub(crate) fn to_raw(mut rows: Vec<Row>, range: &Range<DateTime<Utc>>) -> RawData {
+    let (start, end) = (range.start.timestamp(), range.end.timestamp());
+    rows.retain(|row| row.0 >= start && row.0 < end);
+    rows.sort_by_key(|row| row.0);
+    rows.dedup_by_key(|row| row.0);
+
+    let mut raw = RawData::new();
+    for (_, low, high, open, close, volume) in rows {
+        raw.push(open, high, low, close, volume);
+    }
+    raw
+}
@@ -0,0 +1,302 @@
+//! Consensus prices from several sources, and warnings when one drifts from the rest
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::future;
+use tokio::sync::mpsc;
+
+use crate::api::money::{Decimal, Increments};
+use crate::api::pair::Pair;
+use crate::api::series::{History, Window};
+use crate::api::source::{PriceSource, Quote};
+use crate::error::{Error, Result};
+
+/// How the quotes from each source are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// The median of each price, which ignores a minority of bad sources.
+    Median,
+    /// The mean of each price weighted by each source's 24 hour volume.
+    ///
+    /// Sources that do not report a volume are left out of the average, and the median
+    /// is used if none of them do.
+    VolumeWeighted,
+}
+
+/// How far one source's quote is from the consensus.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Deviation {
+    /// Name the source was added under.
+    pub source: String,
+    /// The quote the source gave.
+    pub quote: Quote,
+    /// Difference between the source's spot price and the consensus spot price, as a
+    /// fraction of the consensus.
+    pub deviation: Decimal,
+}
+
+/// A source that could not give a quote.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Failure {
+    /// Name the source was added under.
+    pub source: String,
+    /// Why it failed.
+    pub error: String,
+}
+
+/// The consensus for a pair, and how each source compared with it.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Report {
+    pub pair: Pair,
+    /// The combined quote. Its time is that of the oldest quote used, and its volume is
+    /// the total reported.
+    pub quote: Quote,
+    /// Every source that answered, in the order they were added.
+    pub sources: Vec<Deviation>,
+    /// Every source that failed, in the order they were added.
+    pub failed: Vec<Failure>,
+}
+
+impl Report {
+    /// Get the sources further from the consensus than `threshold`.
+    pub fn divergent(&self, threshold: Decimal) -> impl Iterator<Item = &Deviation> {
+        self.sources.iter().filter(move |d| d.deviation.abs() > threshold)
+    }
+}
+
+/// Raised when a source's spot price drifts further from the consensus than allowed.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Divergence {
+    pub pair: Pair,
+    /// Name the source was added under.
+    pub source: String,
+    /// The source's spot price.
+    pub spot: Decimal,
+    /// The consensus spot price.
+    pub consensus: Decimal,
+    /// Difference between the two, as a fraction of the consensus.
+    pub deviation: Decimal,
+    pub time: DateTime<Utc>,
+}
+
+/// A `PriceSource` combining the quotes of several others.
+///
+/// Every quote asks all of the sources at once and combines the answers with the
+/// consensus `Method`, the median by default. Sources that fail are skipped, as long as at
+/// least `min_sources` answer; otherwise the quote fails with `Error::NoConsensus`.
+/// History and increments come from the first source added.
+///
+/// After each quote, any source whose spot price is more than `threshold` from the
+/// consensus, 2% by default, is sent as a `Divergence` to every receiver from
+/// `divergences`, so a bad feed is noticed before it is traded on. `report` gives the full
+/// comparison.
+///
+/// # Example
+///
+/// ```no_run
+/// use seventh_core::api::consensus::{Consensus, Method};
+/// use seventh_core::api::source::{GeckoSource, WebSource};
+/// use seventh_core::api::{DataAPI, Decimal};
+///
+/// # async fn run() -> seventh_core::Result<()> {
+/// let consensus = Consensus::new()
+///     .source("coinbase", WebSource::new())
+///     .source("coingecko", GeckoSource::new())
+///     .method(Method::VolumeWeighted)
+///     .threshold(Decimal::new(1, 2));
+/// let mut divergences = consensus.divergences();
+///
+/// let mut data = DataAPI::with_source(consensus);
+/// data.update("BTC").await?;
+/// while let Ok(divergence) = divergences.try_recv() {
+///     println!("{} is {} away from the consensus", divergence.source, divergence.deviation);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Consensus {
+    sources: Vec<(String, Box<dyn PriceSource>)>,
+    method: Method,
+    threshold: Decimal,
+    min_sources: usize,
+    reports: Mutex<HashMap<Pair, Report>>,
+    listeners: Mutex<Vec<mpsc::UnboundedSender<Divergence>>>,
+}
+
+impl Consensus {
+    /// Create a consensus with no sources, using the median and a 2% threshold.
+    pub fn new() -> Self {
+        Consensus {
+            sources: vec![],
+            method: Method::Median,
+            threshold: Decimal::new(2, 2),
+            min_sources: 1,
+            reports: Mutex::new(HashMap::new()),
+            listeners: Mutex::new(vec![]),
+        }
+    }
+
+    /// Add `source` under `name`, which identifies it in reports and divergences.
+    pub fn source<S: PriceSource + 'static>(mut self, name: &str, source: S) -> Self {
+        self.sources.push((name.to_owned(), Box::new(source)));
+        self
+    }
+
+    /// Set how the quotes are combined.
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Raise a divergence for sources more than `fraction` away from the consensus.
+    pub fn threshold(mut self, fraction: Decimal) -> Self {
+        self.threshold = fraction;
+        self
+    }
+
+    /// Fail unless at least `count` sources answer.
+    pub fn min_sources(mut self, count: usize) -> Self {
+        self.min_sources = count.max(1);
+        self
+    }
+
+    /// Get the names of the sources, in the order they were added.
+    pub fn names(&self) -> Vec<&str> {
+        self.sources.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Receive a `Divergence` for every source that drifts past the threshold from now on.
+    pub fn divergences(&self) -> mpsc::UnboundedReceiver<Divergence> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.listeners.lock().expect("Lock listeners").push(sender);
+        receiver
+    }
+
+    /// Ask every source for a quote for `pair` and compare them with the consensus.
+    pub async fn report(&self, pair: &Pair) -> Result<Report> {
+        let quotes = future::join_all(self.sources.iter().map(|(_, source)| source.quote(pair))).await;
+
+        let mut answered = vec![];
+        let mut failed = vec![];
+        for ((name, _), quote) in self.sources.iter().zip(quotes) {
+            match quote {
+                Ok(quote) => answered.push((name.clone(), quote)),
+                Err(e) => failed.push(Failure { source: name.clone(), error: e.to_string() }),
+            }
+        }
+
+        if answered.len() < self.min_sources {
+            let failures: Vec<String> = failed.iter().map(|f| format!("{}: {}", f.source, f.error)).collect();
+            return Err(Error::NoConsensus(format!(
+                "{} of {} required sources answered for {} ({})",
+                answered.len(),
+                self.min_sources,
+                pair,
+                failures.join(", ")
+            )));
+        }
+
+        let quotes: Vec<Quote> = answered.iter().map(|(_, quote)| *quote).collect();
+        let quote = combine(&quotes, self.method);
+        let sources = answered
+            .into_iter()
+            .map(|(source, q)| Deviation { source, quote: q, deviation: deviation(q.spot, quote.spot) })
+            .collect();
+
+        Ok(Report { pair: pair.clone(), quote, sources, failed })
+    }
+
+    /// Get the report from the most recent quote for `pair`, if there has been one.
+    pub fn last_report(&self, pair: &Pair) -> Option<Report> {
+        self.reports.lock().expect("Lock reports").get(pair).cloned()
+    }
+
+    fn raise(&self, report: &Report) {
+        let mut listeners = self.listeners.lock().expect("Lock listeners");
+        for source in report.divergent(self.threshold) {
+            let divergence = Divergence {
+                pair: report.pair.clone(),
+                source: source.source.clone(),
+                spot: source.quote.spot,
+                consensus: report.quote.spot,
+                deviation: source.deviation,
+                time: report.quote.time,
+            };
+            // Receivers that have been dropped are forgotten.
+            listeners.retain(|listener| listener.send(divergence.clone()).is_ok());
+        }
+    }
+}
+
+impl Default for Consensus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl PriceSource for Consensus {
+    /// Returns the consensus quote, raising divergences and keeping the report.
+    async fn quote(&self, pair: &Pair) -> Result<Quote> {
+        let report = self.report(pair).await?;
+        self.raise(&report);
+
+        let quote = report.quote;
+        self.reports.lock().expect("Lock reports").insert(pair.clone(), report);
+        Ok(quote)
+    }
+
+    async fn historical(&self, pair: &Pair, window: &Window) -> Result<History> {
+        match self.sources.first() {
+            Some((_, source)) => source.historical(pair, window).await,
+            None => Err(Error::NoConsensus(format!("no sources for {}", pair))),
+        }
+    }
+
+    fn increments(&self, pair: &Pair) -> Increments {
+        match self.sources.first() {
+            Some((_, source)) => source.increments(pair),
+            None => Increments::default(),
+        }
+    }
+}
+
+fn deviation(price: Decimal, consensus: Decimal) -> Decimal {
+    if consensus.is_zero() {
+        return Decimal::ZERO;
+    }
+    (price - consensus) / consensus
+}
+
+/// Combine at least one quote into one.
+fn combine(quotes: &[Quote], method: Method) -> Quote {
+    let weights: Vec<Decimal> = quotes.iter().map(|q| q.volume.unwrap_or_default()).collect();
+    let total: Decimal = weights.iter().sum();
+    let price = |get: fn(&Quote) -> Decimal| -> Decimal {
+        let prices: Vec<Decimal> = quotes.iter().map(get).collect();
+        match method {
+            Method::VolumeWeighted if total > Decimal::ZERO => {
+                prices.iter().zip(&weights).map(|(p, w)| p * w).sum::<Decimal>() / total
+            }
+            _ => median(prices),
+        }
+    };
+
+    let mut quote = Quote::new(price(|q| q.buy), price(|q| q.sell), price(|q| q.spot));
+    quote.time = quotes.iter().map(|q| q.time).min().unwrap_or(quote.time);
+    quote.volume = quotes.iter().filter_map(|q| q.volume).fold(None, |sum, v| Some(sum.unwrap_or_default() + v));
+    quote
+}
+
+fn median(mut prices: Vec<Decimal>) -> Decimal {
+    prices.sort();
+    let middle = prices.len() / 2;
+    if prices.len() % 2 == 0 {
+        (prices[middle - 1] + prices[middle]) / Decimal::TWO
+    } else {
+        prices[middle]
+    }
+}
@@ -1,37 +1,31 @@
 //! Retrieve data from Coinbase and CoinGecko APIs
 
+use std::collections::vec_deque::{self, VecDeque};
 use std::fmt;
-use chrono::Utc;
-use phf::{Map, phf_map};
+use std::mem;
+use std::ops::Range;
 
-const UNIX_DAY: i64 = 86400;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
 
-static COIN_ID: Map<&'static str, &'static str> = phf_map! {
-    "BTC" => "bitcoin",
-    "ETH" => "ethereum",
-    "ETC" => "ethereum-classic",
-    "LTC" => "litecoin",
-};
+use crate::api::book::OrderBook;
+use crate::api::cache::Cache;
+use crate::api::candles::Granularity;
+use crate::api::endpoints::Endpoints;
+use crate::api::money::Decimal;
+use crate::api::pair::Pair;
+use crate::api::retention::Retention;
+use crate::api::series::{History, Point, Window};
+use crate::api::source::{PriceSource, Quote, WebSource};
+use crate::api::validation::{Anomaly, Validation};
+use crate::error::{Error, Result};
+use crate::historical::RawData;
 
-#[derive(Deserialize, Debug)]
-struct Price {
-    base: String,
-    currency: String,
-    amount: String,
-}
-
-#[derive(Deserialize, Debug)]
-struct PriceData {
-    data: Price
-}
-
-#[derive(Deserialize, Debug)]
-struct HistoricalData {
-    prices: Vec<Vec<f32>>,
-    total_volumes: Vec<Vec<f32>>
-}
-
-/// Datatype for representing the instantaneous price data for a given cryptocurrency.
+/// Datatype for representing the instantaneous price data for a given cryptocurrency,
+/// priced in the quote currency `currency`.
+///
+/// Alongside the buy, sell and spot snapshot, a `CoinData` keeps the time the snapshot
+/// was taken and the recent price and volume series with their timestamps.
 ///
 /// A `CoinData` cannot be created directly, but is the format by which the DataAPI
 /// retrieves price data.
@@ -40,23 +34,42 @@ struct HistoricalData {
 #[derive(Serialize, Debug, PartialEq, Clone)]
 pub struct CoinData {
     ticker: String,
-    historical: Vec<f32>,
-    buy: f32,
-    sell: f32,
-    spot: f32,
+    currency: String,
+    time: DateTime<Utc>,
+    prices: Vec<Point>,
+    volumes: Vec<Point>,
+    buy: Decimal,
+    sell: Decimal,
+    spot: Decimal,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    anomalies: Vec<Anomaly>,
 }
 
 impl CoinData {
-    fn new(buyprice: Price, sellprice: Price, spotprice: Price, history: HistoricalData) -> Self {
+    pub(crate) fn new(pair: &Pair, quote: Quote, history: History) -> Self {
         CoinData {
-            ticker: spotprice.base,
-            historical: history.prices.into_iter().map(|v| v[1]).collect(),
-            buy: buyprice.amount.parse().unwrap(),
-            sell: sellprice.amount.parse().unwrap(),
-            spot: spotprice.amount.parse().unwrap()
+            ticker: pair.base.clone(),
+            currency: pair.quote.clone(),
+            time: quote.time,
+            prices: history.prices,
+            volumes: history.volumes,
+            buy: quote.buy,
+            sell: quote.sell,
+            spot: quote.spot,
+            anomalies: vec![],
         }
     }
 
+    /// Get the trading pair this CoinData was retrieved for.
+    pub fn pair(&self) -> Pair {
+        Pair::new(&self.ticker, &self.currency)
+    }
+
+    /// Get the quote currency all prices in this CoinData are in.
+    pub fn currency(&self) -> &str {
+        &self.currency
+    }
+
     /// Get buy price from CoinData
     ///
     /// # Example
@@ -64,13 +77,16 @@ impl CoinData {
     /// ```no_run
     /// use seventh_core::api::{CoinData, DataAPI};
     ///
+    /// # async fn run() -> seventh_core::Result<()> {
     /// let mut data = DataAPI::new();
-    /// data.update("BTC");
+    /// data.update("BTC").await?;
     ///
-    /// let coin: CoinData = data.last();
+    /// let coin: &CoinData = data.last_ref().unwrap();
     /// println!("{}", coin.buyprice());
+    /// # Ok(())
+    /// # }
     /// ```
-    pub fn buyprice(&self) -> f32 {
+    pub fn buyprice(&self) -> Decimal {
         self.buy
     }
 
@@ -81,16 +97,24 @@ impl CoinData {
     /// ```no_run
     /// use seventh_core::api::{CoinData, DataAPI};
     ///
+    /// # async fn run() -> seventh_core::Result<()> {
     /// let mut data = DataAPI::new();
-    /// data.update("BTC");
+    /// data.update("BTC").await?;
     ///
-    /// let coin: CoinData = data.last();
+    /// let coin: &CoinData = data.last_ref().unwrap();
     /// println!("{}", coin.sellprice());
+    /// # Ok(())
+    /// # }
     /// ```
-    pub fn sellprice(&self) -> f32 {
+    pub fn sellprice(&self) -> Decimal {
         self.sell
     }
 
+    /// Get the spot price from CoinData.
+    pub fn spotprice(&self) -> Decimal {
+        self.spot
+    }
+
     /// Get historical prices from CoinData
     ///
     /// # Example
@@ -98,21 +122,59 @@ impl CoinData {
     /// ```no_run
     /// use seventh_core::api::{CoinData, DataAPI};
     ///
+    /// # async fn run() -> seventh_core::Result<()> {
     /// let mut data = DataAPI::new();
-    /// data.update("BTC");
+    /// data.update("BTC").await?;
     ///
-    /// let coin: CoinData = data.last();
+    /// let coin: &CoinData = data.last_ref().unwrap();
     /// println!("{:?}", coin.historical());
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn historical(&self) -> Vec<f32> {
-        self.historical.clone()
+        self.prices.iter().map(|p| p.value).collect()
+    }
+
+    /// Get historical prices with their timestamps, oldest first.
+    pub fn prices(&self) -> &[Point] {
+        &self.prices
+    }
+
+    /// Get the traded volume at each timestamp, oldest first.
+    pub fn volumes(&self) -> &[Point] {
+        &self.volumes
+    }
+
+    /// Get the time the buy, sell and spot prices were taken.
+    pub fn time(&self) -> DateTime<Utc> {
+        self.time
+    }
+
+    /// Get the anomalies the `DataAPI`'s `Validation` flagged in this snapshot.
+    pub fn anomalies(&self) -> &[Anomaly] {
+        &self.anomalies
+    }
+
+    /// Whether validation flagged nothing in this snapshot.
+    pub fn is_valid(&self) -> bool {
+        self.anomalies.is_empty()
+    }
+
+    /// Approximate memory taken up by this CoinData, in bytes.
+    fn size(&self) -> usize {
+        mem::size_of::<Self>()
+            + self.ticker.capacity()
+            + self.currency.capacity()
+            + (self.prices.capacity() + self.volumes.capacity()) * mem::size_of::<Point>()
+            + self.anomalies.capacity() * mem::size_of::<Anomaly>()
     }
 }
 
 impl fmt::Display for CoinData {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "CoinData: ticker {}, buy {}, sell {}, spot {}",
+        write!(f, "CoinData: ticker {}, currency {}, buy {}, sell {}, spot {}",
             self.ticker,
+            self.currency,
             self.buy,
             self.sell,
             self.spot
@@ -122,113 +184,372 @@ impl fmt::Display for CoinData {
 
 /// Active API for retrieving cryptocurrency price data.
 ///
-/// Maintains both an active reqwest client as well as a vector of `CoinData` structs.
-/// The API is coin-agnostic, but currently supports only BTC, ETC, ETH, and LTC, due to
-/// limitations of the CoinGecko API.
-pub struct DataAPI {
-    client: reqwest::Client,
-    coins: Vec<CoinData>
+/// Maintains both a `PriceSource` as well as the `CoinData` snapshots fetched from it,
+/// pruned according to a `Retention` policy that keeps the last 1000 by default. By default
+/// the source is `WebSource`, which reads from Coinbase and CoinGecko; any other
+/// `PriceSource` can be supplied with `DataAPI::with_source`.
+///
+/// Each update fetches the history covered by the `DataAPI`'s `Window`, which defaults to
+/// the last day at the source's resolution. With a `Cache`, updates reuse recent quotes
+/// and history instead of fetching them again. Every snapshot is checked against the
+/// `Validation`, which by default flags crossed, non-positive and stale prices.
+pub struct DataAPI<S: PriceSource = WebSource> {
+    source: S,
+    window: Window,
+    concurrency: usize,
+    cache: Option<Cache>,
+    retention: Retention,
+    validation: Validation,
+    coins: VecDeque<CoinData>,
+    bytes: usize,
 }
 
+/// Pairs fetched at once by `DataAPI::update_many`, unless set with `with_concurrency`.
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
 impl DataAPI {
-    #[tokio::main]
-    async fn get<T: for<'de> serde::Deserialize<'de>>(&self, endpoint: String) -> Result<T, reqwest::Error> {
-        let response = self.client.get(&endpoint).send().await?;
-        let result: T = response.json().await?;
+    /// Create a new `DataAPI`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use seventh_core::api::DataAPI;
+    ///
+    /// let mut data = DataAPI::new();
+    /// assert!(data.is_empty());
+    /// ```
+    pub fn new() -> Self {
+        DataAPI::with_source(WebSource::new())
+    }
 
-        Ok(result)
+    /// Create a new `DataAPI` reading from the Coinbase and CoinGecko APIs at `endpoints`.
+    pub fn with_endpoints(endpoints: Endpoints) -> Result<Self> {
+        Ok(DataAPI::with_source(WebSource::with_endpoints(endpoints)?))
     }
 
-    fn get_coinbase(&self, coin: &str, query: &str) -> Price {
-        let endpoint = format!("https://api.coinbase.com/v2/prices/{}-USD/{}", coin, query);
-        let result = self.get::<PriceData>(endpoint.clone());
+    /// Get open, high, low, close and volume candles for `pair` covering `range`.
+    ///
+    /// Candles are read from the Coinbase Exchange API, oldest first, in the same
+    /// `RawData` form that `historical::run` reads from CSV files. They are returned
+    /// directly and not stored in the `DataAPI`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use chrono::{Duration, Utc};
+    /// use seventh_core::api::{DataAPI, Granularity};
+    ///
+    /// # async fn run() -> seventh_core::Result<()> {
+    /// let data = DataAPI::new();
+    /// let end = Utc::now();
+    /// let candles = data.candles("BTC-EUR", Granularity::OneHour, end - Duration::days(30)..end).await?;
+    /// println!("{:?}", candles.close());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn candles<P: Into<Pair>>(&self, pair: P, granularity: Granularity, range: Range<DateTime<Utc>>) -> Result<RawData> {
+        self.source.candles(&pair.into(), granularity, range).await
+    }
 
-        match result {
-            Ok(price_data) => price_data.data,
-            Err(e) => panic!("Error calling Coinbase API: {}", e)
-        }
+    /// Get the current level 2 order book for `pair` from the Coinbase Exchange API.
+    ///
+    /// Like candles, the book is returned directly and not stored in the `DataAPI`. Use a
+    /// `book::BookFeed` to keep books current instead of polling.
+    pub async fn order_book<P: Into<Pair>>(&self, pair: P) -> Result<OrderBook> {
+        self.source.order_book(&pair.into()).await
     }
+}
 
-    fn get_historical(&self, coin: &str) -> HistoricalData {
-        let curr_time = Utc::now().timestamp();
-        let endpoint = format!("https://api.coingecko.com/api/v3/coins/{}/market_chart/range?vs_currency=usd&from={}&to={}", coin, curr_time - UNIX_DAY, curr_time);
-        let result = self.get::<HistoricalData>(endpoint);
+impl Default for DataAPI {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        match result {
-            Ok(historical_data) => historical_data,
-            Err(e) => panic!("Error calling CoinGecko API: {}", e)
+impl<S: PriceSource> DataAPI<S> {
+    /// Create a new `DataAPI` reading from `source`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use seventh_core::api::{DataAPI, ScriptedSource};
+    ///
+    /// let data = DataAPI::with_source(ScriptedSource::new());
+    /// assert!(data.is_empty());
+    /// ```
+    pub fn with_source(source: S) -> Self {
+        DataAPI {
+            source,
+            window: Window::default(),
+            concurrency: DEFAULT_CONCURRENCY,
+            cache: None,
+            retention: Retention::default(),
+            validation: Validation::default(),
+            coins: VecDeque::new(),
+            bytes: 0,
         }
     }
 
-    /// Create a new `DataAPI`.
+    /// Set how many of the fetched snapshots are kept.
+    ///
+    /// The new policy applies from the next update.
+    pub fn with_retention(mut self, retention: Retention) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Get the policy deciding how many fetched snapshots are kept.
+    pub fn retention(&self) -> &Retention {
+        &self.retention
+    }
+
+    /// Set the checks each fetched snapshot must pass.
+    pub fn with_validation(mut self, validation: Validation) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    /// Get the checks each fetched snapshot must pass.
+    pub fn validation(&self) -> &Validation {
+        &self.validation
+    }
+
+    /// Set the lookback and granularity of the history fetched by `update`.
+    pub fn with_window(mut self, window: Window) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Get the lookback and granularity of the history fetched by `update`.
+    pub fn window(&self) -> &Window {
+        &self.window
+    }
+
+    /// Answer updates from `cache` where it holds fresh enough data.
+    pub fn with_cache(mut self, cache: Cache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Get the cache in front of the source, if there is one.
+    pub fn cache(&self) -> Option<&Cache> {
+        self.cache.as_ref()
+    }
+
+    /// Set how many pairs `update_many` fetches at once.
+    ///
+    /// A limit of zero is treated as one.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Get the `PriceSource` backing this `DataAPI`.
+    pub fn source(&self) -> &S {
+        &self.source
+    }
+
+    /// Update the DataAPI with the data for `pair` at the current time.
+    ///
+    /// `pair` can be a `Pair` or a string such as "BTC-EUR"; a bare ticker such as "BTC"
+    /// is quoted in USD.
+    ///
+    /// If the underlying `PriceSource` fails, or the snapshot is rejected by validation,
+    /// the error is returned and no `CoinData` is stored.
     ///
     /// # Example
     ///
     /// ```no_run
     /// use seventh_core::api::DataAPI;
     ///
+    /// # async fn run() -> seventh_core::Result<()> {
     /// let mut data = DataAPI::new();
-    /// assert_eq!(data.coins().len(), 0);
+    /// assert_eq!(data.len(), 0);
+    /// data.update("BTC").await?; // Gets current data for BTC-USD
+    /// data.update("ETH-EUR").await?;
+    /// assert_eq!(data.len(), 2);
+    /// # Ok(())
+    /// # }
     /// ```
-    pub fn new() -> Self {
-        DataAPI { client: reqwest::Client::new(), coins: vec![] }
+    pub async fn update<P: Into<Pair>>(&mut self, pair: P) -> Result<()> {
+        let coin = self.fetch(&pair.into()).await?;
+        self.store(coin)
     }
 
-    /// Update the DataAPI with the data for `coin` at the current time.
+    /// Update the DataAPI with the data for each of `pairs`, fetching them concurrently.
     ///
-    /// If the connection to either the Coinbase or CoinGecko fails, the method panics.
+    /// At most `with_concurrency` pairs are in flight at once. Every pair is attempted, and
+    /// its result is returned in the same order as `pairs`; the `CoinData` for each
+    /// successful pair is stored in that order too, so a failing pair does not hold back
+    /// the rest.
     ///
     /// # Example
     ///
     /// ```no_run
     /// use seventh_core::api::DataAPI;
     ///
+    /// # async fn run() {
     /// let mut data = DataAPI::new();
-    /// assert_eq!(data.coins().len(), 0);
-    /// data.update("BTC"); // Gets current data for BTC-USD
-    /// assert_eq!(data.coins().len(), 1);
+    /// for (pair, result) in data.update_many(&["BTC", "ETH-EUR", "LTC"]).await {
+    ///     if let Err(e) = result {
+    ///         println!("{}: {}", pair, e);
+    ///     }
+    /// }
+    /// # }
     /// ```
-    pub fn update(&mut self, coin: &str) {
-        let buy = self.get_coinbase(coin, "buy");
-        let sell = self.get_coinbase(coin, "sell");
-        let spot = self.get_coinbase(coin, "spot");
-        let history = self.get_historical(COIN_ID[coin]);
-        self.coins.push(CoinData::new(buy, sell, spot, history))
+    pub async fn update_many<P: Into<Pair> + Clone>(&mut self, pairs: &[P]) -> Vec<(Pair, Result<()>)> {
+        let pairs: Vec<Pair> = pairs.iter().cloned().map(Into::into).collect();
+        let fetched: Vec<Result<CoinData>> = stream::iter(&pairs)
+            .map(|pair| self.fetch(pair))
+            .buffered(self.concurrency)
+            .collect()
+            .await;
+
+        pairs
+            .into_iter()
+            .zip(fetched)
+            .map(|(pair, coin)| {
+                let result = coin.and_then(|coin| self.store(coin));
+                (pair, result)
+            })
+            .collect()
     }
 
-    /// Get list of all `CoinData` items stored in `DataAPI`
-    ///
-    /// This method is not reccomended to be used directly.
+    async fn fetch(&self, pair: &Pair) -> Result<CoinData> {
+        let (quote, mut history) = tokio::try_join!(self.quote(pair), self.history(pair))?;
+        if let Some(granularity) = self.window.granularity {
+            history = history.resample(granularity);
+        }
+        Ok(CoinData::new(pair, quote, history))
+    }
+
+    fn store(&mut self, mut coin: CoinData) -> Result<()> {
+        let anomalies = self.validation.check(&coin, self.latest(coin.pair()));
+        if self.validation.reject && !anomalies.is_empty() {
+            return Err(Error::Invalid(anomalies));
+        }
+        coin.anomalies = anomalies;
+
+        self.bytes += coin.size();
+        self.coins.push_back(coin);
+
+        let now = Utc::now();
+        while self.coins.len() > 1 {
+            let oldest = &self.coins[0];
+            let age = (now - oldest.time).to_std().unwrap_or_default();
+            let too_many = matches!(self.retention.count, Some(n) if self.coins.len() > n);
+            let too_old = matches!(self.retention.max_age, Some(max) if age > max);
+            let too_big = matches!(self.retention.max_bytes, Some(n) if self.bytes > n);
+            if !(too_many || too_old || too_big) {
+                break;
+            }
+
+            let dropped = self.coins.pop_front().expect("More than one snapshot");
+            self.bytes -= dropped.size();
+        }
+        Ok(())
+    }
+
+    async fn quote(&self, pair: &Pair) -> Result<Quote> {
+        match &self.cache {
+            Some(cache) => cache.quote(pair, self.source.quote(pair)).await,
+            None => self.source.quote(pair).await,
+        }
+    }
+
+    async fn history(&self, pair: &Pair) -> Result<History> {
+        let fetch = self.source.historical(pair, &self.window);
+        match &self.cache {
+            Some(cache) => cache.history(pair, &self.window, fetch).await,
+            None => fetch.await,
+        }
+    }
+
+    /// Get the `CoinData` generated by the most recent successful call to `update`, or
+    /// `None` before the first.
     ///
     /// # Example
     ///
     /// ```no_run
     /// use seventh_core::api::DataAPI;
     ///
+    /// # async fn run() -> seventh_core::Result<()> {
     /// let mut data = DataAPI::new();
-    /// data.update("BTC")
-    /// data.update("ETC")
-    /// println!("{:?}", data.coins());
+    /// data.update("BTC").await?;
+    /// data.update("ETC").await?;
+    /// if let Some(coin) = data.last_ref() {
+    ///     println!("{}", coin);
+    /// }
+    /// # Ok(())
+    /// # }
     /// ```
-    pub fn coins(&self) -> Vec<CoinData> {
-        self.coins.clone()
+    pub fn last_ref(&self) -> Option<&CoinData> {
+        self.coins.back()
     }
 
-    /// Get last `CoinData` itemsstored in `DataAPI`
-    ///
-    /// This method returns the CoinData generated by the most recent call to `update`.
+    /// Get the most recent `CoinData` stored for `pair`.
     ///
     /// # Example
     ///
     /// ```no_run
     /// use seventh_core::api::DataAPI;
     ///
+    /// # async fn run() -> seventh_core::Result<()> {
     /// let mut data = DataAPI::new();
-    /// data.update("BTC")
-    /// data.update("ETC")
-    /// println!("{}", data.last());
+    /// data.update_many(&["BTC", "ETH"]).await;
+    /// if let Some(coin) = data.latest("BTC") {
+    ///     println!("{}", coin.buyprice());
+    /// }
+    /// # Ok(())
+    /// # }
     /// ```
+    pub fn latest<P: Into<Pair>>(&self, pair: P) -> Option<&CoinData> {
+        let pair = pair.into();
+        self.coins.iter().rev().find(|c| c.ticker == pair.base && c.currency == pair.quote)
+    }
+
+    /// Iterate over the stored `CoinData`, oldest first.
+    pub fn iter(&self) -> vec_deque::Iter<'_, CoinData> {
+        self.coins.iter()
+    }
+
+    /// Iterate over the stored `CoinData` for `pair`, oldest first.
+    pub fn iter_pair<P: Into<Pair>>(&self, pair: P) -> impl Iterator<Item = &CoinData> {
+        let pair = pair.into();
+        self.coins.iter().filter(move |c| c.ticker == pair.base && c.currency == pair.quote)
+    }
+
+    /// Number of `CoinData` snapshots stored.
+    pub fn len(&self) -> usize {
+        self.coins.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.coins.is_empty()
+    }
+
+    /// Get a copy of every `CoinData` stored in `DataAPI`.
+    #[deprecated(note = "clones every snapshot; use `iter` or `latest` instead")]
+    pub fn coins(&self) -> Vec<CoinData> {
+        self.coins.iter().cloned().collect()
+    }
+
+    /// Get a copy of the last `CoinData` stored in `DataAPI`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if nothing has been stored yet.
+    #[deprecated(note = "panics before the first update; use `last_ref` instead")]
     pub fn last(&self) -> CoinData {
-        self.coins.last().unwrap().clone()
+        self.coins.back().expect("No data stored").clone()
+    }
+}
+
+impl<'a, S: PriceSource> IntoIterator for &'a DataAPI<S> {
+    type Item = &'a CoinData;
+    type IntoIter = vec_deque::Iter<'a, CoinData>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
     }
 }
@@ -0,0 +1,146 @@
+//! Endpoint configuration for the exchanges behind the data and trading APIs
+
+use std::time::Duration;
+
+use crate::api::ratelimit::RateLimit;
+use crate::api::retry::RetryPolicy;
+use crate::error::Result;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Connection settings for a single HTTP provider.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provider {
+    /// Base URL that request paths are appended to, without a trailing slash.
+    pub base_url: String,
+    /// Timeout applied to each request sent to this provider.
+    pub timeout: Duration,
+    /// Limit on how fast requests are sent to this provider, if any.
+    pub rate_limit: Option<RateLimit>,
+    /// How failed requests to this provider are retried.
+    pub retry: RetryPolicy,
+}
+
+impl Provider {
+    /// A provider at `base_url` with the default timeout and retry policy and no rate limit.
+    pub fn new(base_url: &str) -> Self {
+        Provider {
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            timeout: DEFAULT_TIMEOUT,
+            rate_limit: None,
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Set the request timeout for this provider.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Limit how fast requests are sent to this provider.
+    pub fn rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
+    /// Set how failed requests to this provider are retried.
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Join `path` onto the base URL.
+    pub(crate) fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+}
+
+/// Where the data and trading APIs send their requests.
+///
+/// The default points at the production Coinbase and CoinGecko APIs, rate limited to stay
+/// within their public limits. `Endpoints::local` points every provider at one base URL,
+/// for running against a stand-in server, without rate limits or retries.
+///
+/// Cloned endpoints share their rate limits.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use seventh_core::api::DataAPI;
+/// use seventh_core::api::endpoints::{Endpoints, Provider};
+///
+/// let mut endpoints = Endpoints::sandbox();
+/// endpoints.coingecko = Provider::new("http://localhost:8080").timeout(Duration::from_secs(2));
+///
+/// let data = DataAPI::with_endpoints(endpoints)?;
+/// # Ok::<(), seventh_core::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Endpoints {
+    /// Coinbase API, used for prices and orders.
+    pub coinbase: Provider,
+    /// Coinbase Exchange API, used for the product list, candles and order book snapshots.
+    pub exchange: Provider,
+    /// CoinGecko API, used for price history.
+    pub coingecko: Provider,
+    /// Coinbase Exchange WebSocket feed, used for streaming prices.
+    pub feed: String,
+    /// `User-Agent` header sent with every request.
+    pub user_agent: String,
+}
+
+impl Endpoints {
+    /// The production Coinbase and CoinGecko APIs.
+    pub fn production() -> Self {
+        Endpoints {
+            coinbase: Provider::new("https://api.coinbase.com").rate_limit(RateLimit::per_second(10)),
+            exchange: Provider::new("https://api.exchange.coinbase.com").rate_limit(RateLimit::per_second(10)),
+            coingecko: Provider::new("https://api.coingecko.com/api/v3").rate_limit(RateLimit::per_minute(30)),
+            feed: "wss://ws-feed.exchange.coinbase.com".to_owned(),
+            user_agent: format!("seventh_core/{}", env!("CARGO_PKG_VERSION")),
+        }
+    }
+
+    /// The Coinbase sandboxes for orders and prices, with production CoinGecko history.
+    pub fn sandbox() -> Self {
+        Endpoints {
+            coinbase: Provider::new("https://api.sandbox.coinbase.com").rate_limit(RateLimit::per_second(10)),
+            exchange: Provider::new("https://api-public.sandbox.exchange.coinbase.com").rate_limit(RateLimit::per_second(10)),
+            feed: "wss://ws-feed-public.sandbox.exchange.coinbase.com".to_owned(),
+            ..Endpoints::production()
+        }
+    }
+
+    /// Send requests for every provider to `base_url`, without rate limits or retries.
+    ///
+    /// The feed is expected at the same address, over `ws://` in place of `http://`.
+    pub fn local(base_url: &str) -> Self {
+        let provider = Provider::new(base_url).retry(RetryPolicy::none());
+        let feed = provider.base_url.replacen("http", "ws", 1);
+
+        Endpoints {
+            coinbase: provider.clone(),
+            exchange: provider.clone(),
+            coingecko: provider,
+            feed,
+            ..Endpoints::production()
+        }
+    }
+
+    /// Build a `reqwest::Client` configured with these settings.
+    pub fn client(&self) -> Result<reqwest::Client> {
+        let client = reqwest::Client::builder()
+            .user_agent(self.user_agent.as_str())
+            .build()?;
+
+        Ok(client)
+    }
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Endpoints::production()
+    }
+}
@@ -0,0 +1,344 @@
+//! Streaming prices from the Coinbase Exchange WebSocket feed
+
+use std::collections::{HashMap, VecDeque};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures::{SinkExt, Stream, StreamExt};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot};
+use tokio_tungstenite::stream::Stream as MaybeTls;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use crate::api::data::CoinData;
+use crate::api::money::Decimal;
+use crate::api::pair::Pair;
+use crate::api::series::{History, Point};
+use crate::api::source::Quote;
+use crate::error::{Error, Result};
+
+/// Updates held for a consumer that is not keeping up; newer ones are dropped until it
+/// catches up.
+const BUFFER: usize = 1024;
+
+/// Trades kept for each pair.
+const MAX_TRADES: usize = 1000;
+
+/// Longest silence before the connection is assumed dead. The heartbeat channel sends a
+/// message every second.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+const MIN_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+type Socket = WebSocketStream<MaybeTls<TcpStream, tokio_tls::TlsStream<TcpStream>>>;
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum FeedMessage {
+    Ticker {
+        product_id: String,
+        price: String,
+        best_bid: String,
+        best_ask: String,
+        time: Option<DateTime<Utc>>,
+    },
+    Match {
+        product_id: String,
+        price: String,
+        size: String,
+        time: DateTime<Utc>,
+    },
+    Error {
+        message: String,
+        reason: Option<String>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// What is known about a single pair so far.
+#[derive(Default)]
+struct Book {
+    bid: Option<Decimal>,
+    ask: Option<Decimal>,
+    last: Option<Decimal>,
+    time: Option<DateTime<Utc>>,
+    prices: VecDeque<Point>,
+    volumes: VecDeque<Point>,
+}
+
+impl Book {
+    fn snapshot(&self, pair: &Pair) -> Option<CoinData> {
+        let quote = Quote::new(self.ask?, self.bid?, self.last?).at(self.time?);
+        let history = History::new(self.prices.iter().copied().collect(), self.volumes.iter().copied().collect());
+
+        Some(CoinData::new(pair, quote, history))
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    books: HashMap<Pair, Book>,
+    latest: HashMap<Pair, CoinData>,
+}
+
+/// Live prices from the Coinbase Exchange WebSocket feed.
+///
+/// A `TickerFeed` subscribes to the ticker, matches and heartbeat channels for its pairs
+/// and yields a `CoinData` each time a pair's best bid, best ask or last trade changes.
+/// `buy` is the best ask, `sell` the best bid and `spot` the last trade price. The price
+/// and volume series hold the trades seen since subscribing, up to the last 1000.
+///
+/// The connection runs as a task on the current Tokio runtime. If it drops or goes quiet
+/// it is reopened with exponential backoff, so the stream itself never ends while the
+/// `TickerFeed` is alive. Updates that the consumer does not read in time are dropped,
+/// but `latest` always has the most recent data for each pair.
+///
+/// # Example
+///
+/// ```no_run
+/// use futures::StreamExt;
+/// use seventh_core::api::Endpoints;
+/// use seventh_core::api::feed::TickerFeed;
+///
+/// # async fn run() {
+/// let mut feed = TickerFeed::subscribe(&Endpoints::production().feed, &["BTC-USD", "ETH-EUR"]);
+/// while let Some(coin) = feed.next().await {
+///     println!("{}", coin);
+/// }
+/// # }
+/// ```
+pub struct TickerFeed {
+    url: String,
+    pairs: Vec<Pair>,
+    updates: mpsc::Receiver<CoinData>,
+    shared: Arc<Mutex<Shared>>,
+    connection: Connection,
+}
+
+impl TickerFeed {
+    /// Subscribe to `pairs` on the feed at `url`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside a Tokio runtime.
+    pub fn subscribe<P: Into<Pair> + Clone>(url: &str, pairs: &[P]) -> Self {
+        let pairs: Vec<Pair> = pairs.iter().cloned().map(Into::into).collect();
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let (mut sender, updates) = mpsc::channel(BUFFER);
+
+        let books = shared.clone();
+        let channels = &["ticker", "matches", "heartbeat"];
+        let connection = Connection::open(url, channels, &pairs, move |text| {
+            match apply(&books, serde_json::from_str(text)?)? {
+                Some(coin) => {
+                    sender.try_send(coin).ok();
+                    Ok(true)
+                }
+                None => Ok(false),
+            }
+        });
+
+        TickerFeed { url: url.to_owned(), pairs, updates, shared, connection }
+    }
+
+    /// Get the feed URL this feed is subscribed to.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Get the pairs this feed is subscribed to.
+    pub fn pairs(&self) -> &[Pair] {
+        &self.pairs
+    }
+
+    /// Get the most recent data for `pair`, or `None` until its first ticker arrives.
+    pub fn latest<P: Into<Pair>>(&self, pair: P) -> Option<CoinData> {
+        let shared = self.shared.lock().expect("Lock feed");
+        shared.latest.get(&pair.into()).cloned()
+    }
+
+    /// Get why the connection last failed, or the last message that could not be read.
+    pub fn last_error(&self) -> Option<String> {
+        self.connection.last_error()
+    }
+}
+
+impl Stream for TickerFeed {
+    type Item = CoinData;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<CoinData>> {
+        self.updates.poll_recv(cx)
+    }
+}
+
+/// A subscription to the feed, kept open by a background task until dropped.
+///
+/// Every text message received is passed to the handler, which returns whether it carried
+/// market data. A message the handler fails on is skipped and its error kept as the last
+/// one, leaving the connection open. A connection that ends after delivering data is
+/// reopened straight away; otherwise reconnects back off exponentially.
+pub(crate) struct Connection {
+    last_error: Arc<Mutex<Option<String>>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl Connection {
+    pub(crate) fn open<H>(url: &str, channels: &[&str], pairs: &[Pair], handler: H) -> Self
+    where
+        H: FnMut(&str) -> Result<bool> + Send + 'static,
+    {
+        let products: Vec<String> = pairs.iter().map(Pair::to_string).collect();
+        let subscribe = serde_json::json!({
+            "type": "subscribe",
+            "product_ids": products,
+            "channels": channels
+        });
+
+        let last_error = Arc::new(Mutex::new(None));
+        let (shutdown, signal) = oneshot::channel();
+        tokio::spawn(run(url.to_owned(), subscribe.to_string(), handler, last_error.clone(), signal));
+
+        Connection { last_error, shutdown: Some(shutdown) }
+    }
+
+    pub(crate) fn last_error(&self) -> Option<String> {
+        self.last_error.lock().expect("Lock feed").clone()
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+}
+
+async fn run<H>(
+    url: String,
+    subscribe: String,
+    mut handler: H,
+    last_error: Arc<Mutex<Option<String>>>,
+    mut shutdown: oneshot::Receiver<()>,
+) where
+    H: FnMut(&str) -> Result<bool>,
+{
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let result = tokio::select! {
+            result = session(&url, &subscribe, &mut handler, &last_error) => result,
+            _ = &mut shutdown => return,
+        };
+
+        match result {
+            // The connection delivered data before it ended, so start backing off afresh.
+            Ok(true) => backoff = MIN_BACKOFF,
+            Ok(false) => (),
+            Err(e) => *last_error.lock().expect("Lock feed") = Some(e.to_string()),
+        }
+
+        tokio::select! {
+            _ = tokio::time::delay_for(backoff) => (),
+            _ = &mut shutdown => return,
+        }
+        backoff = std::cmp::min(backoff * 2, MAX_BACKOFF);
+    }
+}
+
+/// Run one connection until it closes, returning whether any market data arrived on it.
+async fn session<H>(url: &str, subscribe: &str, handler: &mut H, last_error: &Mutex<Option<String>>) -> Result<bool>
+where
+    H: FnMut(&str) -> Result<bool>,
+{
+    let mut socket = connect(url).await?;
+    socket.send(Message::Text(subscribe.to_owned())).await.map_err(feed_error)?;
+
+    let mut received = false;
+    loop {
+        let message = match tokio::time::timeout(IDLE_TIMEOUT, socket.next()).await {
+            Ok(Some(message)) => message.map_err(feed_error)?,
+            Ok(None) => return Ok(received),
+            Err(_) => return Err(Error::Feed("no messages received".to_owned())),
+        };
+
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => return Ok(received),
+            _ => continue,
+        };
+
+        match handler(&text) {
+            Ok(data) => received |= data,
+            Err(e) => *last_error.lock().expect("Lock feed") = Some(e.to_string()),
+        }
+    }
+}
+
+async fn connect(url: &str) -> Result<Socket> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| Error::Feed(e.to_string()))?;
+    let host = parsed.host_str().ok_or_else(|| Error::Feed(format!("no host in {}", url)))?;
+    let port = parsed.port_or_known_default().unwrap_or(443);
+
+    let tcp = TcpStream::connect((host, port)).await?;
+    let stream = if parsed.scheme() == "wss" {
+        let connector = native_tls::TlsConnector::new().map_err(feed_error)?;
+        let tls = tokio_tls::TlsConnector::from(connector).connect(host, tcp).await.map_err(feed_error)?;
+        MaybeTls::Tls(tls)
+    } else {
+        MaybeTls::Plain(tcp)
+    };
+
+    let (socket, _) = tokio_tungstenite::client_async(url, stream).await.map_err(feed_error)?;
+    Ok(socket)
+}
+
+/// Apply `message` to the pair it is about, returning the pair's new data if it changed.
+fn apply(shared: &Mutex<Shared>, message: FeedMessage) -> Result<Option<CoinData>> {
+    let mut shared = shared.lock().expect("Lock feed");
+
+    let pair = match message {
+        FeedMessage::Ticker { product_id, price, best_bid, best_ask, time } => {
+            let pair = Pair::from(product_id.as_str());
+            let book = shared.books.entry(pair.clone()).or_default();
+            book.bid = Some(best_bid.parse()?);
+            book.ask = Some(best_ask.parse()?);
+            book.last = Some(price.parse()?);
+            book.time = Some(time.unwrap_or_else(Utc::now));
+            pair
+        }
+        FeedMessage::Match { product_id, price, size, time } => {
+            let pair = Pair::from(product_id.as_str());
+            let book = shared.books.entry(pair.clone()).or_default();
+            book.last = Some(price.parse()?);
+            book.time = Some(time);
+            book.prices.push_back(Point::new(time, price.parse()?));
+            book.volumes.push_back(Point::new(time, size.parse()?));
+            if book.prices.len() > MAX_TRADES {
+                book.prices.pop_front();
+                book.volumes.pop_front();
+            }
+            pair
+        }
+        FeedMessage::Error { message, reason } => {
+            return Err(Error::Feed(format!("{}: {}", message, reason.unwrap_or_default())));
+        }
+        FeedMessage::Other => return Ok(None),
+    };
+
+    let coin = match shared.books[&pair].snapshot(&pair) {
+        Some(coin) => coin,
+        None => return Ok(None),
+    };
+    shared.latest.insert(pair, coin.clone());
+    Ok(Some(coin))
+}
+
+fn feed_error<E: std::fmt::Display>(e: E) -> Error {
+    Error::Feed(e.to_string())
+}
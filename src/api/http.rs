@@ -0,0 +1,90 @@
+//! Shared HTTP handling for the data and trading APIs
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::header::RETRY_AFTER;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+
+use crate::api::endpoints::Provider;
+use crate::error::{Error, Result};
+
+/// Send the request built by `request` to `provider` and decode the JSON response.
+///
+/// Each attempt builds the request afresh, so signed requests carry the time they were
+/// sent, then waits for the provider's rate limit and uses its timeout. Failures are
+/// retried as the provider's retry policy allows, but only if the request is
+/// `idempotent`; otherwise it is sent exactly once. Failing to build the request is
+/// never retried.
+pub(crate) async fn send<T, F>(provider: &Provider, idempotent: bool, request: F) -> Result<T>
+where
+    T: DeserializeOwned,
+    F: Fn() -> Result<RequestBuilder>,
+{
+    let mut attempt = 0;
+    loop {
+        if let Some(rate_limit) = &provider.rate_limit {
+            rate_limit.acquire().await;
+        }
+
+        let error = match request()?.timeout(provider.timeout).send().await {
+            Ok(response) => match read_json(response).await {
+                Ok(value) => return Ok(value),
+                Err(e) => e,
+            },
+            Err(e) => e.into(),
+        };
+
+        match provider.retry.delay(&error, attempt).filter(|_| idempotent) {
+            Some(wait) => tokio::time::delay_for(wait).await,
+            None => return Err(error),
+        }
+        attempt += 1;
+    }
+}
+
+/// Send `request` to `provider` once, without retrying, and decode the JSON response
+/// along with the local time halfway between sending it and the response arriving.
+///
+/// The request waits for the provider's rate limit before it is timed, so the time is
+/// off by at most half the round trip.
+pub(crate) async fn send_timed<T: DeserializeOwned>(
+    provider: &Provider,
+    request: RequestBuilder,
+) -> Result<(T, DateTime<Utc>)> {
+    if let Some(rate_limit) = &provider.rate_limit {
+        rate_limit.acquire().await;
+    }
+
+    let before = Utc::now();
+    let response = request.timeout(provider.timeout).send().await?;
+    let midpoint = before + (Utc::now() - before) / 2;
+    Ok((read_json(response).await?, midpoint))
+}
+
+/// Check the status of `response` and decode its body as JSON.
+async fn read_json<T: DeserializeOwned>(response: Response) -> Result<T> {
+    let status = response.status();
+
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse().ok())
+            .map(Duration::from_secs);
+        return Err(Error::RateLimited { retry_after });
+    }
+
+    let body = response.text().await?;
+
+    if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+        return Err(Error::Auth(body));
+    }
+    if !status.is_success() {
+        return Err(Error::Status { status: status.as_u16(), body });
+    }
+
+    Ok(serde_json::from_str(&body)?)
+}
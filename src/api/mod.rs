@@ -1,4 +1,34 @@
 //! Data and live trading API
 
+pub mod auth;
+pub mod blocking;
+pub mod book;
+pub mod cache;
+pub mod candles;
+pub mod consensus;
 pub mod data;
+pub mod endpoints;
+pub mod feed;
+mod http;
+pub mod money;
+pub mod orders;
+pub mod pair;
+pub mod ratelimit;
+pub mod retention;
+pub mod retry;
+pub mod series;
+pub mod source;
+pub mod symbols;
 pub mod transactions;
+pub mod validation;
+
+pub use candles::Granularity;
+pub use consensus::Consensus;
+pub use data::{CoinData, DataAPI};
+pub use endpoints::{Endpoints, Provider};
+pub use money::{Decimal, Increments};
+pub use orders::Order;
+pub use pair::Pair;
+pub use series::{History, Point, Window};
+pub use source::{GeckoSource, PriceSource, Quote, ScriptedSource, WebSource};
+pub use transactions::{BrokerAPI, OrderQuote, OrderReceipt};
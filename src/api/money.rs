@@ -0,0 +1,64 @@
+//! Exact decimal prices and amounts, and how each product rounds them
+
+pub use rust_decimal::Decimal;
+
+/// The smallest steps a product's sizes and prices move in.
+///
+/// Coinbase rejects orders that are not a whole number of increments, so every amount is
+/// rounded before it is traded. Sizes and funds are always rounded down, so an order never
+/// spends or sells more than was asked for; prices are rounded to the nearest step.
+///
+/// # Example
+///
+/// ```
+/// use seventh_core::api::money::{Decimal, Increments};
+///
+/// let btc_usd = Increments::default();
+/// assert_eq!(btc_usd.size("0.123456789".parse().unwrap()), Decimal::new(12345678, 8));
+/// assert_eq!(btc_usd.funds(Decimal::new(100999, 3)), Decimal::new(10099, 2));
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Increments {
+    /// Smallest size step, in the base currency.
+    pub base: Decimal,
+    /// Smallest price and funds step, in the quote currency.
+    pub quote: Decimal,
+}
+
+impl Increments {
+    pub fn new(base: Decimal, quote: Decimal) -> Self {
+        Increments { base, quote }
+    }
+
+    /// Round a size in the base currency down to a whole number of base increments.
+    pub fn size(&self, size: Decimal) -> Decimal {
+        floor_to(size, self.base)
+    }
+
+    /// Round an amount of the quote currency down to a whole number of quote increments.
+    pub fn funds(&self, funds: Decimal) -> Decimal {
+        floor_to(funds, self.quote)
+    }
+
+    /// Round a price to the nearest quote increment.
+    pub fn price(&self, price: Decimal) -> Decimal {
+        if self.quote.is_zero() {
+            return price;
+        }
+        ((price / self.quote).round() * self.quote).round_dp(self.quote.scale())
+    }
+}
+
+impl Default for Increments {
+    /// Satoshis and cents, which is what Coinbase uses for BTC-USD.
+    fn default() -> Self {
+        Increments { base: Decimal::new(1, 8), quote: Decimal::new(1, 2) }
+    }
+}
+
+fn floor_to(value: Decimal, step: Decimal) -> Decimal {
+    if step.is_zero() {
+        return value;
+    }
+    ((value / step).floor() * step).round_dp(step.scale())
+}
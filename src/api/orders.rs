@@ -0,0 +1,389 @@
+//! Orders to place with the Coinbase API, and tracking them until they settle
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::api::book::Side;
+use crate::api::money::{Decimal, Increments};
+use crate::api::transactions::{OrderReceipt, OrderStatus, Session};
+use crate::error::{Error, Result};
+
+/// Most orders Coinbase returns in one page.
+const MAX_LIMIT: u32 = 100;
+
+/// How an order is priced.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OrderType {
+    /// Trade straight away at the best price available.
+    Market,
+    /// Trade at `limit` or better: buy at or below it, or sell at or above it.
+    Limit { limit: Decimal },
+    /// Trade at market once the price reaches `stop`: rises to it for a buy, or falls to
+    /// it for a sell.
+    Stop { stop: Decimal },
+    /// Place a limit order at `limit` once the price reaches `stop`.
+    StopLimit { stop: Decimal, limit: Decimal },
+}
+
+/// How long an order stays open if it does not fill straight away.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeInForce {
+    /// Until it fills or is cancelled.
+    GoodTilCancelled,
+    /// Until it fills or is cancelled, or the time given passes.
+    GoodTilTime(DateTime<Utc>),
+    /// Fill what can be filled straight away and cancel the rest.
+    ImmediateOrCancel,
+    /// Fill completely straight away, or not at all.
+    FillOrKill,
+}
+
+impl TimeInForce {
+    /// Whether an order is cancelled rather than left open if it does not fill when placed.
+    pub fn is_immediate(self) -> bool {
+        matches!(self, TimeInForce::ImmediateOrCancel | TimeInForce::FillOrKill)
+    }
+}
+
+/// An order to place, with `Actions::Order` or `BrokerAPI::place_order`.
+///
+/// Market orders are immediate-or-cancel by default, and the rest good-til-cancelled.
+/// Prices are in the quote currency.
+///
+/// # Example
+///
+/// ```
+/// use seventh_core::api::book::Side;
+/// use seventh_core::api::orders::{Order, TimeInForce};
+/// use seventh_core::api::Decimal;
+///
+/// // Sell 0.5 at 105 or better, but only as a maker.
+/// let order = Order::limit(Side::Sell, Decimal::new(5, 1), Decimal::from(105)).post_only();
+/// assert!(order.validate().is_ok());
+///
+/// // Market orders cannot rest on the book.
+/// let order = Order::market(Side::Buy, Decimal::from(100)).time_in_force(TimeInForce::GoodTilCancelled);
+/// assert!(order.validate().is_err());
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Order {
+    pub side: Side,
+    pub kind: OrderType,
+    /// For market buys, the funds to spend in the quote currency, as with `Actions::Buy`;
+    /// otherwise the size to trade in the base currency.
+    pub amount: Decimal,
+    pub time_in_force: TimeInForce,
+    /// Only add liquidity: reject a limit order that would fill as soon as it is placed.
+    pub post_only: bool,
+}
+
+impl Order {
+    fn new(side: Side, kind: OrderType, amount: Decimal, time_in_force: TimeInForce) -> Self {
+        Order { side, kind, amount, time_in_force, post_only: false }
+    }
+
+    /// A market order, spending `amount` of the quote currency for a buy or selling
+    /// `amount` of the base currency.
+    pub fn market(side: Side, amount: Decimal) -> Self {
+        Order::new(side, OrderType::Market, amount, TimeInForce::ImmediateOrCancel)
+    }
+
+    /// A limit order for `size` of the base currency at `limit` or better.
+    pub fn limit(side: Side, size: Decimal, limit: Decimal) -> Self {
+        Order::new(side, OrderType::Limit { limit }, size, TimeInForce::GoodTilCancelled)
+    }
+
+    /// A stop order for `size` of the base currency, trading at market once the price
+    /// reaches `stop`.
+    ///
+    /// Only the simulated traders can fill stop orders: Coinbase has none, so `BrokerAPI`
+    /// and `Livetrader` reject them with `Error::Order`. Use `Order::stop_limit` live.
+    pub fn stop(side: Side, size: Decimal, stop: Decimal) -> Self {
+        Order::new(side, OrderType::Stop { stop }, size, TimeInForce::GoodTilCancelled)
+    }
+
+    /// A stop-limit order for `size` of the base currency, placed at `limit` once the
+    /// price reaches `stop`.
+    pub fn stop_limit(side: Side, size: Decimal, stop: Decimal, limit: Decimal) -> Self {
+        Order::new(side, OrderType::StopLimit { stop, limit }, size, TimeInForce::GoodTilCancelled)
+    }
+
+    /// Keep the order open for `time_in_force`.
+    pub fn time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = time_in_force;
+        self
+    }
+
+    /// Reject the order rather than let it fill as soon as it is placed.
+    pub fn post_only(mut self) -> Self {
+        self.post_only = true;
+        self
+    }
+
+    /// Whether `amount` is in the quote currency rather than the base currency.
+    pub fn is_funds(&self) -> bool {
+        self.side == Side::Buy && self.kind == OrderType::Market
+    }
+
+    /// Round the amount and prices to `increments`, as the traders do before placing.
+    pub fn rounded(&self, increments: &Increments) -> Self {
+        let price = |price| increments.price(price);
+        let kind = match self.kind {
+            OrderType::Market => OrderType::Market,
+            OrderType::Limit { limit } => OrderType::Limit { limit: price(limit) },
+            OrderType::Stop { stop } => OrderType::Stop { stop: price(stop) },
+            OrderType::StopLimit { stop, limit } => OrderType::StopLimit { stop: price(stop), limit: price(limit) },
+        };
+        let amount = if self.is_funds() { increments.funds(self.amount) } else { increments.size(self.amount) };
+
+        Order { kind, amount, ..self.clone() }
+    }
+
+    /// Check the order makes sense before it is placed, failing with `Error::Order` if not.
+    ///
+    /// The amount and prices must be positive. Market orders must fill straight away,
+    /// stop and stop-limit orders cannot, and only limit orders left open can be
+    /// post-only.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: &str| Err(Error::Order(format!("invalid order: {}", reason)));
+
+        if self.amount <= Decimal::ZERO {
+            return invalid("amount must be positive");
+        }
+        let prices = match self.kind {
+            OrderType::Market => vec![],
+            OrderType::Limit { limit } => vec![limit],
+            OrderType::Stop { stop } => vec![stop],
+            OrderType::StopLimit { stop, limit } => vec![stop, limit],
+        };
+        if prices.iter().any(|price| *price <= Decimal::ZERO) {
+            return invalid("prices must be positive");
+        }
+
+        let immediate = self.time_in_force.is_immediate();
+        match self.kind {
+            OrderType::Market if !immediate => invalid("market orders must be immediate-or-cancel or fill-or-kill"),
+            OrderType::Stop { .. } | OrderType::StopLimit { .. } if immediate => {
+                invalid("stop orders must be good-til-cancelled or good-til-time")
+            }
+            OrderType::Limit { .. } if self.post_only && immediate => invalid("post-only orders must be left open"),
+            OrderType::Limit { .. } => Ok(()),
+            _ if self.post_only => invalid("only limit orders can be post-only"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Which orders `BrokerAPI::list_orders` returns.
+///
+/// # Example
+///
+/// ```no_run
+/// use seventh_core::api::BrokerAPI;
+/// use seventh_core::api::book::Side;
+/// use seventh_core::api::orders::OrderQuery;
+/// use seventh_core::api::transactions::OrderStatus;
+///
+/// # async fn run(broker: BrokerAPI) -> seventh_core::Result<()> {
+/// let mut query = OrderQuery::new().limit(50).status(OrderStatus::Completed);
+/// loop {
+///     let page = broker.list_orders(Side::Buy, &query).await?;
+///     for order in &page.orders {
+///         println!("{} {}", order.id, order.total.amount);
+///     }
+///     match page.next {
+///         Some(next) => query = query.after(&next),
+///         None => break,
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderQuery {
+    /// Orders per page, at most 100.
+    pub limit: u32,
+    /// Start after the order with this id, from `OrderPage::next`.
+    pub starting_after: Option<String>,
+    /// Only return orders with this status.
+    pub status: Option<OrderStatus>,
+}
+
+impl OrderQuery {
+    /// The first page of 25 orders, whatever their status.
+    pub fn new() -> Self {
+        OrderQuery { limit: 25, starting_after: None, status: None }
+    }
+
+    /// Return up to `limit` orders per page.
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = limit.clamp(1, MAX_LIMIT);
+        self
+    }
+
+    /// Start after the order `id`.
+    pub fn after(mut self, id: &str) -> Self {
+        self.starting_after = Some(id.to_owned());
+        self
+    }
+
+    /// Only return orders with `status`.
+    pub fn status(mut self, status: OrderStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+}
+
+impl Default for OrderQuery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One page of orders.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderPage {
+    pub orders: Vec<OrderReceipt>,
+    /// Id to continue after for the next page, or `None` if this is the last.
+    pub next: Option<String>,
+}
+
+/// An order was placed, or its status changed.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct OrderEvent {
+    /// The status before, or `None` if the order was just placed.
+    pub previous: Option<OrderStatus>,
+    /// The latest receipt for the order.
+    pub order: OrderReceipt,
+}
+
+/// The receipts for the orders a `BrokerAPI` placed, shared with its poller.
+#[derive(Default)]
+pub(crate) struct Orders {
+    records: Mutex<Vec<OrderReceipt>>,
+    listeners: Mutex<Vec<mpsc::UnboundedSender<OrderEvent>>>,
+}
+
+impl Orders {
+    pub(crate) fn all(&self) -> Vec<OrderReceipt> {
+        self.records.lock().expect("Lock orders").clone()
+    }
+
+    pub(crate) fn get(&self, id: &str) -> Option<OrderReceipt> {
+        self.records.lock().expect("Lock orders").iter().find(|r| r.id == id).cloned()
+    }
+
+    pub(crate) fn subscribe(&self) -> mpsc::UnboundedReceiver<OrderEvent> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.listeners.lock().expect("Lock listeners").push(sender);
+        receiver
+    }
+
+    /// Record `receipt`, replacing any earlier receipt for the same order, and send an
+    /// event if the order is new or its status changed.
+    pub(crate) fn update(&self, receipt: OrderReceipt) -> Option<OrderEvent> {
+        let previous = {
+            let mut records = self.records.lock().expect("Lock orders");
+            match records.iter_mut().find(|r| r.id == receipt.id) {
+                Some(record) => Some(std::mem::replace(record, receipt.clone()).status),
+                None => {
+                    records.push(receipt.clone());
+                    None
+                }
+            }
+        };
+        if previous == Some(receipt.status) {
+            return None;
+        }
+
+        let event = OrderEvent { previous, order: receipt };
+        let mut listeners = self.listeners.lock().expect("Lock listeners");
+        // Receivers that have been dropped are forgotten.
+        listeners.retain(|listener| listener.send(event.clone()).is_ok());
+        Some(event)
+    }
+
+    /// Look up every order that has not settled, carrying on past any that fail.
+    ///
+    /// Changes to the orders that were found are recorded either way. Fails with the
+    /// lookup's error if one failed, or with `Error::Order` naming each if several did.
+    pub(crate) async fn refresh(&self, session: &Session) -> Result<Vec<OrderEvent>> {
+        let pending: Vec<OrderReceipt> = self.all().into_iter().filter(|r| !r.status.is_final()).collect();
+
+        let mut events = vec![];
+        let mut failed = vec![];
+        for order in pending {
+            match session.track(&order).await {
+                Ok(receipt) => events.extend(self.update(receipt)),
+                Err(e) => failed.push((order.id, e)),
+            }
+        }
+
+        if failed.len() > 1 {
+            let failures: Vec<String> = failed.iter().map(|(id, e)| format!("{}: {}", id, e)).collect();
+            return Err(Error::Order(format!("could not look up orders ({})", failures.join(", "))));
+        }
+        match failed.pop() {
+            Some((_, e)) => Err(e),
+            None => Ok(events),
+        }
+    }
+}
+
+/// Looks up unsettled orders in the background, kept running until dropped.
+///
+/// Changes are sent to the receivers from `BrokerAPI::events`, and recorded in its
+/// `history`.
+pub struct OrderPoller {
+    last_error: Arc<Mutex<Option<String>>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl OrderPoller {
+    pub(crate) fn start(session: Session, orders: Arc<Orders>, interval: Duration) -> Self {
+        let last_error = Arc::new(Mutex::new(None));
+        let (shutdown, signal) = oneshot::channel();
+        tokio::spawn(run(session, orders, interval, last_error.clone(), signal));
+
+        OrderPoller { last_error, shutdown: Some(shutdown) }
+    }
+
+    /// Get why the last lookup failed, if it did.
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error.lock().expect("Lock poller").clone()
+    }
+}
+
+impl Drop for OrderPoller {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+}
+
+async fn run(
+    session: Session,
+    orders: Arc<Orders>,
+    interval: Duration,
+    last_error: Arc<Mutex<Option<String>>>,
+    mut shutdown: oneshot::Receiver<()>,
+) {
+    loop {
+        tokio::select! {
+            _ = tokio::time::delay_for(interval) => (),
+            _ = &mut shutdown => return,
+        }
+
+        let result = tokio::select! {
+            result = orders.refresh(&session) => result,
+            _ = &mut shutdown => return,
+        };
+        *last_error.lock().expect("Lock poller") = result.err().map(|e| e.to_string());
+    }
+}
@@ -0,0 +1,59 @@
+//! Trading pairs of a base and quote currency
+
+use std::fmt;
+
+/// Quote currency assumed when only a ticker is given.
+pub const DEFAULT_QUOTE: &str = "USD";
+
+/// A base currency priced in a quote currency, such as BTC-EUR.
+///
+/// Pairs convert from strings in `BASE-QUOTE` or `BASE/QUOTE` form. A bare ticker is
+/// quoted in USD, so existing calls such as `update("BTC")` keep their meaning.
+///
+/// # Example
+///
+/// ```
+/// use seventh_core::api::Pair;
+///
+/// assert_eq!(Pair::from("btc-eur"), Pair::new("BTC", "EUR"));
+/// assert_eq!(Pair::from("ETH"), Pair::new("ETH", "USD"));
+/// assert_eq!(Pair::new("LTC", "GBP").to_string(), "LTC-GBP");
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Pair {
+    pub base: String,
+    pub quote: String,
+}
+
+impl Pair {
+    pub fn new(base: &str, quote: &str) -> Self {
+        Pair { base: base.to_uppercase(), quote: quote.to_uppercase() }
+    }
+}
+
+impl fmt::Display for Pair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.base, self.quote)
+    }
+}
+
+impl From<&str> for Pair {
+    fn from(pair: &str) -> Self {
+        match pair.find(&['-', '/'][..]) {
+            Some(i) => Pair::new(&pair[..i], &pair[i + 1..]),
+            None => Pair::new(pair, DEFAULT_QUOTE),
+        }
+    }
+}
+
+impl From<&String> for Pair {
+    fn from(pair: &String) -> Self {
+        Pair::from(pair.as_str())
+    }
+}
+
+impl From<&Pair> for Pair {
+    fn from(pair: &Pair) -> Self {
+        pair.clone()
+    }
+}
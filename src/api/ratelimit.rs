@@ -0,0 +1,90 @@
+//! Client-side rate limiting for providers
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A token bucket holding up to `requests` tokens, refilled evenly over `per`.
+///
+/// Every request to a provider takes a token, waiting for one to be refilled if the
+/// bucket is empty, so bursts of up to `requests` go out at once and the long-run rate
+/// stays under `requests` per `per`.
+///
+/// Clones share the same bucket, so endpoints cloned into several APIs draw on one
+/// limit between them.
+#[derive(Clone)]
+pub struct RateLimit {
+    requests: u32,
+    per: Duration,
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimit {
+    /// Allow `requests` requests every `per`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `requests` or `per` is zero.
+    pub fn new(requests: u32, per: Duration) -> Self {
+        assert!(requests > 0 && per > Duration::from_secs(0), "Rate limit must be positive");
+
+        RateLimit {
+            requests,
+            per,
+            bucket: Arc::new(Mutex::new(Bucket { tokens: requests as f64, last: Instant::now() })),
+        }
+    }
+
+    pub fn per_second(requests: u32) -> Self {
+        RateLimit::new(requests, Duration::from_secs(1))
+    }
+
+    pub fn per_minute(requests: u32) -> Self {
+        RateLimit::new(requests, Duration::from_secs(60))
+    }
+
+    /// Wait until a token is available and take it.
+    pub(crate) async fn acquire(&self) {
+        while let Some(wait) = self.try_acquire() {
+            tokio::time::delay_for(wait).await;
+        }
+    }
+
+    /// Take a token if one is available, or return how long until one will be.
+    fn try_acquire(&self) -> Option<Duration> {
+        let rate = self.requests as f64 / self.per.as_secs_f64();
+        let mut bucket = self.bucket.lock().expect("Lock rate limit");
+
+        let now = Instant::now();
+        let refill = now.duration_since(bucket.last).as_secs_f64() * rate;
+        bucket.tokens = (bucket.tokens + refill).min(self.requests as f64);
+        bucket.last = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        }
+    }
+}
+
+impl fmt::Debug for RateLimit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RateLimit")
+            .field("requests", &self.requests)
+            .field("per", &self.per)
+            .finish()
+    }
+}
+
+impl PartialEq for RateLimit {
+    fn eq(&self, other: &Self) -> bool {
+        self.requests == other.requests && self.per == other.per
+    }
+}
@@ -0,0 +1,66 @@
+//! Limits on how many snapshots a `DataAPI` keeps
+
+use std::time::Duration;
+
+/// How long a `DataAPI` keeps the `CoinData` snapshots it has fetched.
+///
+/// After each update the oldest snapshots are dropped until every set limit holds. The
+/// most recent snapshot is always kept, even if it alone breaks a limit.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use seventh_core::api::{DataAPI, ScriptedSource};
+/// use seventh_core::api::retention::Retention;
+///
+/// // Keep at most an hour of snapshots, and never more than 500.
+/// let retention = Retention::unbounded().count(500).max_age(Duration::from_secs(3600));
+/// let data = DataAPI::with_source(ScriptedSource::new()).with_retention(retention);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Retention {
+    /// Most snapshots to keep.
+    pub count: Option<usize>,
+    /// Oldest snapshot to keep, by the time its quote was taken.
+    pub max_age: Option<Duration>,
+    /// Most memory the snapshots may take up, approximately.
+    pub max_bytes: Option<usize>,
+}
+
+impl Retention {
+    /// Keep every snapshot.
+    pub fn unbounded() -> Self {
+        Retention { count: None, max_age: None, max_bytes: None }
+    }
+
+    /// Keep only the last `count` snapshots.
+    pub fn last(count: usize) -> Self {
+        Retention::unbounded().count(count)
+    }
+
+    /// Keep at most `count` snapshots.
+    pub fn count(mut self, count: usize) -> Self {
+        self.count = Some(count);
+        self
+    }
+
+    /// Drop snapshots older than `max_age`.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Drop the oldest snapshots once they take up more than `max_bytes`.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+}
+
+impl Default for Retention {
+    /// The last 1000 snapshots.
+    fn default() -> Self {
+        Retention::last(1000)
+    }
+}
@@ -0,0 +1,95 @@
+//! Retrying failed requests with exponential backoff
+
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::error::Error;
+
+/// When and how long to wait before retrying a failed request to a provider.
+///
+/// Transport errors, rate limits and `5xx` responses are retried; anything else, such as
+/// an authentication or decode error, is returned straight away. Each retry waits twice
+/// as long as the one before, plus up to the same again in random jitter, capped at
+/// `max_delay`. A rate limit response that names a `Retry-After` is retried after exactly
+/// that long, unless it exceeds `max_delay`, in which case the error is returned.
+///
+/// Requests that are not idempotent, such as placing an order, are never retried.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use seventh_core::api::endpoints::{Endpoints, Provider};
+/// use seventh_core::api::retry::RetryPolicy;
+///
+/// let mut endpoints = Endpoints::production();
+/// endpoints.coingecko = Provider::new("https://api.coingecko.com/api/v3")
+///     .retry(RetryPolicy::new(5).max_delay(Duration::from_secs(60)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt.
+    pub max_retries: u32,
+    /// Wait before the first retry, before jitter.
+    pub base_delay: Duration,
+    /// Longest wait before any retry.
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Retry up to `max_retries` times, starting from a quarter-second wait.
+    pub fn new(max_retries: u32) -> Self {
+        RetryPolicy {
+            max_retries,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+
+    /// Never retry.
+    pub fn none() -> Self {
+        RetryPolicy::new(0)
+    }
+
+    /// Set the wait before the first retry.
+    pub fn base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    /// Set the longest wait before any retry.
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// How long to wait before retrying after `error` on the given `attempt`, counting
+    /// from zero, or `None` if the request should not be retried.
+    pub(crate) fn delay(&self, error: &Error, attempt: u32) -> Option<Duration> {
+        if attempt >= self.max_retries {
+            return None;
+        }
+
+        match error {
+            Error::RateLimited { retry_after: Some(wait) } => Some(*wait).filter(|w| *w <= self.max_delay),
+            Error::RateLimited { retry_after: None } | Error::Transport(_) => Some(self.backoff(attempt)),
+            Error::Status { status, .. } if *status >= 500 => Some(self.backoff(attempt)),
+            _ => None,
+        }
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self.base_delay * 2u32.saturating_pow(attempt);
+        let jitter = delay.mul_f64(rand::thread_rng().gen::<f64>());
+
+        std::cmp::min(delay + jitter, self.max_delay)
+    }
+}
+
+impl Default for RetryPolicy {
+    /// Three retries, starting from a quarter-second wait and waiting at most 30 seconds.
+    fn default() -> Self {
+        RetryPolicy::new(3)
+    }
+}
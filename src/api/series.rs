@@ -0,0 +1,137 @@
+//! Timestamped price and volume series
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+/// Most points `History::resample` produces for each series.
+pub const MAX_POINTS: usize = 10_000;
+
+/// A single value at a point in time.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct Point {
+    pub time: DateTime<Utc>,
+    pub value: f32,
+}
+
+impl Point {
+    pub fn new(time: DateTime<Utc>, value: f32) -> Self {
+        Point { time, value }
+    }
+}
+
+/// Price history for a pair, with the traded volume at the same timestamps.
+///
+/// Both series are ordered oldest first.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct History {
+    pub prices: Vec<Point>,
+    pub volumes: Vec<Point>,
+}
+
+impl History {
+    pub fn new(prices: Vec<Point>, volumes: Vec<Point>) -> Self {
+        History { prices, volumes }
+    }
+
+    /// Build a history from bare prices spaced `step` apart and ending at `end`, with zero
+    /// volume.
+    pub fn from_values(values: &[f32], end: DateTime<Utc>, step: Duration) -> Self {
+        let start = end - step * (values.len().saturating_sub(1) as i32);
+        let times = (0..values.len()).map(|i| start + step * i as i32);
+
+        History {
+            prices: times.clone().zip(values).map(|(t, v)| Point::new(t, *v)).collect(),
+            volumes: times.map(|t| Point::new(t, 0.0)).collect(),
+        }
+    }
+
+    /// The prices without their timestamps.
+    pub fn values(&self) -> Vec<f32> {
+        self.prices.iter().map(|p| p.value).collect()
+    }
+
+    /// Resample both series onto a regular grid `step` apart.
+    ///
+    /// Each point is labelled with the start of its interval and holds the last value seen
+    /// before the interval ends, so intervals without data repeat the previous value.
+    /// Only the latest `MAX_POINTS` intervals are kept, so a `step` that is short for the
+    /// span of the history cannot grow it without bound. A `step` shorter than a
+    /// millisecond leaves the history unchanged.
+    pub fn resample(&self, step: Duration) -> History {
+        History {
+            prices: resample(&self.prices, step),
+            volumes: resample(&self.volumes, step),
+        }
+    }
+}
+
+fn resample(points: &[Point], step: Duration) -> Vec<Point> {
+    let step = step.num_milliseconds();
+    let (first, last) = match (points.first(), points.last()) {
+        (Some(first), Some(last)) if step > 0 => (first, last),
+        _ => return points.to_vec(),
+    };
+    let floor = |time: DateTime<Utc>| time.timestamp_millis().div_euclid(step) * step;
+
+    let mut resampled = vec![];
+    let mut rest = points.iter().peekable();
+    let end = floor(last.time);
+    let earliest = end.saturating_sub(step.saturating_mul(MAX_POINTS as i64 - 1));
+
+    let mut value = first.value;
+    let mut slot = floor(first.time).max(earliest);
+    while slot <= end {
+        while let Some(point) = rest.peek() {
+            if point.time.timestamp_millis() >= slot + step {
+                break;
+            }
+            value = point.value;
+            rest.next();
+        }
+        resampled.push(Point::new(Utc.timestamp_millis_opt(slot).unwrap(), value));
+        slot += step;
+    }
+    resampled
+}
+
+/// How much history to request, and at what resolution.
+///
+/// Providers choose their own resolution for a given lookback; CoinGecko, for example,
+/// returns five-minute points for a day and hourly points for up to 90 days. Setting a
+/// `granularity` resamples the series to that spacing after it is fetched.
+///
+/// # Example
+///
+/// ```
+/// use chrono::Duration;
+/// use seventh_core::api::{DataAPI, ScriptedSource, Window};
+///
+/// // Thirty daily closes, enough for a 30-day moving average.
+/// let window = Window::new(Duration::days(30)).granularity(Duration::days(1));
+/// let data = DataAPI::with_source(ScriptedSource::new()).with_window(window);
+/// ```
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Window {
+    /// How far back from now the history starts.
+    pub lookback: Duration,
+    /// Spacing to resample the history to, or `None` to keep the provider's resolution.
+    pub granularity: Option<Duration>,
+}
+
+impl Window {
+    pub fn new(lookback: Duration) -> Self {
+        Window { lookback, granularity: None }
+    }
+
+    /// Resample the history to points `granularity` apart.
+    pub fn granularity(mut self, granularity: Duration) -> Self {
+        self.granularity = Some(granularity);
+        self
+    }
+}
+
+impl Default for Window {
+    /// The last day, at the provider's resolution.
+    fn default() -> Self {
+        Window::new(Duration::days(1))
+    }
+}
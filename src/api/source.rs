@@ -0,0 +1,405 @@
+//! Pluggable sources of price data for `DataAPI`
+
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, SecondsFormat, TimeZone, Utc};
+
+use crate::api::book::{self, OrderBook};
+use crate::api::candles::{self, Granularity};
+use crate::api::endpoints::{Endpoints, Provider};
+use crate::api::http;
+use crate::api::money::{Decimal, Increments};
+use crate::api::pair::Pair;
+use crate::api::series::{History, Point, Window};
+use crate::api::symbols::SymbolRegistry;
+use crate::error::{Error, Result};
+use crate::historical::RawData;
+
+#[derive(Deserialize, Debug)]
+struct Price {
+    currency: String,
+    amount: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct PriceData {
+    data: Price
+}
+
+/// CoinGecko `GET /simple/price` response, keyed by coin id and then by currency.
+type SimplePrice = HashMap<String, HashMap<String, Decimal>>;
+
+#[derive(Deserialize, Debug)]
+struct HistoricalData {
+    prices: Vec<(f64, f32)>,
+    total_volumes: Vec<(f64, f32)>
+}
+
+fn to_series(points: Vec<(f64, f32)>) -> Result<Vec<Point>> {
+    points
+        .into_iter()
+        .map(|(ms, value)| {
+            Utc.timestamp_millis_opt(ms as i64)
+                .single()
+                .map(|time| Point::new(time, value))
+                .ok_or_else(|| Error::Decode(format!("invalid timestamp {}", ms)))
+        })
+        .collect()
+}
+
+/// Instantaneous buy, sell and spot prices for a single pair, in its quote currency.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct Quote {
+    pub buy: Decimal,
+    pub sell: Decimal,
+    pub spot: Decimal,
+    /// When the prices were taken.
+    pub time: DateTime<Utc>,
+    /// Volume traded over the last 24 hours in the quote currency, if the source reports it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume: Option<Decimal>,
+}
+
+impl Quote {
+    /// Create a quote taken now.
+    pub fn new(buy: Decimal, sell: Decimal, spot: Decimal) -> Self {
+        Quote { buy, sell, spot, time: Utc::now(), volume: None }
+    }
+
+    /// Set the time the prices were taken.
+    pub fn at(mut self, time: DateTime<Utc>) -> Self {
+        self.time = time;
+        self
+    }
+
+    /// Set the volume traded over the last 24 hours, in the quote currency.
+    pub fn with_volume(mut self, volume: Decimal) -> Self {
+        self.volume = Some(volume);
+        self
+    }
+}
+
+/// A provider of price data.
+///
+/// `DataAPI` and the traders are generic over `PriceSource`, so the same strategy code
+/// can run against the live exchanges or against an in-memory script.
+#[async_trait]
+pub trait PriceSource: Send + Sync {
+    /// Get the current buy, sell and spot prices for `pair`.
+    async fn quote(&self, pair: &Pair) -> Result<Quote>;
+
+    /// Get the price and volume history for `pair` over `window.lookback`, oldest first.
+    ///
+    /// Sources return their native resolution; `DataAPI` applies `window.granularity`.
+    async fn historical(&self, pair: &Pair, window: &Window) -> Result<History>;
+
+    /// Get how sizes and prices for `pair` are rounded when it is traded.
+    ///
+    /// Sources that know nothing about the product use `Increments::default`.
+    fn increments(&self, _pair: &Pair) -> Increments {
+        Increments::default()
+    }
+}
+
+/// Live source using Coinbase for quotes and CoinGecko for price history.
+///
+/// Coins are looked up in a `SymbolRegistry`, which by default knows only BTC, ETC, ETH,
+/// and LTC. Use `with_symbols` or `load_symbols` to trade anything else; tickers missing
+/// from the registry are rejected with `Error::UnknownSymbol` before any request is sent.
+pub struct WebSource {
+    client: reqwest::Client,
+    endpoints: Endpoints,
+    symbols: SymbolRegistry,
+}
+
+impl WebSource {
+    pub fn new() -> Self {
+        WebSource::with_client(reqwest::Client::new(), Endpoints::production())
+    }
+
+    /// Create a `WebSource` that sends its requests to `endpoints`.
+    pub fn with_endpoints(endpoints: Endpoints) -> Result<Self> {
+        Ok(WebSource::with_client(endpoints.client()?, endpoints))
+    }
+
+    /// Create a `WebSource` that sends its requests to `endpoints` through an existing
+    /// `client`.
+    ///
+    /// `reqwest::Client` is reference counted, so a clone can be shared with a `BrokerAPI`
+    /// to reuse one connection pool.
+    pub fn with_client(client: reqwest::Client, endpoints: Endpoints) -> Self {
+        WebSource { client, endpoints, symbols: SymbolRegistry::new() }
+    }
+
+    /// Replace the registry used to look up coins.
+    pub fn with_symbols(mut self, symbols: SymbolRegistry) -> Self {
+        self.symbols = symbols;
+        self
+    }
+
+    /// Get the registry used to look up coins.
+    pub fn symbols(&self) -> &SymbolRegistry {
+        &self.symbols
+    }
+
+    /// Fetch the CoinGecko and Coinbase listings into the registry.
+    pub async fn load_symbols(&mut self) -> Result<()> {
+        self.symbols.load_listings(&self.client, &self.endpoints).await
+    }
+
+    /// Fetch OHLCV candles for `pair` covering `range` from the Coinbase Exchange API.
+    ///
+    /// Ranges longer than `candles::MAX_CANDLES` candles are fetched a page at a time.
+    pub async fn candles(&self, pair: &Pair, granularity: Granularity, range: Range<DateTime<Utc>>) -> Result<RawData> {
+        if !self.symbols.on_coinbase(&pair.base) {
+            return Err(Error::UnknownSymbol(pair.base.clone()));
+        }
+        let stamp = |time: DateTime<Utc>| time.to_rfc3339_opts(SecondsFormat::Secs, true);
+
+        let mut rows = vec![];
+        for page in candles::pages(&range, granularity) {
+            let path = format!("/products/{}/candles?granularity={}&start={}&end={}", pair, granularity.seconds(), stamp(page.start), stamp(page.end));
+            rows.extend(self.get::<Vec<candles::Row>>(&self.endpoints.exchange, path).await?);
+        }
+
+        Ok(candles::to_raw(rows, &range))
+    }
+
+    /// Fetch the level 2 order book for `pair` from the Coinbase Exchange API.
+    pub async fn order_book(&self, pair: &Pair) -> Result<OrderBook> {
+        if !self.symbols.on_coinbase(&pair.base) {
+            return Err(Error::UnknownSymbol(pair.base.clone()));
+        }
+        let path = format!("/products/{}/book?level=2", pair);
+        let snapshot = self.get::<book::Snapshot>(&self.endpoints.exchange, path).await?;
+
+        OrderBook::from_snapshot(pair, snapshot)
+    }
+
+    async fn get<T: for<'de> serde::Deserialize<'de>>(&self, provider: &Provider, path: String) -> Result<T> {
+        let url = provider.url(&path);
+
+        http::send(provider, true, || Ok(self.client.get(&url))).await
+    }
+
+    async fn get_coinbase(&self, pair: &Pair, query: &str) -> Result<Decimal> {
+        if !self.symbols.on_coinbase(&pair.base) {
+            return Err(Error::UnknownSymbol(pair.base.clone()));
+        }
+        let path = format!("/v2/prices/{}/{}", pair, query);
+        let price = self.get::<PriceData>(&self.endpoints.coinbase, path).await?.data;
+
+        if !price.currency.eq_ignore_ascii_case(&pair.quote) {
+            return Err(Error::Decode(format!("{} price for {} is in {}", query, pair, price.currency)));
+        }
+        Ok(price.amount.parse()?)
+    }
+
+    async fn get_gecko_price(&self, pair: &Pair) -> Result<(Decimal, Option<Decimal>)> {
+        let id = self.symbols.coingecko_id(&pair.base)?;
+        let currency = pair.quote.to_lowercase();
+        let path = format!("/simple/price?ids={}&vs_currencies={}&include_24hr_vol=true", id, currency);
+        let mut prices = self.get::<SimplePrice>(&self.endpoints.coingecko, path).await?;
+
+        let mut price = prices.remove(id).unwrap_or_default();
+        let volume = price.remove(&format!("{}_24h_vol", currency));
+        match price.remove(&currency) {
+            Some(spot) => Ok((spot, volume)),
+            None => Err(Error::UnknownSymbol(pair.to_string())),
+        }
+    }
+
+    async fn get_historical(&self, pair: &Pair, lookback: Duration) -> Result<HistoricalData> {
+        let id = self.symbols.coingecko_id(&pair.base)?;
+        let curr_time = Utc::now().timestamp();
+        let path = format!("/coins/{}/market_chart/range?vs_currency={}&from={}&to={}", id, pair.quote.to_lowercase(), curr_time - lookback.num_seconds(), curr_time);
+
+        self.get::<HistoricalData>(&self.endpoints.coingecko, path).await
+    }
+}
+
+impl Default for WebSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl PriceSource for WebSource {
+    async fn quote(&self, pair: &Pair) -> Result<Quote> {
+        let (buy, sell, spot) = tokio::try_join!(
+            self.get_coinbase(pair, "buy"),
+            self.get_coinbase(pair, "sell"),
+            self.get_coinbase(pair, "spot")
+        )?;
+
+        Ok(Quote::new(buy, sell, spot))
+    }
+
+    async fn historical(&self, pair: &Pair, window: &Window) -> Result<History> {
+        let history = self.get_historical(pair, window.lookback).await?;
+
+        Ok(History::new(to_series(history.prices)?, to_series(history.total_volumes)?))
+    }
+
+    /// Uses the increments from the Coinbase product listings, once loaded.
+    fn increments(&self, pair: &Pair) -> Increments {
+        self.symbols.increments(pair)
+    }
+}
+
+/// Live source using CoinGecko for both quotes and price history.
+///
+/// CoinGecko publishes a single aggregated price per coin, so the buy, sell and spot prices
+/// of each quote are all that price, and the quote carries CoinGecko's 24 hour volume. It
+/// is mostly useful as a second opinion on the prices from a `WebSource`; see
+/// `consensus::Consensus`.
+pub struct GeckoSource {
+    web: WebSource,
+}
+
+impl GeckoSource {
+    pub fn new() -> Self {
+        GeckoSource { web: WebSource::new() }
+    }
+
+    /// Create a `GeckoSource` that sends its requests to `endpoints.coingecko`.
+    pub fn with_endpoints(endpoints: Endpoints) -> Result<Self> {
+        Ok(GeckoSource { web: WebSource::with_endpoints(endpoints)? })
+    }
+
+    /// Create a `GeckoSource` sharing the client, endpoints and registry of `web`.
+    pub fn from_web(web: WebSource) -> Self {
+        GeckoSource { web }
+    }
+
+    /// Get the registry used to look up coins.
+    pub fn symbols(&self) -> &SymbolRegistry {
+        self.web.symbols()
+    }
+}
+
+impl Default for GeckoSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl PriceSource for GeckoSource {
+    async fn quote(&self, pair: &Pair) -> Result<Quote> {
+        let (price, volume) = self.web.get_gecko_price(pair).await?;
+
+        let quote = Quote::new(price, price, price);
+        Ok(match volume {
+            Some(volume) => quote.with_volume(volume),
+            None => quote,
+        })
+    }
+
+    async fn historical(&self, pair: &Pair, window: &Window) -> Result<History> {
+        self.web.historical(pair, window).await
+    }
+
+    fn increments(&self, pair: &Pair) -> Increments {
+        self.web.increments(pair)
+    }
+}
+
+#[derive(Default)]
+struct Script {
+    quotes: VecDeque<Quote>,
+    last: Option<Quote>,
+    history: History,
+    increments: Increments,
+}
+
+/// In-memory source that replays scripted quotes without touching the network.
+///
+/// Each call to `quote` consumes the next scripted quote for that pair; once the script
+/// runs out, the last quote is repeated. Pairs with nothing scripted are reported as
+/// `Error::UnknownSymbol`. Clones share the same script, so a test can keep a handle and
+/// keep feeding quotes after moving the source into a `DataAPI` or trader.
+///
+/// # Example
+///
+/// ```
+/// use seventh_core::api::{DataAPI, Decimal, Quote, ScriptedSource};
+///
+/// let source = ScriptedSource::new();
+/// source.push_quote("BTC", Quote::new(101.into(), 99.into(), 100.into()));
+/// source.set_historical("BTC", vec![98.0, 99.5, 100.0]);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let mut data = DataAPI::with_source(source);
+/// data.update("BTC").await.unwrap();
+/// assert_eq!(data.last_ref().unwrap().buyprice(), Decimal::from(101));
+/// # });
+/// ```
+#[derive(Clone, Default)]
+pub struct ScriptedSource {
+    scripts: Arc<Mutex<HashMap<Pair, Script>>>,
+}
+
+impl ScriptedSource {
+    pub fn new() -> Self {
+        ScriptedSource::default()
+    }
+
+    /// Append a quote to the script for `pair`.
+    pub fn push_quote<P: Into<Pair>>(&self, pair: P, quote: Quote) {
+        let mut scripts = self.scripts.lock().expect("Lock script");
+        scripts.entry(pair.into()).or_default().quotes.push_back(quote);
+    }
+
+    /// Replace the price history returned for `pair` with bare prices, taken five minutes
+    /// apart up to now with zero volume.
+    pub fn set_historical<P: Into<Pair>>(&self, pair: P, historical: Vec<f32>) {
+        self.set_history(pair, History::from_values(&historical, Utc::now(), Duration::minutes(5)));
+    }
+
+    /// Replace the price and volume history returned for `pair`.
+    pub fn set_history<P: Into<Pair>>(&self, pair: P, history: History) {
+        let mut scripts = self.scripts.lock().expect("Lock script");
+        scripts.entry(pair.into()).or_default().history = history;
+    }
+
+    /// Set how sizes and prices for `pair` are rounded when it is traded.
+    pub fn set_increments<P: Into<Pair>>(&self, pair: P, increments: Increments) {
+        let mut scripts = self.scripts.lock().expect("Lock script");
+        scripts.entry(pair.into()).or_default().increments = increments;
+    }
+}
+
+#[async_trait]
+impl PriceSource for ScriptedSource {
+    async fn quote(&self, pair: &Pair) -> Result<Quote> {
+        let mut scripts = self.scripts.lock().expect("Lock script");
+        let script = scripts
+            .get_mut(pair)
+            .ok_or_else(|| Error::UnknownSymbol(pair.to_string()))?;
+
+        if let Some(quote) = script.quotes.pop_front() {
+            script.last = Some(quote);
+        }
+
+        script.last.ok_or_else(|| Error::UnknownSymbol(pair.to_string()))
+    }
+
+    /// Returns the whole scripted history, whatever the lookback.
+    async fn historical(&self, pair: &Pair, _window: &Window) -> Result<History> {
+        let scripts = self.scripts.lock().expect("Lock script");
+        scripts
+            .get(pair)
+            .map(|s| s.history.clone())
+            .ok_or_else(|| Error::UnknownSymbol(pair.to_string()))
+    }
+
+    fn increments(&self, pair: &Pair) -> Increments {
+        let scripts = self.scripts.lock().expect("Lock script");
+        scripts.get(pair).map(|s| s.increments).unwrap_or_default()
+    }
+}
@@ -0,0 +1,284 @@
+//! Registry of the coins the data APIs know how to price
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use crate::api::endpoints::Endpoints;
+use crate::api::http;
+use crate::api::money::{Decimal, Increments};
+use crate::api::pair::Pair;
+use crate::error::{Error, Result};
+
+/// Coins known without loading any listings, with their CoinGecko ids.
+const BUILTIN: [(&str, &str); 4] = [
+    ("BTC", "bitcoin"),
+    ("ETH", "ethereum"),
+    ("ETC", "ethereum-classic"),
+    ("LTC", "litecoin"),
+];
+
+#[derive(Deserialize, Debug)]
+struct CoinListing {
+    id: String,
+    symbol: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Product {
+    base_currency: String,
+    quote_currency: String,
+    base_increment: Decimal,
+    quote_increment: Decimal,
+}
+
+fn listed() -> bool {
+    true
+}
+
+/// What the registry knows about a single ticker.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Symbol {
+    /// CoinGecko coin id, used to fetch price history.
+    #[serde(default)]
+    pub coingecko: Option<String>,
+    /// Whether Coinbase lists the coin, so that quotes can be fetched for it.
+    #[serde(default = "listed")]
+    pub coinbase: bool,
+}
+
+impl Symbol {
+    pub fn new(coingecko: &str) -> Self {
+        Symbol { coingecko: Some(coingecko.to_owned()), coinbase: true }
+    }
+}
+
+/// Mapping from tickers such as "BTC" to the identifiers each provider uses.
+///
+/// A new registry knows BTC, ETH, ETC and LTC. `load_listings` adds every coin listed by
+/// CoinGecko and Coinbase, `load_config` adds or overrides entries from a config file,
+/// and `save_cache`/`load_cache` keep a copy on disk so the listings need not be fetched
+/// on every start.
+///
+/// The registry also records the rounding `Increments` of each Coinbase product, which
+/// `load_listings` fills in. Products without an entry use `Increments::default`.
+///
+/// CoinGecko reuses tickers across unrelated coins. Tickers that map to more than one
+/// CoinGecko id are left without one, and need an entry in the config file to get price
+/// history.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::path::Path;
+/// use std::time::Duration;
+/// use seventh_core::api::{DataAPI, Endpoints, WebSource};
+/// use seventh_core::api::symbols::SymbolRegistry;
+///
+/// # async fn run() -> seventh_core::Result<()> {
+/// let endpoints = Endpoints::production();
+/// let cache = Path::new("symbols.json");
+///
+/// let mut symbols = match SymbolRegistry::load_cache(cache, Duration::from_secs(86400))? {
+///     Some(symbols) => symbols,
+///     None => {
+///         let mut symbols = SymbolRegistry::new();
+///         symbols.load_listings(&endpoints.client()?, &endpoints).await?;
+///         symbols.save_cache(cache)?;
+///         symbols
+///     }
+/// };
+/// symbols.load_config(Path::new("Coins.toml"))?;
+///
+/// let source = WebSource::with_endpoints(endpoints)?.with_symbols(symbols);
+/// let mut data = DataAPI::with_source(source);
+/// data.update("SOL").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SymbolRegistry {
+    symbols: BTreeMap<String, Symbol>,
+    /// Keyed by product, such as "BTC-USD".
+    #[serde(default)]
+    increments: BTreeMap<String, Increments>,
+}
+
+impl SymbolRegistry {
+    /// Create a registry holding only the built-in coins.
+    pub fn new() -> Self {
+        let symbols = BUILTIN
+            .iter()
+            .map(|(ticker, id)| (ticker.to_string(), Symbol::new(id)))
+            .collect();
+
+        SymbolRegistry { symbols, increments: BTreeMap::new() }
+    }
+
+    /// Create a registry with no coins at all.
+    pub fn empty() -> Self {
+        SymbolRegistry { symbols: BTreeMap::new(), increments: BTreeMap::new() }
+    }
+
+    /// Look up `ticker`, ignoring case.
+    pub fn get(&self, ticker: &str) -> Result<&Symbol> {
+        self.symbols
+            .get(&ticker.to_uppercase())
+            .ok_or_else(|| Error::UnknownSymbol(ticker.to_owned()))
+    }
+
+    /// Get the CoinGecko id for `ticker`.
+    pub fn coingecko_id(&self, ticker: &str) -> Result<&str> {
+        self.get(ticker)?
+            .coingecko
+            .as_deref()
+            .ok_or_else(|| Error::UnknownSymbol(ticker.to_owned()))
+    }
+
+    /// Whether `ticker` can be quoted from Coinbase.
+    pub fn on_coinbase(&self, ticker: &str) -> bool {
+        self.get(ticker).map(|s| s.coinbase).unwrap_or(false)
+    }
+
+    /// Get how sizes and prices for `pair` are rounded on Coinbase.
+    pub fn increments(&self, pair: &Pair) -> Increments {
+        self.increments.get(&pair.to_string()).copied().unwrap_or_default()
+    }
+
+    /// Set how sizes and prices for `pair` are rounded.
+    pub fn set_increments(&mut self, pair: &Pair, increments: Increments) {
+        self.increments.insert(pair.to_string(), increments);
+    }
+
+    /// Add or replace the entry for `ticker`.
+    pub fn insert(&mut self, ticker: &str, symbol: Symbol) {
+        self.symbols.insert(ticker.to_uppercase(), symbol);
+    }
+
+    /// All known tickers, in alphabetical order.
+    pub fn tickers(&self) -> impl Iterator<Item = &str> {
+        self.symbols.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Add the entries of `other` that this registry does not already have.
+    ///
+    /// Existing CoinGecko ids are kept, and a coin is on Coinbase if either registry says
+    /// so.
+    pub fn merge(&mut self, other: SymbolRegistry) {
+        for (ticker, symbol) in other.symbols {
+            let entry = self
+                .symbols
+                .entry(ticker)
+                .or_insert(Symbol { coingecko: None, coinbase: false });
+
+            if entry.coingecko.is_none() {
+                entry.coingecko = symbol.coingecko;
+            }
+            entry.coinbase |= symbol.coinbase;
+        }
+        for (product, increments) in other.increments {
+            self.increments.entry(product).or_insert(increments);
+        }
+    }
+
+    /// Fetch the CoinGecko coin list and the Coinbase product list and merge them in.
+    pub async fn load_listings(&mut self, client: &reqwest::Client, endpoints: &Endpoints) -> Result<()> {
+        let coingecko = &endpoints.coingecko;
+        let exchange = &endpoints.exchange;
+        let (coins, products) = tokio::try_join!(
+            http::send::<Vec<CoinListing>, _>(coingecko, true, || Ok(client.get(&coingecko.url("/coins/list")))),
+            http::send::<Vec<Product>, _>(exchange, true, || Ok(client.get(&exchange.url("/products"))))
+        )?;
+
+        let mut ids: HashMap<String, Vec<String>> = HashMap::new();
+        for coin in coins {
+            ids.entry(coin.symbol.to_uppercase()).or_default().push(coin.id);
+        }
+
+        let mut listed = SymbolRegistry::empty();
+        for (ticker, mut ids) in ids {
+            let coingecko = if ids.len() == 1 { ids.pop() } else { None };
+            listed.insert(&ticker, Symbol { coingecko, coinbase: false });
+        }
+        for product in products {
+            let pair = Pair::new(&product.base_currency, &product.quote_currency);
+            listed.set_increments(&pair, Increments::new(product.base_increment, product.quote_increment));
+
+            let ticker = product.base_currency.to_uppercase();
+            let entry = listed
+                .symbols
+                .entry(ticker)
+                .or_insert(Symbol { coingecko: None, coinbase: false });
+            entry.coinbase = true;
+        }
+
+        self.merge(listed);
+        Ok(())
+    }
+
+    /// Add or override entries from the `coins` table of a config file.
+    ///
+    /// Any format supported by the `config` crate works; in TOML:
+    ///
+    /// ```toml
+    /// [coins.SOL]
+    /// coingecko = "solana"
+    ///
+    /// [coins.WBTC]
+    /// coingecko = "wrapped-bitcoin"
+    /// coinbase = false
+    /// ```
+    pub fn load_config(&mut self, path: &Path) -> Result<()> {
+        let mut settings = config::Config::default();
+        settings
+            .merge(config::File::from(path))
+            .map_err(|e| Error::Decode(e.to_string()))?;
+
+        let coins: HashMap<String, Symbol> = settings
+            .get("coins")
+            .map_err(|e| Error::Decode(e.to_string()))?;
+
+        for (ticker, symbol) in coins {
+            self.insert(&ticker, symbol);
+        }
+        Ok(())
+    }
+
+    /// Write the registry to `path` as JSON.
+    pub fn save_cache(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Read a registry written by `save_cache`.
+    ///
+    /// Returns `None` if there is no cache at `path`, or if it is older than `max_age`.
+    pub fn load_cache(path: &Path, max_age: Duration) -> Result<Option<Self>> {
+        let modified = match fs::metadata(path) {
+            Ok(meta) => meta.modified()?,
+            Err(_) => return Ok(None),
+        };
+
+        let age = SystemTime::now().duration_since(modified).unwrap_or_default();
+        if age > max_age {
+            return Ok(None);
+        }
+
+        Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+    }
+}
+
+impl Default for SymbolRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,57 +1,270 @@
 //! Place orders using the CoinBase API
 
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, Duration, Utc};
+use rand::Rng;
+use reqwest::Method;
+use tokio::sync::mpsc;
+
+use crate::api::auth::{Authenticator, Bearer, Request};
+use crate::api::book::Side;
+use crate::api::endpoints::Endpoints;
+use crate::api::http;
+use crate::api::money::Decimal;
+use crate::api::orders::{Order, OrderEvent, OrderPage, OrderPoller, OrderQuery, OrderType, Orders, TimeInForce};
+use crate::api::pair::Pair;
+use crate::error::{Error, Result};
+
+/// How long a quote can be committed for by default, in seconds.
+const DEFAULT_QUOTE_TTL: i64 = 60;
+
+/// An amount of a currency, as Coinbase reports it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Amount {
+    pub amount: Decimal,
+    pub currency: String,
+}
+
+/// How far Coinbase has got with an order.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    /// Accepted, but not yet settled.
+    Created,
+    /// Settled, with the amounts in the receipt.
+    Completed,
+    /// Cancelled before it settled.
+    Canceled,
+    /// Could not be settled, such as when the payment was declined.
+    Failed,
+    /// A status this version does not know about.
+    #[serde(other)]
+    Unknown,
+}
+
+impl OrderStatus {
+    /// Whether the order has stopped changing.
+    pub fn is_final(self) -> bool {
+        matches!(self, OrderStatus::Completed | OrderStatus::Canceled | OrderStatus::Failed)
+    }
+}
+
+/// What Coinbase reports about an order it accepted.
+///
+/// `amount` is what was bought or sold, in the base currency. For a buy `total` is what
+/// was paid including the fee; for a sell it is what was received after the fee. For
+/// orders placed with `BrokerAPI::place_order` these are what has filled so far.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderReceipt {
+    pub id: String,
+    #[serde(rename = "resource")]
+    pub side: Side,
+    pub status: OrderStatus,
+    pub amount: Amount,
+    pub total: Amount,
+    /// The total before the fee.
+    pub subtotal: Amount,
+    pub fee: Amount,
+    /// Whether the order was placed outright rather than left to be committed later.
+    pub committed: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// When the funds become available, if Coinbase said.
+    pub payout_at: Option<DateTime<Utc>>,
+    /// The order as placed, for orders placed with `BrokerAPI::place_order`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<Order>,
+}
+
+impl OrderReceipt {
+    /// Whether `amount` and `total` were actually traded: the order completed, or it was
+    /// placed with `BrokerAPI::place_order` and filled in part before it was cancelled.
+    pub fn has_fills(&self) -> bool {
+        match self.status {
+            OrderStatus::Completed => true,
+            OrderStatus::Canceled => self.order.is_some() && !self.amount.amount.is_zero(),
+            _ => false,
+        }
+    }
+}
+
 #[derive(Deserialize, Debug)]
-struct Amount {
-    amount: String,
-    currency: String,
+struct OrderData {
+    data: OrderReceipt,
 }
 
 #[derive(Deserialize, Debug)]
-struct TransactionMethod {
-    id: String,
-    resource: String,
-    resource_path: String,
+struct Pagination {
+    next_starting_after: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
-struct Order {
-    id: String,
+struct OrderList {
+    pagination: Pagination,
+    data: Vec<OrderReceipt>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CancelResult {
+    success: bool,
+    failure_reason: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CancelData {
+    results: Vec<CancelResult>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CreateSuccess {
+    order_id: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct CreateFailure {
+    error: Option<String>,
+    message: Option<String>,
+    new_order_failure_reason: Option<String>,
+    preview_failure_reason: Option<String>,
+}
+
+/// Coinbase Advanced Trade `POST /api/v3/brokerage/orders` response.
+#[derive(Deserialize, Debug)]
+struct CreateOrder {
+    success: bool,
+    success_response: Option<CreateSuccess>,
+    error_response: Option<CreateFailure>,
+}
+
+/// An order as the Coinbase Advanced Trade API reports it.
+#[derive(Deserialize, Debug)]
+struct BrokerageOrder {
+    order_id: String,
+    product_id: String,
     status: String,
-    payment_method: TransactionMethod,
-    transaction: TransactionMethod,
-    amount: Amount,
-    total: Amount,
-    subtotal: Amount,
-    created_at: String,
-    updated_at: String,
-    resource: String,
-    resource_path: String,
-    committed: bool,
-    instant: bool,
-    fee: Amount,
-    payout_at: String,
+    #[serde(default)]
+    filled_size: Decimal,
+    #[serde(default)]
+    filled_value: Decimal,
+    #[serde(default)]
+    total_fees: Decimal,
+    #[serde(default)]
+    total_value_after_fees: Decimal,
+    created_time: DateTime<Utc>,
+    last_fill_time: Option<DateTime<Utc>>,
+}
+
+impl BrokerageOrder {
+    /// Update `placed`, the receipt from placing this order, with what has filled.
+    fn receipt(self, placed: &OrderReceipt) -> OrderReceipt {
+        let pair = Pair::from(&self.product_id);
+        let status = match self.status.as_str() {
+            "PENDING" | "OPEN" | "QUEUED" | "CANCEL_QUEUED" => OrderStatus::Created,
+            "FILLED" => OrderStatus::Completed,
+            "CANCELLED" | "EXPIRED" => OrderStatus::Canceled,
+            "FAILED" => OrderStatus::Failed,
+            _ => OrderStatus::Unknown,
+        };
+
+        OrderReceipt {
+            id: self.order_id,
+            side: placed.side,
+            status,
+            amount: Amount { amount: self.filled_size, currency: pair.base },
+            total: Amount { amount: self.total_value_after_fees, currency: pair.quote.clone() },
+            subtotal: Amount { amount: self.filled_value, currency: pair.quote.clone() },
+            fee: Amount { amount: self.total_fees, currency: pair.quote },
+            committed: true,
+            created_at: self.created_time,
+            updated_at: self.last_fill_time.unwrap_or(self.created_time),
+            payout_at: None,
+            order: placed.order.clone(),
+        }
+    }
 }
 
 #[derive(Deserialize, Debug)]
-struct OrderData {
-    pub data: Order,
+struct BrokerageOrderData {
+    order: BrokerageOrder,
+}
+
+#[derive(Deserialize, Debug)]
+struct Time {
+    iso: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TimeData {
+    data: Time,
+}
+
+/// An uncommitted order, showing what it would cost before it is placed.
+///
+/// Coinbase holds the quoted price for a short while. Pass `id` to `BrokerAPI::commit`
+/// before `expires_at` to place the order; after that the quote is dropped and the order
+/// has to be quoted again.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct OrderQuote {
+    pub id: String,
+    pub side: Side,
+    /// What would be bought or sold, in the base currency.
+    pub amount: Amount,
+    /// Price per unit of the base currency, before the fee.
+    pub price: Decimal,
+    /// The total before the fee.
+    pub subtotal: Amount,
+    pub fee: Amount,
+    /// What would be paid for a buy, or received for a sell, including the fee.
+    pub total: Amount,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl OrderQuote {
+    fn new(receipt: OrderReceipt, ttl: Duration) -> Self {
+        let price = if receipt.amount.amount.is_zero() {
+            Decimal::ZERO
+        } else {
+            receipt.subtotal.amount / receipt.amount.amount
+        };
+
+        OrderQuote {
+            id: receipt.id,
+            side: receipt.side,
+            amount: receipt.amount,
+            price,
+            subtotal: receipt.subtotal,
+            fee: receipt.fee,
+            total: receipt.total,
+            created_at: receipt.created_at,
+            expires_at: Utc::now() + ttl,
+        }
+    }
+
+    /// Whether the quote can no longer be committed.
+    pub fn is_expired(&self) -> bool {
+        Utc::now() >= self.expires_at
+    }
 }
 
 #[derive(Debug)]
 struct Transaction {
-    amount: f32,
+    amount: Decimal,
     currency: String,
     payment_method: String,
-    isbuy: bool,
+    commit: bool,
 }
 
 impl Transaction {
-    fn new(amount: f32, currency: &str, payment_method: &str, isbuy: bool) -> Self {
+    fn new(amount: Decimal, currency: &str, payment_method: &str, commit: bool) -> Self {
         Transaction {
-            amount: amount,
+            amount,
             currency: currency.to_string(),
             payment_method: payment_method.to_string(),
-            isbuy,
+            commit,
         }
     }
 
@@ -59,71 +272,471 @@ impl Transaction {
         serde_json::json!({
             "amount": self.amount,
             "currency": self.currency,
-            "payment_method": self.payment_method
+            "payment_method": self.payment_method,
+            "commit": self.commit
         })
         .to_string()
     }
 }
 
+/// Sends authenticated requests for one Coinbase account.
+#[derive(Clone)]
+pub(crate) struct Session {
+    client: reqwest::Client,
+    endpoints: Endpoints,
+    auth: Arc<dyn Authenticator>,
+    account: String,
+}
+
+impl Session {
+    /// Look up the `side` order `id`.
+    pub(crate) async fn order(&self, side: Side, id: &str) -> Result<OrderReceipt> {
+        let path = format!("/v2/accounts/{}/{}/{}", self.account, resource(side), id);
+        Ok(self.get::<OrderData>(path).await?.data)
+    }
+
+    /// Look up the order `receipt` is for, from whichever API it was placed with.
+    pub(crate) async fn track(&self, receipt: &OrderReceipt) -> Result<OrderReceipt> {
+        if receipt.order.is_none() {
+            return self.order(receipt.side, &receipt.id).await;
+        }
+        let path = format!("/api/v3/brokerage/orders/historical/{}", receipt.id);
+        Ok(self.get::<BrokerageOrderData>(path).await?.order.receipt(receipt))
+    }
+
+    async fn get<T: for<'de> serde::Deserialize<'de>>(&self, path: String) -> Result<T> {
+        self.send(Method::GET, path, String::new(), true).await
+    }
+
+    /// Orders are not idempotent, so a failed order is never retried.
+    async fn post<T: for<'de> serde::Deserialize<'de>>(&self, path: String, body: String) -> Result<T> {
+        self.send(Method::POST, path, body, false).await
+    }
+
+    async fn send<T>(&self, method: Method, path: String, body: String, idempotent: bool) -> Result<T>
+    where
+        T: for<'de> serde::Deserialize<'de>,
+    {
+        let provider = &self.endpoints.coinbase;
+        let url = provider.url(&path);
+
+        http::send(provider, idempotent, || {
+            let mut request = self.client.request(method.clone(), &url);
+            for (name, value) in self.sign(method.as_str(), &url, &body)? {
+                request = request.header(name.as_str(), value.as_str());
+            }
+            if body.is_empty() {
+                Ok(request)
+            } else {
+                Ok(request.header("Content-Type", "application/json").body(body.clone()))
+            }
+        })
+        .await
+    }
+
+    fn sign(&self, method: &str, url: &str, body: &str) -> Result<Vec<(String, String)>> {
+        let url = reqwest::Url::parse(url).map_err(|e| Error::Decode(format!("invalid URL {}: {}", url, e)))?;
+        let request = Request {
+            method,
+            host: url.host_str().unwrap_or_default(),
+            path: url.path(),
+            query: url.query(),
+            body,
+            time: Utc::now(),
+        };
+
+        self.auth.headers(&request)
+    }
+}
+
+/// The path segment for `side` orders in the Coinbase API.
+fn resource(side: Side) -> &'static str {
+    match side {
+        Side::Buy => "buys",
+        Side::Sell => "sells",
+    }
+}
+
+/// The Coinbase Advanced Trade `order_configuration` for `order`, which is valid.
+fn configuration(order: &Order) -> Result<serde_json::Value> {
+    let size = order.amount;
+    let post_only = order.post_only;
+    let direction = match order.side {
+        Side::Buy => "STOP_DIRECTION_STOP_UP",
+        Side::Sell => "STOP_DIRECTION_STOP_DOWN",
+    };
+
+    let configuration = match (order.kind, order.time_in_force) {
+        (OrderType::Market, TimeInForce::FillOrKill) if order.is_funds() => {
+            serde_json::json!({ "market_market_fok": { "quote_size": size } })
+        }
+        (OrderType::Market, TimeInForce::FillOrKill) => serde_json::json!({ "market_market_fok": { "base_size": size } }),
+        (OrderType::Market, _) if order.is_funds() => serde_json::json!({ "market_market_ioc": { "quote_size": size } }),
+        (OrderType::Market, _) => serde_json::json!({ "market_market_ioc": { "base_size": size } }),
+        (OrderType::Limit { limit }, TimeInForce::GoodTilCancelled) => serde_json::json!({
+            "limit_limit_gtc": { "base_size": size, "limit_price": limit, "post_only": post_only }
+        }),
+        (OrderType::Limit { limit }, TimeInForce::GoodTilTime(end)) => serde_json::json!({
+            "limit_limit_gtd": { "base_size": size, "limit_price": limit, "end_time": end, "post_only": post_only }
+        }),
+        (OrderType::Limit { limit }, TimeInForce::ImmediateOrCancel) => {
+            serde_json::json!({ "sor_limit_ioc": { "base_size": size, "limit_price": limit } })
+        }
+        (OrderType::Limit { limit }, TimeInForce::FillOrKill) => {
+            serde_json::json!({ "limit_limit_fok": { "base_size": size, "limit_price": limit } })
+        }
+        (OrderType::StopLimit { stop, limit }, TimeInForce::GoodTilTime(end)) => serde_json::json!({
+            "stop_limit_stop_limit_gtd": {
+                "base_size": size,
+                "limit_price": limit,
+                "stop_price": stop,
+                "end_time": end,
+                "stop_direction": direction
+            }
+        }),
+        (OrderType::StopLimit { stop, limit }, _) => serde_json::json!({
+            "stop_limit_stop_limit_gtc": {
+                "base_size": size,
+                "limit_price": limit,
+                "stop_price": stop,
+                "stop_direction": direction
+            }
+        }),
+        (OrderType::Stop { .. }, _) => {
+            return Err(Error::Order(
+                "Coinbase has no stop market orders; place a stop-limit order instead".to_owned(),
+            ))
+        }
+    };
+    Ok(configuration)
+}
+
 /// Active API for placing trades using the Coinbase trading API.
 ///
 /// Requires a valid authentication token, account token, and payment method token. See
-/// the Coinbase API documentation for details.
+/// the Coinbase API documentation for details. The token is sent as an OAuth bearer
+/// token; use `with_authenticator` to sign requests with an API key instead.
+///
+/// Amounts are sent exactly as given, as decimal strings. Round them to the product's
+/// `Increments` first, as the traders do, or Coinbase may reject the order.
+///
+/// Every accepted order returns an `OrderReceipt`, which is also kept in `history`.
+/// Orders usually settle some time after they are placed: `refresh` looks up every order
+/// that has not settled yet and updates `history`, and `poll` does so in the background.
+/// Each change is sent as an `OrderEvent` to every receiver from `events`.
+///
+/// `buy` and `sell` place orders straight away. To see the fee-inclusive total first,
+/// `quote_buy` or `quote_sell` instead, and `commit` the quote to place the order.
+/// Limit, stop-limit and other orders with a time in force go through `place_order`.
 pub struct BrokerAPI {
-    client: reqwest::Client,
-    history: Vec<Transaction>,
-    auth: String,
-    account: String,
+    session: Session,
+    orders: Arc<Orders>,
+    quotes: HashMap<String, OrderQuote>,
+    quote_ttl: Duration,
     payment: String,
 }
 
 impl BrokerAPI {
-    #[tokio::main]
-    async fn post<T: for<'de> serde::Deserialize<'de>>(&self, endpoint: String, data: &Transaction) -> Result<T, reqwest::Error> {
-        let response = self.client
-            .post(&endpoint)
-            .header("Content-Type", "application/json")
-            .bearer_auth(self.auth.clone())
-            .body(data.json())
-            .send()
-            .await?;
-
-        let result: T = response.json().await?;
+    pub fn new(auth: &str, account: &str, payment_method: &str) -> Self {
+        BrokerAPI::with_client(reqwest::Client::new(), Endpoints::production(), auth, account, payment_method)
+    }
 
-        Ok(result)
+    /// Create a `BrokerAPI` that places orders with the Coinbase API at `endpoints`.
+    pub fn with_endpoints(endpoints: Endpoints, auth: &str, account: &str, payment_method: &str) -> Result<Self> {
+        let client = endpoints.client()?;
+        Ok(BrokerAPI::with_client(client, endpoints, auth, account, payment_method))
     }
 
-    pub fn new(auth: &str, account: &str, payment_method: &str) -> Self {
+    /// Create a `BrokerAPI` that places orders with the Coinbase API at `endpoints`
+    /// through an existing `client`.
+    pub fn with_client(
+        client: reqwest::Client,
+        endpoints: Endpoints,
+        auth: &str,
+        account: &str,
+        payment_method: &str,
+    ) -> Self {
         BrokerAPI {
-            client: reqwest::Client::new(),
-            history: vec![],
-            auth: auth.to_owned(),
-            account: account.to_owned(),
+            session: Session { client, endpoints, auth: Arc::new(Bearer::new(auth)), account: account.to_owned() },
+            orders: Arc::new(Orders::default()),
+            quotes: HashMap::new(),
+            quote_ttl: Duration::seconds(DEFAULT_QUOTE_TTL),
             payment: payment_method.to_owned()
         }
     }
 
-    pub fn sell(&mut self, amount: f32, currency: &str) {
-        let trans = Transaction::new(amount, currency, &self.payment, false);
-        let endpoint = format!("https://api.coinbase.com/v2/accounts/{}/sells", self.account);
+    /// Authenticate requests with `auth` instead of the bearer token given on creation.
+    pub fn with_authenticator<A: Authenticator + 'static>(mut self, auth: A) -> Self {
+        self.session.auth = Arc::new(auth);
+        self
+    }
+
+    /// Get how far the exchange's clock is ahead of the local one, from its time endpoint.
+    ///
+    /// The local time is taken halfway through the request, so the result is accurate to
+    /// about half the round trip. The request is timed after waiting for the rate limit
+    /// and is not retried, so neither wait is counted in the round trip.
+    pub async fn clock_skew(&self) -> Result<Duration> {
+        let provider = &self.session.endpoints.coinbase;
+        let request = self.session.client.get(&provider.url("/v2/time"));
+
+        let (time, local) = http::send_timed::<TimeData>(provider, request).await?;
+        Ok(time.data.iso - local)
+    }
+
+    /// Check the local clock is within `max_skew` of the exchange's, failing with
+    /// `Error::Auth` if not, and return the skew.
+    ///
+    /// Signed requests carry the local time, and Coinbase rejects them once it is more
+    /// than 30 seconds off, so check before trading with an `HmacKey` or `JwtKey`.
+    pub async fn check_clock(&self, max_skew: StdDuration) -> Result<Duration> {
+        let skew = self.clock_skew().await?;
+        let off = skew.num_milliseconds().unsigned_abs() as u128;
+
+        if off > max_skew.as_millis() {
+            return Err(Error::Auth(format!(
+                "local clock is {}ms off the exchange's, more than the {}ms allowed",
+                skew.num_milliseconds(),
+                max_skew.as_millis()
+            )));
+        }
+        Ok(skew)
+    }
+
+    /// Get the latest receipts for every order placed so far, oldest first.
+    pub fn history(&self) -> Vec<OrderReceipt> {
+        self.orders.all()
+    }
+
+    /// Get the latest receipt for the order placed as `id`, if it was placed here.
+    pub fn receipt(&self, id: &str) -> Option<OrderReceipt> {
+        self.orders.get(id)
+    }
+
+    /// Receive an `OrderEvent` for every order placed, and every change to one, from now on.
+    pub fn events(&self) -> mpsc::UnboundedReceiver<OrderEvent> {
+        self.orders.subscribe()
+    }
+
+    /// Let quotes be committed for up to `ttl` after they are made.
+    pub fn with_quote_ttl(mut self, ttl: StdDuration) -> Self {
+        self.quote_ttl = Duration::from_std(ttl).unwrap_or(self.quote_ttl);
+        self
+    }
 
-        match self.post::<OrderData>(endpoint, &trans) {
-            Ok(order) => println!("{:?}", order.data),
-            Err(e) => panic!("Error placing sell: {}", e)
+    /// Get the quotes that have not been committed or expired yet.
+    pub fn quotes(&self) -> impl Iterator<Item = &OrderQuote> {
+        self.quotes.values().filter(|quote| !quote.is_expired())
+    }
+
+    /// Forget every expired quote, returning them.
+    pub fn expire(&mut self) -> Vec<OrderQuote> {
+        let expired: Vec<String> = self.quotes.values().filter(|q| q.is_expired()).map(|q| q.id.clone()).collect();
+        expired.iter().filter_map(|id| self.quotes.remove(id)).collect()
+    }
+
+    /// Sell `amount` of `pair.base`.
+    pub async fn sell(&mut self, pair: &Pair, amount: Decimal) -> Result<OrderReceipt> {
+        let trans = Transaction::new(amount, &pair.base, &self.payment, true);
+        self.place(Side::Sell, &trans).await
+    }
+
+    /// Buy `amount` worth of `pair.base`, with `amount` in `pair.quote`.
+    pub async fn buy(&mut self, pair: &Pair, amount: Decimal) -> Result<OrderReceipt> {
+        let trans = Transaction::new(amount, &pair.quote, &self.payment, true);
+        self.place(Side::Buy, &trans).await
+    }
+
+    /// Place `order` on `pair` with the Coinbase Advanced Trade API.
+    ///
+    /// The order is checked with `Order::validate` first. Coinbase has no stop market
+    /// orders, so those fail with `Error::Order` without contacting it, as do orders
+    /// Coinbase rejects, such as post-only orders that would fill straight away.
+    ///
+    /// Coinbase only confirms the order was accepted, so the receipt returned starts with
+    /// nothing filled. `refresh`, `poll` or `get_order` fill it in as the order trades.
+    pub async fn place_order(&mut self, pair: &Pair, order: &Order) -> Result<OrderReceipt> {
+        order.validate()?;
+        let side = match order.side {
+            Side::Buy => "BUY",
+            Side::Sell => "SELL",
+        };
+        let body = serde_json::json!({
+            "client_order_id": format!("{:032x}", rand::thread_rng().gen::<u128>()),
+            "product_id": pair.to_string(),
+            "side": side,
+            "order_configuration": configuration(order)?
+        })
+        .to_string();
+
+        let path = "/api/v3/brokerage/orders".to_owned();
+        let id = match self.session.post::<CreateOrder>(path, body).await? {
+            CreateOrder { success: true, success_response: Some(created), .. } => created.order_id,
+            CreateOrder { error_response, .. } => {
+                let reason = error_response
+                    .and_then(|e| e.message.or(e.new_order_failure_reason).or(e.preview_failure_reason).or(e.error));
+                return Err(Error::Order(format!(
+                    "{} order on {} was rejected: {}",
+                    side.to_lowercase(),
+                    pair,
+                    reason.unwrap_or_else(|| "no reason given".to_owned())
+                )));
+            }
+        };
+
+        let now = Utc::now();
+        let nothing = |currency: &str| Amount { amount: Decimal::ZERO, currency: currency.to_owned() };
+        let receipt = OrderReceipt {
+            id,
+            side: order.side,
+            status: OrderStatus::Created,
+            amount: nothing(&pair.base),
+            total: nothing(&pair.quote),
+            subtotal: nothing(&pair.quote),
+            fee: nothing(&pair.quote),
+            committed: true,
+            created_at: now,
+            updated_at: now,
+            payout_at: None,
+            order: Some(order.clone()),
         };
 
-        self.history.push(trans);
+        self.orders.update(receipt.clone());
+        Ok(receipt)
+    }
+
+    /// Quote selling `amount` of `pair.base`, without placing the order.
+    pub async fn quote_sell(&mut self, pair: &Pair, amount: Decimal) -> Result<OrderQuote> {
+        let trans = Transaction::new(amount, &pair.base, &self.payment, false);
+        self.quote(Side::Sell, &trans).await
     }
 
-    pub fn buy(&mut self, amount: f32, currency: &str) {
-        let trans = Transaction::new(amount, currency, &self.payment, true);
-        let endpoint = format!("https://api.coinbase.com/v2/accounts/{}/buys", self.account);
+    /// Quote buying `amount` worth of `pair.base`, with `amount` in `pair.quote`, without
+    /// placing the order.
+    pub async fn quote_buy(&mut self, pair: &Pair, amount: Decimal) -> Result<OrderQuote> {
+        let trans = Transaction::new(amount, &pair.quote, &self.payment, false);
+        self.quote(Side::Buy, &trans).await
+    }
 
-        match self.post::<OrderData>(endpoint, &trans) {
-            Ok(order) => println!("{:?}", order.data),
-            Err(e) => panic!("Error placing buy: {}", e)
+    /// Place the order quoted as `order_id`.
+    ///
+    /// Fails with `Error::Order` without contacting Coinbase if there is no such quote,
+    /// or if it has expired, in which case the quote is forgotten. The quote is kept if
+    /// placing the order fails, so it can be committed again until it expires.
+    pub async fn commit(&mut self, order_id: &str) -> Result<OrderReceipt> {
+        let quote = match self.quotes.get(order_id) {
+            Some(quote) => quote,
+            None => return Err(Error::Order(format!("no quote {} to commit", order_id))),
         };
+        if quote.is_expired() {
+            let expires_at = quote.expires_at;
+            self.quotes.remove(order_id);
+            return Err(Error::Order(format!("quote {} expired at {}", order_id, expires_at)));
+        }
+
+        let path = format!("/v2/accounts/{}/{}/{}/commit", self.session.account, resource(quote.side), order_id);
+        let receipt = self.session.post::<OrderData>(path, String::new()).await?.data;
+        self.quotes.remove(order_id);
+
+        self.orders.update(receipt.clone());
+        Ok(receipt)
+    }
+
+    /// Look up the `side` order `id`, updating its receipt in `history` if it is there.
+    ///
+    /// Orders in `history` are looked up with the API they were placed with, whatever
+    /// `side` is given.
+    pub async fn get_order(&self, side: Side, id: &str) -> Result<OrderReceipt> {
+        match self.orders.get(id) {
+            Some(placed) => {
+                let receipt = self.session.track(&placed).await?;
+                self.orders.update(receipt.clone());
+                Ok(receipt)
+            }
+            None => self.session.order(side, id).await,
+        }
+    }
+
+    /// List a page of the account's `side` orders, newest first.
+    ///
+    /// Coinbase does not filter by status, so `query.status` is applied to each page as it
+    /// arrives, and a filtered page can hold fewer than `query.limit` orders. Keep asking
+    /// for `OrderPage::next` until it is `None` to see every order.
+    pub async fn list_orders(&self, side: Side, query: &OrderQuery) -> Result<OrderPage> {
+        let mut path = format!("/v2/accounts/{}/{}?limit={}", self.session.account, resource(side), query.limit);
+        if let Some(after) = &query.starting_after {
+            path.push_str(&format!("&starting_after={}", after));
+        }
+        let list = self.session.get::<OrderList>(path).await?;
+
+        let orders = list
+            .data
+            .into_iter()
+            .filter(|order| query.status.map_or(true, |status| order.status == status))
+            .collect();
+        Ok(OrderPage { orders, next: list.pagination.next_starting_after })
+    }
+
+    /// Cancel the order `id`, if it has not settled yet.
+    ///
+    /// Coinbase only cancels orders through its Advanced Trade API, so the request goes
+    /// there. Fails with `Error::Order` if Coinbase refuses, such as when the order has
+    /// already settled. On success, the order's receipt in `history` is marked canceled.
+    pub async fn cancel_order(&self, id: &str) -> Result<()> {
+        let path = "/api/v3/brokerage/orders/batch_cancel".to_owned();
+        let body = serde_json::json!({ "order_ids": [id] }).to_string();
+        let data = self.session.post::<CancelData>(path, body).await?;
+
+        match data.results.into_iter().next() {
+            Some(CancelResult { success: true, .. }) => {
+                if let Some(mut receipt) = self.orders.get(id) {
+                    receipt.status = OrderStatus::Canceled;
+                    receipt.updated_at = Utc::now();
+                    self.orders.update(receipt);
+                }
+                Ok(())
+            }
+            Some(CancelResult { failure_reason, .. }) => Err(Error::Order(format!(
+                "could not cancel {}: {}",
+                id,
+                failure_reason.unwrap_or_else(|| "no reason given".to_owned())
+            ))),
+            None => Err(Error::Decode(format!("no result for cancelling {}", id))),
+        }
+    }
+
+    /// Look up every order in `history` that has not settled yet, and return what changed.
+    ///
+    /// One order failing to be looked up does not stop the rest: every change found is
+    /// recorded, and the failures are returned afterwards.
+    pub async fn refresh(&self) -> Result<Vec<OrderEvent>> {
+        self.orders.refresh(&self.session).await
+    }
+
+    /// Look up every order in `history` that has not settled yet every `interval`, in the
+    /// background, until the returned poller is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside a Tokio runtime.
+    pub fn poll(&self, interval: StdDuration) -> OrderPoller {
+        OrderPoller::start(self.session.clone(), self.orders.clone(), interval)
+    }
+
+    async fn place(&mut self, side: Side, trans: &Transaction) -> Result<OrderReceipt> {
+        let path = format!("/v2/accounts/{}/{}", self.session.account, resource(side));
+        let receipt = self.session.post::<OrderData>(path, trans.json()).await?.data;
+
+        self.orders.update(receipt.clone());
+        Ok(receipt)
+    }
+
+    async fn quote(&mut self, side: Side, trans: &Transaction) -> Result<OrderQuote> {
+        let path = format!("/v2/accounts/{}/{}", self.session.account, resource(side));
+        let receipt = self.session.post::<OrderData>(path, trans.json()).await?.data;
+        let quote = OrderQuote::new(receipt, self.quote_ttl);
 
-        self.history.push(trans);
+        self.quotes.insert(quote.id.clone(), quote.clone());
+        Ok(quote)
     }
 }
@@ -0,0 +1,164 @@
+//! Sanity checks on the snapshots a `DataAPI` fetches
+
+use std::fmt;
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::api::data::CoinData;
+use crate::api::money::Decimal;
+use crate::error::{Error, Result};
+
+/// Something wrong with a `CoinData` snapshot.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Anomaly {
+    /// The buy price is below the sell price.
+    Crossed { buy: Decimal, sell: Decimal },
+    /// A buy, sell or spot price is zero or negative.
+    NonPositive { price: Decimal },
+    /// The spot price moved further from the previous snapshot for the pair than allowed.
+    Jump { previous: Decimal, current: Decimal },
+    /// The prices were taken longer ago than allowed.
+    Stale { age: Duration },
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Anomaly::Crossed { buy, sell } => write!(f, "crossed quote, buy {} below sell {}", buy, sell),
+            Anomaly::NonPositive { price } => write!(f, "non-positive price {}", price),
+            Anomaly::Jump { previous, current } => write!(f, "price jumped from {} to {}", previous, current),
+            Anomaly::Stale { age } => write!(f, "prices are {}s old", age.as_secs()),
+        }
+    }
+}
+
+/// Which anomalies to look for in each snapshot, and what to do about them.
+///
+/// By default crossed quotes, prices that are not positive and prices more than five
+/// minutes old are flagged, and the snapshot is stored with its `CoinData::anomalies`.
+/// With `reject`, an anomalous snapshot is not stored at all and the update fails with
+/// `Error::Invalid` instead.
+///
+/// Jumps are not checked unless `max_jump` is set, since how far a price can fairly move
+/// between snapshots depends on the pair and on how often it is fetched.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use seventh_core::api::{DataAPI, Decimal, ScriptedSource};
+/// use seventh_core::api::validation::Validation;
+///
+/// // Refuse snapshots that moved more than 20% or are over a minute old.
+/// let validation = Validation::new()
+///     .max_jump(Decimal::new(2, 1))
+///     .max_age(Duration::from_secs(60))
+///     .reject();
+/// let data = DataAPI::with_source(ScriptedSource::new()).with_validation(validation);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Validation {
+    /// Check for crossed quotes and prices that are not positive.
+    pub prices: bool,
+    /// Oldest prices to accept, by the time they were taken.
+    pub max_age: Option<Duration>,
+    /// Largest move in the spot price from the previous snapshot, as a fraction of it.
+    pub max_jump: Option<Decimal>,
+    /// Reject anomalous snapshots instead of flagging them.
+    pub reject: bool,
+}
+
+impl Validation {
+    /// Flag crossed, non-positive and stale prices.
+    pub fn new() -> Self {
+        Validation {
+            prices: true,
+            max_age: Some(Duration::from_secs(300)),
+            max_jump: None,
+            reject: false,
+        }
+    }
+
+    /// Check nothing.
+    pub fn none() -> Self {
+        Validation { prices: false, max_age: None, max_jump: None, reject: false }
+    }
+
+    /// Flag prices taken longer than `max_age` ago.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Flag spot prices more than `fraction` away from the previous snapshot's.
+    pub fn max_jump(mut self, fraction: Decimal) -> Self {
+        self.max_jump = Some(fraction);
+        self
+    }
+
+    /// Reject anomalous snapshots instead of flagging them.
+    pub fn reject(mut self) -> Self {
+        self.reject = true;
+        self
+    }
+
+    /// Look for anomalies in `coin`, comparing it with the `previous` snapshot for the
+    /// same pair if there is one.
+    pub fn check(&self, coin: &CoinData, previous: Option<&CoinData>) -> Vec<Anomaly> {
+        let mut anomalies = vec![];
+        let (buy, sell, spot) = (coin.buyprice(), coin.sellprice(), coin.spotprice());
+
+        if self.prices {
+            if buy < sell {
+                anomalies.push(Anomaly::Crossed { buy, sell });
+            }
+            for &price in &[buy, sell, spot] {
+                if price <= Decimal::ZERO {
+                    anomalies.push(Anomaly::NonPositive { price });
+                }
+            }
+        }
+
+        if let Some(max_age) = self.max_age {
+            let age = (Utc::now() - coin.time()).to_std().unwrap_or_default();
+            if age > max_age {
+                anomalies.push(Anomaly::Stale { age });
+            }
+        }
+
+        if let (Some(max_jump), Some(previous)) = (self.max_jump, previous) {
+            let before = previous.spotprice();
+            if before > Decimal::ZERO && ((spot - before) / before).abs() > max_jump {
+                anomalies.push(Anomaly::Jump { previous: before, current: spot });
+            }
+        }
+
+        anomalies
+    }
+
+    /// Check `coin` again just before trading on it, failing with `Error::Invalid` if
+    /// anything is found.
+    ///
+    /// Staleness is measured afresh, so data that has aged since it was fetched fails
+    /// too. Jumps found when the snapshot was fetched also count, since they can only be
+    /// seen against the snapshots before it.
+    pub fn validate(&self, coin: &CoinData) -> Result<()> {
+        let mut anomalies = self.check(coin, None);
+        let jumps = coin.anomalies().iter().filter(|a| matches!(a, Anomaly::Jump { .. }));
+        anomalies.extend(jumps.cloned());
+
+        if anomalies.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Invalid(anomalies))
+        }
+    }
+}
+
+impl Default for Validation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,84 +1,182 @@
 //! Real time backtesting system
 
-use crate::api::data::DataAPI;
-
-pub struct Backtrader {
-    api: DataAPI,
-    coin: String,
-    history: Vec<(f32, Actions)>,
-    account: f32,  // in usd
-    holdings: f32, // in coin
+use crate::api::blocking::DataAPI;
+use crate::api::book::Side;
+use crate::api::cache::Cache;
+use crate::api::data::CoinData;
+use crate::api::endpoints::Endpoints;
+use crate::api::money::{Decimal, Increments};
+use crate::api::orders::Order;
+use crate::api::pair::Pair;
+use crate::api::retention::Retention;
+use crate::api::source::{PriceSource, WebSource};
+use crate::api::validation::Validation;
+use crate::error::Result;
+use crate::simulated::{self, OpenOrders, Prices};
+
+pub struct Backtrader<S: PriceSource = WebSource> {
+    api: DataAPI<S>,
+    pair: Pair,
+    validation: Validation,
+    history: Vec<(Decimal, Actions)>,
+    open: OpenOrders,
+    account: Decimal,  // in quote currency
+    holdings: Decimal, // in base currency
 }
 
-#[derive(Clone)]
+/// A trade to make at the current prices.
+///
+/// Amounts are rounded down to the product's `Increments` before they are traded, and
+/// order prices to the nearest increment.
+#[derive(Clone, Debug, PartialEq)]
 pub enum Actions {
-    Buy(f32),  // amount in usd
-    Sell(f32), // amount in btc
+    Buy(Decimal),  // amount in quote currency
+    Sell(Decimal), // amount in base currency
+    /// Place a market, limit, stop or stop-limit order. Orders that do not fill straight
+    /// away stay open, and are checked against the prices on each later trade. Stop
+    /// orders are simulated only, and fail when trading live.
+    Order(Order),
     Hold,
 }
 
+/// A trader's balances alongside the prices they were valued at.
+///
+/// Prices and balances are exact decimals, serialized as strings; the price history is
+/// for charting and stays approximate.
 #[derive(Serialize)]
 pub struct TraderData {
+    pub currency: String,
     pub historical: Vec<f32>,
-    pub buy: f32,
-    pub sell: f32,
-    pub account: f32,
-    pub holding: f32,
+    pub buy: Decimal,
+    pub sell: Decimal,
+    pub account: Decimal,
+    pub holding: Decimal,
 }
 
 impl Backtrader {
-    pub fn new(account: f32, coin: &str) -> Self {
+    /// Create a `Backtrader` reading prices from Coinbase and CoinGecko.
+    ///
+    /// Prices are cached with the default `Cache` settings, so frequent trades do not
+    /// fetch the full price history each time.
+    pub fn new(account: Decimal, pair: &str) -> Result<Self> {
+        Backtrader::with_endpoints(account, pair, Endpoints::production())
+    }
+
+    /// Create a `Backtrader` that reads prices from the APIs at `endpoints`, with the
+    /// default `Cache`.
+    pub fn with_endpoints(account: Decimal, pair: &str, endpoints: Endpoints) -> Result<Self> {
+        let api = DataAPI::with_source(WebSource::with_endpoints(endpoints)?).with_cache(Cache::new());
+        Backtrader::from_api(account, pair, api)
+    }
+}
+
+impl<S: PriceSource> Backtrader<S> {
+    pub fn with_source(account: Decimal, pair: &str, source: S) -> Result<Self> {
+        Backtrader::from_api(account, pair, DataAPI::with_source(source))
+    }
+
+    fn from_api(account: Decimal, pair: &str, api: DataAPI<S>) -> Result<Self> {
         let mut trader = Backtrader {
-            api: DataAPI::new(),
-            coin: coin.to_owned(),
+            // Trades only ever look at the latest prices.
+            api: api.with_retention(Retention::last(1)),
+            pair: Pair::from(pair),
+            validation: Validation::default(),
             history: vec![],
+            open: OpenOrders::default(),
             account,
-            holdings: 0.0,
+            holdings: Decimal::ZERO,
         };
-        trader.api.update(coin);
-        trader
+        trader.api.update(&trader.pair)?;
+        Ok(trader)
+    }
+
+    /// Set the checks market data must pass before the trader buys or sells on it.
+    ///
+    /// Anomalous snapshots are still fetched and flagged, but trades on them fail with
+    /// `Error::Invalid` and leave the balances untouched.
+    pub fn with_validation(mut self, validation: Validation) -> Self {
+        self.api = self.api.with_validation(Validation { reject: false, ..validation });
+        self.validation = validation;
+        self
     }
 
-    pub fn reset(&mut self, account: f32, coin: &str) {
+    pub fn reset(&mut self, account: Decimal, pair: &str) {
         self.history = vec![];
+        self.open = OpenOrders::default();
         self.account = account;
-        self.coin = coin.to_owned();
-        self.holdings = 0.0;
+        self.pair = Pair::from(pair);
+        self.holdings = Decimal::ZERO;
     }
 
-    pub fn trade(&mut self, action: Actions) {
+    /// Fetch the latest prices, fill any open orders they reach, then take `action`.
+    ///
+    /// Open orders are only filled on prices that pass validation, so a trade fails with
+    /// `Error::Invalid` while any are open, even to `Hold`. Invalid orders, and post-only
+    /// orders that would fill straight away, fail with `Error::Order`.
+    pub fn trade(&mut self, action: Actions) -> Result<()> {
         println!("Making trade");
-        self.api.update(&self.coin);
+        self.api.update(&self.pair)?;
+        if action != Actions::Hold || !self.open.is_empty() {
+            self.validation.validate(self.latest())?;
+        }
         self.history.push((self.account, action.clone()));
+        self.fill_open();
 
         match action {
-            Actions::Buy(usd) => self.buy(usd),
-            Actions::Sell(coin) => self.sell(coin),
-            Actions::Hold => return,
-        };
+            Actions::Buy(amount) => self.buy(amount),
+            Actions::Sell(amount) => self.sell(amount),
+            Actions::Order(order) => self.place(order),
+            Actions::Hold => Ok(()),
+        }
+    }
+
+    fn buy(&mut self, amount: Decimal) -> Result<()> {
+        let price = self.latest().buyprice();
+        self.execute(&Order::market(Side::Buy, amount), price);
+        Ok(())
+    }
+
+    fn sell(&mut self, amount: Decimal) -> Result<()> {
+        let price = self.latest().sellprice();
+        self.execute(&Order::market(Side::Sell, amount), price);
+        Ok(())
     }
 
-    fn buy(&mut self, mut usd: f32) {
-        if usd > self.account {
-            usd = self.account;
+    fn place(&mut self, order: Order) -> Result<()> {
+        let order = order.rounded(&self.increments());
+        if let Some(price) = self.open.place(order.clone(), &self.prices())? {
+            self.execute(&order, price);
         }
-        let price = self.api.last().buyprice();
+        Ok(())
+    }
+
+    fn fill_open(&mut self) {
+        let prices = self.prices();
+        for (order, price) in self.open.fill(&prices) {
+            self.execute(&order, price);
+        }
+    }
 
-        self.account -= usd;
-        self.holdings += usd / price;
+    fn execute(&mut self, order: &Order, price: Decimal) {
+        let increments = self.increments();
+        simulated::execute(order, price, &increments, &mut self.account, &mut self.holdings);
     }
 
-    fn sell(&mut self, coin: f32) {
-        let price = self.api.last().sellprice();
+    /// Get the orders that have not filled yet, oldest first.
+    pub fn open_orders(&self) -> Vec<Order> {
+        self.open.orders()
+    }
 
-        self.account += coin * price;
-        self.holdings -= coin;
+    /// Cancel every open order, returning them.
+    pub fn cancel_orders(&mut self) -> Vec<Order> {
+        self.open.cancel()
     }
 
     pub fn data(&self) -> TraderData {
-        let coins = self.api.last();
+        let coins = self.latest();
 
         TraderData {
+            currency: coins.currency().to_owned(),
             historical: coins.historical(),
             buy: coins.buyprice(),
             sell: coins.sellprice(),
@@ -87,7 +185,20 @@ impl Backtrader {
         }
     }
 
-    pub fn history(&self) -> Vec<(f32, Actions)> {
+    fn latest(&self) -> &CoinData {
+        self.api.last_ref().expect("Trader is updated on creation")
+    }
+
+    fn prices(&self) -> Prices {
+        let coins = self.latest();
+        Prices { buy: coins.buyprice(), sell: coins.sellprice(), time: Some(coins.time()) }
+    }
+
+    fn increments(&self) -> Increments {
+        self.api.source().increments(&self.pair)
+    }
+
+    pub fn history(&self) -> Vec<(Decimal, Actions)> {
         self.history.clone()
     }
 }
@@ -3,11 +3,14 @@
 #[macro_use]
 extern crate rocket;
 
+use rocket::http::Status;
+use rocket::response::status::Custom;
 use rocket::{request::Form, State};
 use std::sync::Mutex;
 
 use seventh_core::backtrader::Actions;
 use seventh_core::historical::{run, Histtrader};
+use seventh_core::Decimal;
 
 struct LockedTrader {
     trader: Mutex<Histtrader>,
@@ -15,13 +18,13 @@ struct LockedTrader {
 
 #[derive(FromForm)]
 struct Transaction {
-    amount: f32,
+    amount: String,
 }
 
 #[get("/")]
 fn index(trader: State<LockedTrader>) -> String {
     let mut lock = trader.trader.lock().expect("Lock state");
-    lock.reset(1000.0, "AAPL");
+    lock.reset(Decimal::new(1000, 0), "AAPL");
     "LIVE".to_owned()
 }
 
@@ -32,18 +35,28 @@ fn data(trader: State<LockedTrader>) -> String {
 }
 
 #[post("/trade/<action>", data = "<trans>")]
-fn trade(action: String, trans: Form<Transaction>, trader: State<LockedTrader>) -> String {
+fn trade(
+    action: String,
+    trans: Form<Transaction>,
+    trader: State<LockedTrader>,
+) -> Result<String, Custom<String>> {
     let mut lock = trader.trader.lock().expect("Lock state");
-    let amount = trans.amount;
+    let amount: Decimal = match trans.amount.parse() {
+        Ok(amount) => amount,
+        Err(e) => return Err(Custom(Status::BadRequest, e.to_string())),
+    };
     println!("{}, {}", amount, action);
 
-    match action.as_str() {
+    let result = match action.as_str() {
         "buy" => lock.trade(Actions::Buy(amount)),
         "sell" => lock.trade(Actions::Sell(amount)),
         _ => lock.trade(Actions::Hold),
     };
 
-    serde_json::to_string(&lock.data()).unwrap()
+    match result {
+        Ok(()) => Ok(serde_json::to_string(&lock.data()).unwrap()),
+        Err(e) => Err(Custom(Status::BadRequest, e.to_string())),
+    }
 }
 
 fn main() {
@@ -56,7 +69,7 @@ fn main() {
 
     rocket::ignite()
         .manage(LockedTrader {
-            trader: Mutex::new(Histtrader::new(data, "aapl", 1000.0, 100)),
+            trader: Mutex::new(Histtrader::new(data, "aapl", Decimal::new(1000, 0), 100)),
         })
         .mount("/", routes![index, data, trade])
         .launch();
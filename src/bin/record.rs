@@ -0,0 +1,107 @@
+//! Record quotes for the configured pairs to rotating files until stopped.
+//!
+//! Settings are read from `Recorder.toml` (or any other format the `config` crate reads):
+//!
+//! ```toml
+//! pairs = ["BTC-USD", "ETH-EUR"]
+//! dir = "market"          # records for BTC-USD go to market/BTC-USD/
+//! format = "csv"          # or "jsonl"
+//! interval = 60           # seconds between polls
+//! rotate_hours = 24       # start a new file after this many hours
+//! stream = false          # take prices from the WebSocket feed instead of polling
+//! ```
+//!
+//! Replay a recording with `history market/BTC-USD`.
+
+use std::thread;
+use std::time::Duration as StdDuration;
+
+use chrono::Duration;
+use futures::StreamExt;
+
+use seventh_core::api::blocking::DataAPI;
+use seventh_core::api::feed::TickerFeed;
+use seventh_core::api::retention::Retention;
+use seventh_core::api::{Endpoints, Pair, Window};
+use seventh_core::recorder::{Format, Recorder};
+
+struct Settings {
+    pairs: Vec<Pair>,
+    dir: String,
+    format: Format,
+    interval: u64,
+    rotate_hours: i64,
+    stream: bool,
+}
+
+fn read_settings() -> Result<Settings, config::ConfigError> {
+    let mut settings = config::Config::default();
+    settings.set_default("dir", "market")?;
+    settings.set_default("format", "csv")?;
+    settings.set_default("interval", 60)?;
+    settings.set_default("rotate_hours", 24)?;
+    settings.set_default("stream", false)?;
+    settings.merge(config::File::with_name("Recorder"))?;
+
+    let pairs: Vec<String> = settings.get("pairs")?;
+    let format = match settings.get_str("format")?.as_str() {
+        "jsonl" => Format::Jsonl,
+        "csv" => Format::Csv,
+        other => return Err(config::ConfigError::Message(format!("unknown format {}", other))),
+    };
+
+    Ok(Settings {
+        pairs: pairs.iter().map(|p| Pair::from(p.as_str())).collect(),
+        dir: settings.get_str("dir")?,
+        format,
+        interval: settings.get_int("interval")? as u64,
+        rotate_hours: settings.get_int("rotate_hours")?,
+        stream: settings.get_bool("stream")?,
+    })
+}
+
+fn poll(settings: &Settings, recorder: &mut Recorder) {
+    // Only the quotes are recorded, so fetch as little history as possible.
+    let mut api = DataAPI::new()
+        .with_window(Window::new(Duration::hours(1)))
+        .with_retention(Retention::last(settings.pairs.len()));
+
+    loop {
+        for (pair, result) in api.update_many(&settings.pairs) {
+            let recorded = result.and_then(|()| recorder.record(api.latest(&pair).expect("Pair was just updated")));
+            if let Err(e) = recorded {
+                eprintln!("{}: {}", pair, e);
+            }
+        }
+        if let Err(e) = recorder.flush() {
+            eprintln!("{}", e);
+        }
+        thread::sleep(StdDuration::from_secs(settings.interval));
+    }
+}
+
+fn stream(settings: &Settings, recorder: &mut Recorder) {
+    let mut runtime = tokio::runtime::Runtime::new().expect("Start runtime");
+    runtime.block_on(async {
+        let mut feed = TickerFeed::subscribe(&Endpoints::production().feed, &settings.pairs);
+        while let Some(coin) = feed.next().await {
+            if let Err(e) = recorder.record(&coin).and_then(|()| recorder.flush()) {
+                eprintln!("{}: {}", coin.pair(), e);
+            }
+        }
+    });
+}
+
+fn main() {
+    let settings = match read_settings() {
+        Ok(settings) => settings,
+        Err(err) => panic!("{}", err),
+    };
+    let mut recorder = Recorder::new(&settings.dir, settings.format).rotate_every(Duration::hours(settings.rotate_hours));
+
+    if settings.stream {
+        stream(&settings, &mut recorder);
+    } else {
+        poll(&settings, &mut recorder);
+    }
+}
@@ -3,10 +3,13 @@
 #[macro_use]
 extern crate rocket;
 
+use rocket::http::Status;
+use rocket::response::status::Custom;
 use rocket::{request::Form, State};
 use std::sync::Mutex;
 
 use seventh_core::backtrader::{Actions, Backtrader};
+use seventh_core::Decimal;
 
 struct LockedTrader {
     trader: Mutex<Backtrader>,
@@ -14,13 +17,13 @@ struct LockedTrader {
 
 #[derive(FromForm)]
 struct Transaction {
-    amount: f32,
+    amount: String,
 }
 
 #[get("/")]
 fn index(trader: State<LockedTrader>) -> String {
     let mut lock = trader.trader.lock().expect("Lock state");
-    lock.reset(1000.0, "BTC");
+    lock.reset(Decimal::new(1000, 0), "BTC");
     "LIVE".to_owned()
 }
 
@@ -31,24 +34,39 @@ fn data(trader: State<LockedTrader>) -> String {
 }
 
 #[post("/trade/<action>", data = "<trans>")]
-fn trade(action: String, trans: Form<Transaction>, trader: State<LockedTrader>) -> String {
+fn trade(
+    action: String,
+    trans: Form<Transaction>,
+    trader: State<LockedTrader>,
+) -> Result<String, Custom<String>> {
     let mut lock = trader.trader.lock().expect("Lock state");
-    let amount = trans.amount;
+    let amount: Decimal = match trans.amount.parse() {
+        Ok(amount) => amount,
+        Err(e) => return Err(Custom(Status::BadRequest, e.to_string())),
+    };
     println!("{}, {}", amount, action);
 
-    match action.as_str() {
+    let result = match action.as_str() {
         "buy" => lock.trade(Actions::Buy(amount)),
         "sell" => lock.trade(Actions::Sell(amount)),
         _ => lock.trade(Actions::Hold),
     };
 
-    serde_json::to_string(&lock.data()).unwrap()
+    match result {
+        Ok(()) => Ok(serde_json::to_string(&lock.data()).unwrap()),
+        Err(e) => Err(Custom(Status::BadGateway, e.to_string())),
+    }
 }
 
 fn main() {
+    let trader = match Backtrader::new(Decimal::new(1000, 0), "BTC") {
+        Ok(trader) => trader,
+        Err(err) => panic!("{}", err),
+    };
+
     rocket::ignite()
         .manage(LockedTrader {
-            trader: Mutex::new(Backtrader::new(1000.0, "BTC")),
+            trader: Mutex::new(trader),
         })
         .mount("/", routes![index, data, trade])
         .launch();
@@ -0,0 +1,115 @@
+//! Error type shared by the data and trading APIs
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::num::ParseFloatError;
+use std::time::Duration;
+
+use crate::api::validation::Anomaly;
+
+/// Everything that can go wrong while talking to a price source or broker.
+#[derive(Debug)]
+pub enum Error {
+    /// The request could not be sent, or the response could not be read.
+    Transport(reqwest::Error),
+    /// The server answered with an unexpected HTTP status.
+    Status { status: u16, body: String },
+    /// A response or file could not be decoded into the expected format.
+    Decode(String),
+    /// The symbol is not known to the price source.
+    UnknownSymbol(String),
+    /// The provider rejected the request for exceeding its rate limit.
+    RateLimited { retry_after: Option<Duration> },
+    /// The credentials were missing or rejected.
+    Auth(String),
+    /// A local file could not be read or written.
+    Io(io::Error),
+    /// The WebSocket feed could not be reached or reported an error.
+    Feed(String),
+    /// Market data failed validation, so it was not stored or traded on.
+    Invalid(Vec<Anomaly>),
+    /// Too few price sources answered to agree on a price.
+    NoConsensus(String),
+    /// An order could not be placed as asked.
+    Order(String),
+}
+
+/// Result type returned throughout `seventh-core`.
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Transport(e) => write!(f, "transport error: {}", e),
+            Error::Status { status, body } => write!(f, "HTTP status {}: {}", status, body),
+            Error::Decode(msg) => write!(f, "could not decode: {}", msg),
+            Error::UnknownSymbol(symbol) => write!(f, "unknown symbol {}", symbol),
+            Error::RateLimited { retry_after: Some(wait) } => {
+                write!(f, "rate limited, retry after {}s", wait.as_secs())
+            }
+            Error::RateLimited { retry_after: None } => write!(f, "rate limited"),
+            Error::Auth(msg) => write!(f, "authentication failed: {}", msg),
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Feed(msg) => write!(f, "feed error: {}", msg),
+            Error::Invalid(anomalies) => {
+                let anomalies: Vec<String> = anomalies.iter().map(Anomaly::to_string).collect();
+                write!(f, "invalid market data: {}", anomalies.join(", "))
+            }
+            Error::NoConsensus(msg) => write!(f, "no consensus: {}", msg),
+            Error::Order(msg) => write!(f, "order error: {}", msg),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Transport(e) => Some(e),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Transport(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Decode(e.to_string())
+    }
+}
+
+impl From<ParseFloatError> for Error {
+    fn from(e: ParseFloatError) -> Self {
+        Error::Decode(e.to_string())
+    }
+}
+
+impl From<csv::Error> for Error {
+    fn from(e: csv::Error) -> Self {
+        if !e.is_io_error() {
+            return Error::Decode(e.to_string());
+        }
+        match e.into_kind() {
+            csv::ErrorKind::Io(e) => Error::Io(e),
+            kind => Error::Decode(format!("{:?}", kind)),
+        }
+    }
+}
+
+impl From<rust_decimal::Error> for Error {
+    fn from(e: rust_decimal::Error) -> Self {
+        Error::Decode(e.to_string())
+    }
+}
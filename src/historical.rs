@@ -3,45 +3,223 @@
 use std::env;
 use std::error::Error;
 use std::ffi::OsString;
-use std::fs::File;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
 
+use rust_decimal::prelude::ToPrimitive;
+
+use crate::api::book::Side;
+use crate::api::money::{Decimal, Increments};
+use crate::api::orders::Order;
+use crate::api::pair::Pair;
 use crate::backtrader::{Actions, TraderData};
+use crate::error;
+use crate::recorder::Record;
+use crate::simulated::{self, OpenOrders, Prices};
 
-#[derive(Debug, Clone)]
+/// Open, high, low, close and volume series, oldest first.
+///
+/// `RawData` is read from CSV files or recordings by `run`, or fetched from Coinbase with
+/// `DataAPI::candles`. Alongside the candles it keeps the price each record can be bought
+/// and sold at: the recorded ask and bid where there are any, or else the low and high.
+#[derive(Debug, Clone, PartialEq)]
 pub struct RawData {
     open: Vec<f32>,
     high: Vec<f32>,
     low: Vec<f32>,
     close: Vec<f32>,
+    volume: Vec<f32>,
+    buy: Vec<Decimal>,
+    sell: Vec<Decimal>,
 }
 
 impl RawData {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         RawData {
             open: vec![],
             high: vec![],
             low: vec![],
             close: vec![],
+            volume: vec![],
+            buy: vec![],
+            sell: vec![],
         }
     }
 
-    fn add_record(&mut self, rec: csv::StringRecord) {
-        self.open.push(rec.get(1).unwrap().parse().unwrap());
-        self.high.push(rec.get(2).unwrap().parse().unwrap());
-        self.low.push(rec.get(3).unwrap().parse().unwrap());
-        self.close.push(rec.get(4).unwrap().parse().unwrap());
+    pub(crate) fn push(&mut self, open: f32, high: f32, low: f32, close: f32, volume: f32) {
+        self.open.push(open);
+        self.high.push(high);
+        self.low.push(low);
+        self.close.push(close);
+        self.volume.push(volume);
+        self.buy.push(price(low));
+        self.sell.push(price(high));
+    }
+
+    fn add_record(&mut self, rec: csv::StringRecord) -> Result<(), Box<dyn Error>> {
+        let field = |i: usize| rec.get(i).filter(|v| !v.is_empty());
+        let column = |i: usize, name: &str| field(i).ok_or_else(|| format!("record {:?} has no {}", rec, name));
+
+        let (open, high) = (column(1, "open")?, column(2, "high")?);
+        let (low, close) = (column(3, "low")?, column(4, "close")?);
+        let volume = match field(5) {
+            Some(volume) => volume.parse()?,
+            None => 0.0,
+        };
+        let buy = decimal(field(6).unwrap_or(low))?;
+        let sell = decimal(field(7).unwrap_or(high))?;
+
+        self.open.push(open.parse()?);
+        self.high.push(high.parse()?);
+        self.low.push(low.parse()?);
+        self.close.push(close.parse()?);
+        self.volume.push(volume);
+        self.buy.push(buy);
+        self.sell.push(sell);
+        Ok(())
+    }
+
+    fn add_recorded(&mut self, rec: Record) -> Result<(), Box<dyn Error>> {
+        // Recordings hold exact decimals: the candles only need them approximately, but
+        // the buy and sell prices are traded at, so they are kept as they are.
+        let approx = |value: Decimal| {
+            value
+                .to_f32()
+                .ok_or_else(|| format!("recorded price {} is out of range", value))
+        };
+
+        self.open.push(approx(rec.open)?);
+        self.high.push(approx(rec.high)?);
+        self.low.push(approx(rec.low)?);
+        self.close.push(approx(rec.close)?);
+        self.volume.push(rec.volume);
+        self.buy.push(rec.buy);
+        self.sell.push(rec.sell);
+        Ok(())
+    }
+
+    /// Read OHLC records from CSV data with a header row, one record per line in
+    /// `timestamp,open,high,low,close` order, optionally followed by `volume` and then
+    /// `buy` and `sell` prices, as written by a `Recorder`.
+    pub fn from_reader<R: Read>(reader: R) -> Result<RawData, Box<dyn Error>> {
+        let mut out = RawData::new();
+
+        let mut rdr = csv::Reader::from_reader(reader);
+        for result in rdr.records() {
+            let record = result?;
+            out.add_record(record)?;
+        }
+        Ok(out)
+    }
+
+    /// Read records in the JSON lines format written by a `Recorder`.
+    pub fn from_jsonl<R: Read>(reader: R) -> Result<RawData, Box<dyn Error>> {
+        let mut out = RawData::new();
+
+        for line in BufReader::new(reader).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                out.add_recorded(serde_json::from_str(&line)?)?;
+            }
+        }
+        Ok(out)
+    }
+
+    /// Read a `.csv` or `.jsonl` file, or every such file in a directory in name order,
+    /// such as the files a `Recorder` writes for one pair.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<RawData, Box<dyn Error>> {
+        let path = path.as_ref();
+        if !path.is_dir() {
+            return RawData::load_file(path);
+        }
+
+        let mut files: Vec<_> = fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<_, _>>()?;
+        files.retain(|file| matches!(extension(file), Some("csv") | Some("jsonl")));
+        files.sort();
+
+        let mut out = RawData::new();
+        for file in files {
+            out.extend(RawData::load_file(&file)?);
+        }
+        Ok(out)
+    }
+
+    fn load_file(path: &Path) -> Result<RawData, Box<dyn Error>> {
+        let file = File::open(path)?;
+        match extension(path) {
+            Some("jsonl") => RawData::from_jsonl(file),
+            _ => RawData::from_reader(file),
+        }
+    }
+
+    fn extend(&mut self, other: RawData) {
+        self.open.extend(other.open);
+        self.high.extend(other.high);
+        self.low.extend(other.low);
+        self.close.extend(other.close);
+        self.volume.extend(other.volume);
+        self.buy.extend(other.buy);
+        self.sell.extend(other.sell);
     }
 
     fn take_slice(&self, size: usize) -> RawData {
         let len = self.open.len();
+        let size = size.min(len);
 
         RawData {
             open: self.open[len - size..len].to_vec(),
             high: self.high[len - size..len].to_vec(),
             low: self.low[len - size..len].to_vec(),
             close: self.close[len - size..len].to_vec(),
+            volume: self.volume[len - size..len].to_vec(),
+            buy: self.buy[len - size..len].to_vec(),
+            sell: self.sell[len - size..len].to_vec(),
         }
     }
+
+    pub fn open(&self) -> &[f32] {
+        &self.open
+    }
+
+    pub fn high(&self) -> &[f32] {
+        &self.high
+    }
+
+    pub fn low(&self) -> &[f32] {
+        &self.low
+    }
+
+    pub fn close(&self) -> &[f32] {
+        &self.close
+    }
+
+    /// Volume traded over each record in the base currency, or zero where the source had
+    /// none, as for snapshots written by a `Recorder`.
+    pub fn volume(&self) -> &[f32] {
+        &self.volume
+    }
+
+    /// Price each record can be bought at.
+    pub fn buy(&self) -> &[Decimal] {
+        &self.buy
+    }
+
+    /// Price each record can be sold at.
+    pub fn sell(&self) -> &[Decimal] {
+        &self.sell
+    }
+
+    /// Number of records.
+    pub fn len(&self) -> usize {
+        self.close.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.close.is_empty()
+    }
 }
 
 fn get_first_arg() -> Result<OsString, Box<dyn Error>> {
@@ -51,31 +229,43 @@ fn get_first_arg() -> Result<OsString, Box<dyn Error>> {
     }
 }
 
+/// Load the last 1000 records from the file or directory named by the first argument.
 pub fn run() -> Result<RawData, Box<dyn Error>> {
     let file_path = get_first_arg()?;
-    let file = File::open(file_path)?;
-    let mut out = RawData::new();
+    let out = RawData::load(file_path)?;
 
-    let mut rdr = csv::Reader::from_reader(file);
-    for result in rdr.records() {
-        let record = result?;
-        out.add_record(record);
-    }
     Ok(out.take_slice(1000))
 }
 
+/// Candles hold their prices as `f32`, so buy and sell at the shortest decimal that reads back
+/// as the same value.
+fn price(value: f32) -> Decimal {
+    value.to_string().parse().unwrap_or_default()
+}
+
+/// Parse a price from CSV, which may be written in exponent form.
+fn decimal(value: &str) -> Result<Decimal, rust_decimal::Error> {
+    value.parse().or_else(|_| Decimal::from_scientific(value))
+}
+
+fn extension(path: &Path) -> Option<&str> {
+    path.extension().and_then(|e| e.to_str())
+}
+
 pub struct Histtrader {
     data: RawData,
     range: usize,
-    current: (usize, Vec<f32>, f32, f32), // (position, hist, buy, sell)
-    ticker: String,
-    history: Vec<(f32, Actions)>,
-    account: f32,  // in usd
-    holdings: f32, // in stock units
+    current: (usize, Vec<f32>, Decimal, Decimal), // (position, hist, buy, sell)
+    pair: Pair,
+    increments: Increments,
+    history: Vec<(Decimal, Actions)>,
+    open: OpenOrders,
+    account: Decimal,  // in quote currency
+    holdings: Decimal, // in stock units
 }
 
 impl Histtrader {
-    pub fn new(data: RawData, ticker: &str, account: f32, start: usize) -> Self {
+    pub fn new(data: RawData, ticker: &str, account: Decimal, start: usize) -> Self {
         println!(
             "Initializing Histtrader: Max iterations {}",
             data.close.len() - start
@@ -86,13 +276,15 @@ impl Histtrader {
             current: (
                 start,
                 data.close[..=start].to_vec(),
-                data.low[start],
-                data.high[start],
+                data.buy[start],
+                data.sell[start],
             ),
-            ticker: ticker.to_owned(),
+            pair: Pair::from(ticker),
+            increments: Increments::default(),
             history: vec![],
+            open: OpenOrders::default(),
             account,
-            holdings: 0.0,
+            holdings: Decimal::ZERO,
         }
     }
 
@@ -101,49 +293,92 @@ impl Histtrader {
         self.current = (
             new_start,
             self.data.close[new_start - self.range..=new_start].to_vec(),
-            self.data.low[new_start],
-            self.data.high[new_start],
+            self.data.buy[new_start],
+            self.data.sell[new_start],
         )
     }
 
-    pub fn reset(&mut self, account: f32, ticker: &str) {
+    pub fn reset(&mut self, account: Decimal, ticker: &str) {
         self.history = vec![];
+        self.open = OpenOrders::default();
         self.account = account;
-        self.ticker = ticker.to_owned();
-        self.holdings = 0.0;
+        self.pair = Pair::from(ticker);
+        self.holdings = Decimal::ZERO;
+    }
+
+    /// Set how sizes and prices are rounded, which is in satoshis and cents by default.
+    pub fn with_increments(mut self, increments: Increments) -> Self {
+        self.increments = increments;
+        self
     }
 
-    pub fn trade(&mut self, action: Actions) {
+    /// Step to the next record, fill any open orders its prices reach, then take `action`.
+    ///
+    /// Records carry no timestamps, so good-til-time orders stay open until cancelled.
+    /// Invalid orders, and post-only orders that would fill straight away, fail with
+    /// `Error::Order`.
+    pub fn trade(&mut self, action: Actions) -> error::Result<()> {
         println!("Making trade");
         self.update();
         self.history.push((self.account, action.clone()));
+        self.fill_open();
 
         match action {
-            Actions::Buy(usd) => self.buy(usd),
-            Actions::Sell(coin) => self.sell(coin),
-            Actions::Hold => return,
-        };
+            Actions::Buy(amount) => self.buy(amount),
+            Actions::Sell(item) => self.sell(item),
+            Actions::Order(order) => self.place(order),
+            Actions::Hold => Ok(()),
+        }
     }
 
-    fn buy(&mut self, mut usd: f32) {
-        if usd > self.account {
-            usd = self.account;
-        }
+    fn buy(&mut self, amount: Decimal) -> error::Result<()> {
         let price = self.current.2;
-
-        self.account -= usd;
-        self.holdings += usd / price;
+        self.execute(&Order::market(Side::Buy, amount), price);
+        Ok(())
     }
 
-    fn sell(&mut self, item: f32) {
+    fn sell(&mut self, item: Decimal) -> error::Result<()> {
         let price = self.current.3;
+        self.execute(&Order::market(Side::Sell, item), price);
+        Ok(())
+    }
+
+    fn place(&mut self, order: Order) -> error::Result<()> {
+        let order = order.rounded(&self.increments);
+        if let Some(price) = self.open.place(order.clone(), &self.prices())? {
+            self.execute(&order, price);
+        }
+        Ok(())
+    }
+
+    fn fill_open(&mut self) {
+        let prices = self.prices();
+        for (order, price) in self.open.fill(&prices) {
+            self.execute(&order, price);
+        }
+    }
+
+    fn execute(&mut self, order: &Order, price: Decimal) {
+        simulated::execute(order, price, &self.increments, &mut self.account, &mut self.holdings);
+    }
+
+    fn prices(&self) -> Prices {
+        Prices { buy: self.current.2, sell: self.current.3, time: None }
+    }
+
+    /// Get the orders that have not filled yet, oldest first.
+    pub fn open_orders(&self) -> Vec<Order> {
+        self.open.orders()
+    }
 
-        self.account += item * price;
-        self.holdings -= item;
+    /// Cancel every open order, returning them.
+    pub fn cancel_orders(&mut self) -> Vec<Order> {
+        self.open.cancel()
     }
 
     pub fn data(&self) -> TraderData {
         TraderData {
+            currency: self.pair.quote.clone(),
             historical: self.current.1.clone(),
             buy: self.current.2,
             sell: self.current.3,
@@ -152,7 +387,7 @@ impl Histtrader {
         }
     }
 
-    pub fn history(&self) -> Vec<(f32, Actions)> {
+    pub fn history(&self) -> Vec<(Decimal, Actions)> {
         self.history.clone()
     }
 }
@@ -8,11 +8,14 @@ trading connection using CoinBase, and can backtest over provided data or live d
 
 The primary types in this crate are `DataAPI`, which maintains a connection to retrieve
 price data from, and `Backtrader`, which provides an interface for writing testing
-servers.
+servers. Both are generic over a `PriceSource`, so strategies can also run against the
+in-memory `ScriptedSource` without any network access.
 
 Other data types include `CoinData` and `TraderData`, which are serializable formats for
-passing trading data in between other applications. Finally, the `Action` enum represents
-all the possible actions in a trade.
+passing trading data in between other applications. The `Action` enum represents all the
+possible actions in a trade, from market buys and sells to limit, stop and stop-limit
+orders; stop orders only work in simulation, as Coinbase rejects them. Every fallible call returns a `seventh_core::Result`, whose `Error` distinguishes
+transport, HTTP, decoding, unknown symbol, rate limit and authentication failures.
 
 # Setup
 
@@ -28,13 +31,18 @@ seventh_core = "0.1"
 ```no_run
 use seventh_core::api::DataAPI;
 
-fn main() {
+#[tokio::main]
+async fn main() -> Result<(), seventh_core::Error> {
     let mut data_api = DataAPI::new();
-    data_api.update("BTC");
-    println!("{:?}", data_api.last())
+    data_api.update("BTC").await?;
+    println!("{:?}", data_api.last_ref());
+    Ok(())
 }
 ```
 
+Synchronous callers can use the wrappers in `api::blocking` instead, which drive the same
+async APIs on a shared Tokio runtime.
+
 */
 
 #[macro_use]
@@ -42,5 +50,13 @@ extern crate serde_derive;
 
 pub mod api;
 pub mod backtrader;
+pub mod error;
 pub mod historical;
 pub mod livetrader;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod recorder;
+mod simulated;
+
+pub use api::money::Decimal;
+pub use error::{Error, Result};
@@ -1,80 +1,332 @@
 //! Livetrading interface
 
-use crate::api::data::DataAPI;
-use crate::api::transactions::BrokerAPI;
+use std::collections::HashMap;
+
+use crate::api::auth::Authenticator;
+use crate::api::blocking::{BrokerAPI, DataAPI, Runtime};
+use crate::api::book::Side;
+use crate::api::data::CoinData;
+use crate::api::endpoints::Endpoints;
+use crate::api::feed::TickerFeed;
+use crate::api::money::{Decimal, Increments};
+use crate::api::orders::{Order, OrderType};
+use crate::api::pair::Pair;
+use crate::api::retention::Retention;
+use crate::api::source::{PriceSource, WebSource};
+use crate::api::transactions::OrderReceipt;
+use crate::api::validation::Validation;
+use crate::api::{data, transactions};
 use crate::backtrader::{Actions, TraderData};
+use crate::error::{Error, Result};
+
+/// Fee rate allowed for on limit buys unless set with `Livetrader::with_fee_rate`: 0.6%,
+/// Coinbase's taker fee at its lowest volume tier.
+const DEFAULT_FEE_RATE: Decimal = Decimal::from_parts(6, 0, 0, false, 3);
 
-pub struct Livetrader {
-    api: DataAPI,
+/// Trades one pair with real orders on Coinbase.
+///
+/// The balances only change once an order completes, by the amounts on its receipt, so
+/// fees are accounted for. Orders that have not settled are looked up again before each
+/// trade, or on `settle`. Until then, the funds a pending buy may spend and the size a
+/// pending sell may sell are held back, so new orders cannot spend them twice. Limit and
+/// stop-limit orders from `Actions::Order` stay pending until they fill, expire or are
+/// cancelled with `cancel_orders`, and whatever they filled is counted. Limit buys hold
+/// their price plus the `fee_rate`, as their fees are paid on top.
+pub struct Livetrader<S: PriceSource = WebSource> {
+    api: DataAPI<S>,
+    feed: Option<TickerFeed>,
     live: BrokerAPI,
-    coin: String,
-    history: Vec<(f32, Actions)>,
-    account: f32,  // in usd
-    holdings: f32, // in coin
+    pair: Pair,
+    validation: Validation,
+    history: Vec<(Decimal, Actions)>,
+    pending: Vec<String>,
+    holds: HashMap<String, (Side, Decimal)>, // funds or size held for each pending order
+    fee_rate: Decimal,
+    account: Decimal,  // in quote currency
+    holdings: Decimal, // in base currency
 }
 
 impl Livetrader {
     pub fn new(
-        account: f32,
-        coin: &str,
+        account: Decimal,
+        pair: &str,
         auth: &str,
         account_name: &str,
         payment_method: &str,
-    ) -> Self {
+    ) -> Result<Self> {
+        Livetrader::with_endpoints(account, pair, Endpoints::production(), auth, account_name, payment_method)
+    }
+
+    /// Create a `Livetrader` that reads prices from and places orders with the APIs at
+    /// `endpoints`, such as the Coinbase sandbox.
+    pub fn with_endpoints(
+        account: Decimal,
+        pair: &str,
+        endpoints: Endpoints,
+        auth: &str,
+        account_name: &str,
+        payment_method: &str,
+    ) -> Result<Self> {
+        let client = endpoints.client()?;
+        let source = WebSource::with_client(client.clone(), endpoints.clone());
+        let broker = transactions::BrokerAPI::with_client(client, endpoints, auth, account_name, payment_method);
+
+        Livetrader::from_parts(account, pair, source, broker)
+    }
+}
+
+impl<S: PriceSource> Livetrader<S> {
+    pub fn with_source(
+        account: Decimal,
+        pair: &str,
+        source: S,
+        auth: &str,
+        account_name: &str,
+        payment_method: &str,
+    ) -> Result<Self> {
+        let broker = transactions::BrokerAPI::new(auth, account_name, payment_method);
+        Livetrader::from_parts(account, pair, source, broker)
+    }
+
+    fn from_parts(account: Decimal, pair: &str, source: S, broker: transactions::BrokerAPI) -> Result<Self> {
+        let runtime = Runtime::shared();
+        // Trades only ever look at the latest prices.
+        let api = data::DataAPI::with_source(source).with_retention(Retention::last(1));
         let mut trader = Livetrader {
-            api: DataAPI::new(),
-            live: BrokerAPI::new(auth, account_name, payment_method),
-            coin: coin.to_owned(),
+            api: DataAPI::from_async(api, runtime.clone()),
+            feed: None,
+            live: BrokerAPI::from_async(broker, runtime),
+            pair: Pair::from(pair),
+            validation: Validation::default(),
             history: vec![],
+            pending: vec![],
+            holds: HashMap::new(),
+            fee_rate: DEFAULT_FEE_RATE,
             account,
-            holdings: 0.0,
+            holdings: Decimal::ZERO,
         };
-        trader.api.update(coin);
-        trader
+        trader.api.update(&trader.pair)?;
+        Ok(trader)
+    }
+
+    /// Take prices from the WebSocket feed at `url`, such as `Endpoints::feed`, instead of
+    /// polling the price source on every trade.
+    ///
+    /// The feed streams in the background. Until it has delivered prices for the pair,
+    /// trades fall back to polling the price source.
+    pub fn with_feed(mut self, url: &str) -> Self {
+        self.feed = Some(self.subscribe(url));
+        self
+    }
+
+    fn subscribe(&self, url: &str) -> TickerFeed {
+        let pair = self.pair.clone();
+        self.api.runtime().block_on(async { TickerFeed::subscribe(url, &[pair]) })
+    }
+
+    /// Authenticate orders with `auth` instead of the bearer token given on creation.
+    pub fn with_authenticator<A: Authenticator + 'static>(mut self, auth: A) -> Self {
+        self.live = self.live.with_authenticator(auth);
+        self
+    }
+
+    /// Set the fee rate, such as 0.004 for 0.4%, allowed for when sizing and holding funds
+    /// for limit and stop-limit buys. Use the account's own rate to spend as much of the
+    /// balance as possible without overspending it.
+    pub fn with_fee_rate(mut self, fee_rate: Decimal) -> Self {
+        self.fee_rate = fee_rate;
+        self
+    }
+
+    /// Set the checks market data must pass before the trader places an order on it.
+    ///
+    /// Anomalous snapshots are still fetched and flagged, but trades on them fail with
+    /// `Error::Invalid` before any order is sent. Prices from the feed are checked the
+    /// same way, apart from jumps.
+    pub fn with_validation(mut self, validation: Validation) -> Self {
+        self.api = self.api.with_validation(Validation { reject: false, ..validation });
+        self.validation = validation;
+        self
     }
 
-    pub fn reset(&mut self, account: f32, coin: &str) {
+    pub fn reset(&mut self, account: Decimal, pair: &str) {
         self.history = vec![];
+        self.pending = vec![];
+        self.holds = HashMap::new();
         self.account = account;
-        self.coin = coin.to_owned();
-        self.holdings = 0.0;
+        self.pair = Pair::from(pair);
+        self.holdings = Decimal::ZERO;
+
+        if let Some(url) = self.feed.as_ref().map(|f| f.url().to_owned()) {
+            self.feed = Some(self.subscribe(&url));
+        }
     }
 
-    pub fn trade(&mut self, action: Actions) {
+    pub fn trade(&mut self, action: Actions) -> Result<()> {
         println!("Making trade");
-        self.api.update(&self.coin);
+        // Orders that cannot be looked up now stay pending, and are tried again next time.
+        self.settle().ok();
+        if self.streamed().is_none() {
+            self.api.update(&self.pair)?;
+        }
+        if action != Actions::Hold {
+            self.validation.validate(&self.latest())?;
+        }
         self.history.push((self.account, action.clone()));
 
         match action {
-            Actions::Buy(usd) => self.buy(usd),
-            Actions::Sell(coin) => self.sell(coin),
-            Actions::Hold => return,
+            Actions::Buy(amount) => self.buy(amount),
+            Actions::Sell(amount) => self.sell(amount),
+            Actions::Order(order) => self.place(order),
+            Actions::Hold => Ok(()),
+        }
+    }
+
+    fn buy(&mut self, amount: Decimal) -> Result<()> {
+        let amount = self.increments().funds(amount.min(self.available(Side::Buy)));
+        if amount.is_zero() {
+            return Err(self.nothing_available(Side::Buy));
+        }
+
+        let receipt = self.live.buy(&self.pair, amount)?;
+        self.track(receipt.id, Side::Buy, amount);
+        Ok(())
+    }
+
+    fn sell(&mut self, amount: Decimal) -> Result<()> {
+        let amount = self.increments().size(amount.min(self.available(Side::Sell)));
+        if amount.is_zero() {
+            return Err(self.nothing_available(Side::Sell));
+        }
+
+        let receipt = self.live.sell(&self.pair, amount)?;
+        self.track(receipt.id, Side::Sell, amount);
+        Ok(())
+    }
+
+    fn place(&mut self, mut order: Order) -> Result<()> {
+        // What each unit of the amount holds: a buy sized in the base currency spends up
+        // to its price on each, plus the fee.
+        let per_unit = match (order.side, order.kind) {
+            (Side::Buy, OrderType::Limit { limit }) | (Side::Buy, OrderType::StopLimit { limit, .. }) => {
+                limit * (Decimal::ONE + self.fee_rate)
+            }
+            _ => Decimal::ONE,
         };
+        if !per_unit.is_zero() {
+            order.amount = order.amount.min(self.available(order.side) / per_unit);
+        }
+        let order = order.rounded(&self.increments());
+        if order.amount.is_zero() {
+            return Err(self.nothing_available(order.side));
+        }
+        let hold = order.amount * per_unit;
+
+        let receipt = self.live.place_order(&self.pair, &order)?;
+        self.track(receipt.id, order.side, hold);
+        Ok(())
     }
 
-    fn buy(&mut self, mut usd: f32) {
-        if usd > self.account {
-            usd = self.account;
+    /// The error for a `side` order that would round down to nothing, which is never sent.
+    fn nothing_available(&self, side: Side) -> Error {
+        match side {
+            Side::Buy => Error::Order(format!("no {} available to buy {} with", self.pair.quote, self.pair.base)),
+            Side::Sell => Error::Order(format!("no {} available to sell", self.pair.base)),
         }
-        let price = self.api.last().buyprice();
+    }
+
+    /// Get what new `side` orders can use: the funds not held for pending buys, or the
+    /// holdings not held for pending sells.
+    fn available(&self, side: Side) -> Decimal {
+        let balance = match side {
+            Side::Buy => self.account,
+            Side::Sell => self.holdings,
+        };
+        let held: Decimal = self.holds.values().filter(|(s, _)| *s == side).map(|(_, amount)| *amount).sum();
+        (balance - held).max(Decimal::ZERO)
+    }
 
-        self.account -= usd;
-        self.holdings += usd / price;
-        self.live.buy(usd, &self.coin);
+    /// Hold `amount` of the balance `side` orders spend until the order `id` settles.
+    fn track(&mut self, id: String, side: Side, amount: Decimal) {
+        self.holds.insert(id.clone(), (side, amount));
+        self.pending.push(id);
+        self.apply_settled();
     }
 
-    fn sell(&mut self, coin: f32) {
-        let price = self.api.last().sellprice();
+    /// Cancel every pending order from `Actions::Order` that is still open, and update the
+    /// balances for anything they filled first.
+    pub fn cancel_orders(&mut self) -> Result<()> {
+        let open: Vec<OrderReceipt> = self
+            .pending
+            .iter()
+            .filter_map(|id| self.live.receipt(id))
+            .filter(|r| r.order.as_ref().is_some_and(|order| !order.time_in_force.is_immediate()))
+            .collect();
 
-        self.account += coin * price;
-        self.holdings -= coin;
-        self.live.sell(coin, &self.coin);
+        for receipt in open {
+            self.live.cancel_order(&receipt.id)?;
+            // Catch any fills from before the cancellation.
+            self.live.get_order(receipt.side, &receipt.id)?;
+        }
+        self.apply_settled();
+        Ok(())
+    }
+
+    /// Look up the orders that have not settled yet, and update the balances for any
+    /// that have completed since.
+    ///
+    /// This is done before every trade, so it only needs calling to see the balances in
+    /// between. Orders that are found are settled even if others cannot be looked up, in
+    /// which case the lookup failures are returned.
+    pub fn settle(&mut self) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let refreshed = self.live.refresh();
+        self.apply_settled();
+        refreshed.map(|_| ())
+    }
+
+    /// Update the balances for the pending orders that have settled with fills, using the
+    /// amounts on their receipts, and stop tracking any that have settled.
+    fn apply_settled(&mut self) {
+        let live = &self.live;
+        let mut settled = vec![];
+        self.pending.retain(|id| match live.receipt(id) {
+            Some(receipt) if receipt.status.is_final() => {
+                settled.push(receipt);
+                false
+            }
+            _ => true,
+        });
+        for receipt in &settled {
+            self.holds.remove(&receipt.id);
+        }
+
+        for receipt in settled.into_iter().filter(OrderReceipt::has_fills) {
+            match receipt.side {
+                Side::Buy => {
+                    self.account -= receipt.total.amount;
+                    self.holdings += receipt.amount.amount;
+                }
+                Side::Sell => {
+                    self.account += receipt.total.amount;
+                    self.holdings -= receipt.amount.amount;
+                }
+            }
+        }
+    }
+
+    /// Get the ids of the orders placed that have not settled yet.
+    pub fn pending(&self) -> &[String] {
+        &self.pending
     }
 
     pub fn data(&self) -> TraderData {
-        let coins = self.api.last();
+        let coins = self.latest();
 
         TraderData {
+            currency: coins.currency().to_owned(),
             historical: coins.historical(),
             buy: coins.buyprice(),
             sell: coins.sellprice(),
@@ -83,7 +335,25 @@ impl Livetrader {
         }
     }
 
-    pub fn history(&self) -> Vec<(f32, Actions)> {
+    fn streamed(&self) -> Option<CoinData> {
+        self.feed.as_ref().and_then(|feed| feed.latest(&self.pair))
+    }
+
+    fn latest(&self) -> CoinData {
+        self.streamed()
+            .unwrap_or_else(|| self.api.last_ref().expect("Trader is updated on creation").clone())
+    }
+
+    fn increments(&self) -> Increments {
+        self.api.source().increments(&self.pair)
+    }
+
+    pub fn history(&self) -> Vec<(Decimal, Actions)> {
         self.history.clone()
     }
+
+    /// Get the latest receipts for every order placed, oldest first.
+    pub fn receipts(&self) -> Vec<OrderReceipt> {
+        self.live.history()
+    }
 }
@@ -0,0 +1,789 @@
+//! Offline stand-in for the Coinbase and CoinGecko APIs
+//!
+//! `MockServer` serves canned responses from a local port, so the data, trader and broker
+//! APIs can be exercised without network access, and `MockFeed` does the same for the
+//! WebSocket feed. They are only available with the `mock` feature.
+//!
+//! # Example
+//!
+//! ```
+//! use seventh_core::api::{DataAPI, Decimal};
+//! use seventh_core::mock::MockServer;
+//!
+//! let server = MockServer::start();
+//! server.mock_quote("BTC", 101.0, 99.0, 100.0);
+//! server.mock_history("bitcoin", &[98.0, 99.0, 100.0]);
+//!
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! let mut data = DataAPI::with_endpoints(server.endpoints()).unwrap();
+//! data.update("BTC").await.unwrap();
+//! assert_eq!(data.last_ref().unwrap().buyprice(), Decimal::from(101));
+//! # });
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use chrono::{DateTime, Utc};
+use futures::{SinkExt, StreamExt};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server};
+use tokio::sync::oneshot;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::api::endpoints::Endpoints;
+use crate::api::pair::Pair;
+
+/// A canned HTTP response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl MockResponse {
+    /// A `200 OK` response with a JSON `body`.
+    pub fn json(body: String) -> Self {
+        MockResponse::status(200).body(body)
+    }
+
+    /// An empty response with the given `status`.
+    pub fn status(status: u16) -> Self {
+        MockResponse {
+            status,
+            headers: vec![("content-type".to_owned(), "application/json".to_owned())],
+            body: String::new(),
+        }
+    }
+
+    /// A `429 Too Many Requests` response asking the client to wait `retry_after` seconds.
+    pub fn rate_limited(retry_after: u64) -> Self {
+        MockResponse::status(429)
+            .header("retry-after", &retry_after.to_string())
+            .body(fixtures::error("rate_limit_exceeded", "Too many requests"))
+    }
+
+    /// Add a header to the response.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Replace the body of the response.
+    pub fn body(mut self, body: String) -> Self {
+        self.body = body;
+        self
+    }
+}
+
+/// A request received by a `MockServer`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    /// Header names are lowercase.
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+#[derive(Default)]
+struct State {
+    routes: HashMap<(String, String), Vec<MockResponse>>,
+    requests: Vec<RecordedRequest>,
+}
+
+/// Local HTTP server standing in for the Coinbase and CoinGecko APIs.
+///
+/// Responses are registered per method and path, ignoring the query string. Several
+/// responses registered for one route are served in order, and the last one is repeated
+/// once the others are used up. Unknown routes answer `404 Not Found`.
+///
+/// The server runs on its own thread and shuts down when dropped.
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockServer {
+    /// Start a server on a free local port.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no local port can be bound.
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Bind mock server");
+        listener.set_nonblocking(true).expect("Configure mock server");
+        let addr = listener.local_addr().expect("Mock server address");
+
+        let state = Arc::new(Mutex::new(State::default()));
+        let (shutdown, signal) = oneshot::channel::<()>();
+
+        let shared = state.clone();
+        thread::spawn(move || {
+            let mut runtime = tokio::runtime::Builder::new()
+                .basic_scheduler()
+                .enable_all()
+                .build()
+                .expect("Build mock runtime");
+
+            runtime.block_on(async move {
+                let make_service = make_service_fn(move |_| {
+                    let state = shared.clone();
+                    async move {
+                        Ok::<_, Infallible>(service_fn(move |req| handle(state.clone(), req)))
+                    }
+                });
+
+                let server = Server::from_tcp(listener)
+                    .expect("Start mock server")
+                    .serve(make_service)
+                    .with_graceful_shutdown(async {
+                        signal.await.ok();
+                    });
+
+                server.await.ok();
+            });
+        });
+
+        MockServer { addr, state, shutdown: Some(shutdown) }
+    }
+
+    /// Base URL of the server, such as `http://127.0.0.1:52114`.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Endpoints that send every provider's requests to this server.
+    pub fn endpoints(&self) -> Endpoints {
+        Endpoints::local(&self.url())
+    }
+
+    /// Serve `response` for requests to `method` `path`.
+    pub fn mock(&self, method: &str, path: &str, response: MockResponse) {
+        let mut state = self.state.lock().expect("Lock mock state");
+        state
+            .routes
+            .entry((method.to_uppercase(), path.to_owned()))
+            .or_default()
+            .push(response);
+    }
+
+    /// Serve Coinbase buy, sell and spot prices for `pair`, such as "BTC-EUR".
+    ///
+    /// A bare ticker is quoted in USD.
+    pub fn mock_quote(&self, pair: &str, buy: f32, sell: f32, spot: f32) {
+        let pair = Pair::from(pair);
+        for (kind, amount) in &[("buy", buy), ("sell", sell), ("spot", spot)] {
+            let path = format!("/v2/prices/{}/{}", pair, kind);
+            let body = fixtures::price(&pair.base, &pair.quote, *amount);
+            self.mock("GET", &path, MockResponse::json(body));
+        }
+    }
+
+    /// Serve a CoinGecko market chart for the coin with CoinGecko id `coin_id`.
+    pub fn mock_history(&self, coin_id: &str, prices: &[f32]) {
+        let path = format!("/coins/{}/market_chart/range", coin_id);
+        self.mock("GET", &path, MockResponse::json(fixtures::market_chart(prices)));
+    }
+
+    /// Serve a CoinGecko simple price, in `currency` such as "usd", for the coin with
+    /// CoinGecko id `coin_id`.
+    pub fn mock_gecko_price(&self, coin_id: &str, currency: &str, price: f32, volume: f32) {
+        let body = fixtures::simple_price(coin_id, currency, price, volume);
+        self.mock("GET", "/simple/price", MockResponse::json(body));
+    }
+
+    /// Serve a page of Coinbase Exchange candles for `pair`, such as "BTC-EUR".
+    ///
+    /// `rows` are `(time, low, high, open, close, volume)` in the order Coinbase sends
+    /// them, newest first. Mock once per page to serve a paginated range.
+    pub fn mock_candles(&self, pair: &str, rows: &[(i64, f32, f32, f32, f32, f32)]) {
+        let path = format!("/products/{}/candles", Pair::from(pair));
+        self.mock("GET", &path, MockResponse::json(fixtures::candles(rows)));
+    }
+
+    /// Serve a Coinbase Exchange level 2 order book for `pair`, such as "BTC-EUR".
+    ///
+    /// `bids` and `asks` are `(price, size)` levels, best price first.
+    pub fn mock_book(&self, pair: &str, bids: &[(f32, f32)], asks: &[(f32, f32)]) {
+        let path = format!("/products/{}/book", Pair::from(pair));
+        self.mock("GET", &path, MockResponse::json(fixtures::book(bids, asks)));
+    }
+
+    /// Serve the CoinGecko coin list and Coinbase product list.
+    ///
+    /// `coins` holds `(id, symbol)` pairs and `products` holds `(base, quote)` pairs.
+    pub fn mock_listings(&self, coins: &[(&str, &str)], products: &[(&str, &str)]) {
+        self.mock("GET", "/coins/list", MockResponse::json(fixtures::coin_list(coins)));
+        self.mock("GET", "/products", MockResponse::json(fixtures::products(products)));
+    }
+
+    /// Accept Coinbase `side` orders ("buys" or "sells") on `account`.
+    pub fn mock_order(&self, account: &str, side: &str, response: MockResponse) {
+        let path = format!("/v2/accounts/{}/{}", account, side);
+        self.mock("POST", &path, response);
+    }
+
+    /// Serve the Coinbase `side` order ("buys" or "sells") `id` on `account`.
+    pub fn mock_get_order(&self, account: &str, side: &str, id: &str, response: MockResponse) {
+        let path = format!("/v2/accounts/{}/{}/{}", account, side, id);
+        self.mock("GET", &path, response);
+    }
+
+    /// Serve a page of Coinbase `side` orders ("buys" or "sells") on `account`.
+    pub fn mock_list_orders(&self, account: &str, side: &str, response: MockResponse) {
+        let path = format!("/v2/accounts/{}/{}", account, side);
+        self.mock("GET", &path, response);
+    }
+
+    /// Answer Coinbase Advanced Trade orders, as placed by `BrokerAPI::place_order`.
+    pub fn mock_place_order(&self, response: MockResponse) {
+        self.mock("POST", "/api/v3/brokerage/orders", response);
+    }
+
+    /// Serve the Coinbase Advanced Trade order `id`.
+    pub fn mock_brokerage_order(&self, id: &str, response: MockResponse) {
+        let path = format!("/api/v3/brokerage/orders/historical/{}", id);
+        self.mock("GET", &path, response);
+    }
+
+    /// Answer Coinbase Advanced Trade order cancellations.
+    pub fn mock_cancel(&self, response: MockResponse) {
+        self.mock("POST", "/api/v3/brokerage/orders/batch_cancel", response);
+    }
+
+    /// Commit Coinbase `side` orders ("buys" or "sells") quoted as `id` on `account`.
+    pub fn mock_commit(&self, account: &str, side: &str, id: &str, response: MockResponse) {
+        let path = format!("/v2/accounts/{}/{}/{}/commit", account, side, id);
+        self.mock("POST", &path, response);
+    }
+
+    /// Serve `time` from the Coinbase time endpoint.
+    pub fn mock_time(&self, time: DateTime<Utc>) {
+        self.mock("GET", "/v2/time", MockResponse::json(fixtures::time(time)));
+    }
+
+    /// All requests received so far, oldest first.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().expect("Lock mock state").requests.clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+}
+
+struct Session {
+    messages: Vec<String>,
+    close: bool,
+}
+
+#[derive(Default)]
+struct FeedState {
+    sessions: VecDeque<Session>,
+    received: Vec<String>,
+    connections: usize,
+}
+
+/// Local WebSocket server standing in for the Coinbase Exchange feed.
+///
+/// Each connection waits for the client's subscribe message and then sends the messages
+/// of the next queued session. A connection without a session, or with one queued by
+/// `session`, stays open until the client leaves; one queued by `dropped_session` is
+/// closed once its messages are sent, so the client has to reconnect.
+///
+/// The server runs on its own thread and shuts down when dropped.
+pub struct MockFeed {
+    addr: SocketAddr,
+    state: Arc<Mutex<FeedState>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockFeed {
+    /// Start a feed on a free local port.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no local port can be bound.
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Bind mock feed");
+        listener.set_nonblocking(true).expect("Configure mock feed");
+        let addr = listener.local_addr().expect("Mock feed address");
+
+        let state = Arc::new(Mutex::new(FeedState::default()));
+        let (shutdown, mut signal) = oneshot::channel::<()>();
+
+        let shared = state.clone();
+        thread::spawn(move || {
+            let mut runtime = tokio::runtime::Builder::new()
+                .basic_scheduler()
+                .enable_all()
+                .build()
+                .expect("Build mock runtime");
+
+            runtime.block_on(async move {
+                let mut listener = tokio::net::TcpListener::from_std(listener).expect("Start mock feed");
+                loop {
+                    tokio::select! {
+                        accepted = listener.accept() => match accepted {
+                            Ok((stream, _)) => {
+                                tokio::spawn(serve_feed(shared.clone(), stream));
+                            }
+                            Err(_) => return,
+                        },
+                        _ = &mut signal => return,
+                    }
+                }
+            });
+        });
+
+        MockFeed { addr, state, shutdown: Some(shutdown) }
+    }
+
+    /// URL of the feed, such as `ws://127.0.0.1:52114`.
+    pub fn url(&self) -> String {
+        format!("ws://{}", self.addr)
+    }
+
+    /// Send `messages` on the next connection and keep it open.
+    pub fn session(&self, messages: Vec<String>) {
+        let mut state = self.state.lock().expect("Lock mock state");
+        state.sessions.push_back(Session { messages, close: false });
+    }
+
+    /// Send `messages` on the next connection and then close it.
+    pub fn dropped_session(&self, messages: Vec<String>) {
+        let mut state = self.state.lock().expect("Lock mock state");
+        state.sessions.push_back(Session { messages, close: true });
+    }
+
+    /// All text messages received from clients, oldest first.
+    pub fn received(&self) -> Vec<String> {
+        self.state.lock().expect("Lock mock state").received.clone()
+    }
+
+    /// Number of connections accepted so far.
+    pub fn connections(&self) -> usize {
+        self.state.lock().expect("Lock mock state").connections
+    }
+}
+
+impl Drop for MockFeed {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+}
+
+async fn serve_feed(state: Arc<Mutex<FeedState>>, stream: tokio::net::TcpStream) {
+    let mut socket = match tokio_tungstenite::accept_async(stream).await {
+        Ok(socket) => socket,
+        Err(_) => return,
+    };
+    let session = {
+        let mut state = state.lock().expect("Lock mock state");
+        state.connections += 1;
+        state.sessions.pop_front()
+    };
+
+    let record = |message| {
+        if let Message::Text(text) = message {
+            state.lock().expect("Lock mock state").received.push(text);
+        }
+    };
+
+    // Coinbase sends nothing until the client subscribes.
+    match socket.next().await {
+        Some(Ok(message)) => record(message),
+        _ => return,
+    }
+
+    if let Some(session) = session {
+        for message in session.messages {
+            if socket.send(Message::Text(message)).await.is_err() {
+                return;
+            }
+        }
+        if session.close {
+            socket.close(None).await.ok();
+            return;
+        }
+    }
+
+    while let Some(Ok(message)) = socket.next().await {
+        record(message);
+    }
+}
+
+async fn handle(state: Arc<Mutex<State>>, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let (parts, body) = req.into_parts();
+    let body = hyper::body::to_bytes(body).await.unwrap_or_default();
+
+    let recorded = RecordedRequest {
+        method: parts.method.to_string(),
+        path: parts.uri.path().to_owned(),
+        query: parts.uri.query().map(str::to_owned),
+        headers: parts
+            .headers
+            .iter()
+            .map(|(k, v)| (k.as_str().to_owned(), v.to_str().unwrap_or_default().to_owned()))
+            .collect(),
+        body: String::from_utf8_lossy(&body).into_owned(),
+    };
+
+    let mock = {
+        let mut state = state.lock().expect("Lock mock state");
+        let key = (recorded.method.clone(), recorded.path.clone());
+        state.requests.push(recorded);
+
+        match state.routes.get_mut(&key) {
+            Some(queue) if queue.len() > 1 => queue.remove(0),
+            Some(queue) => queue[0].clone(),
+            None => MockResponse::status(404).body(fixtures::error("not_found", "Not found")),
+        }
+    };
+
+    let mut response = Response::builder().status(mock.status);
+    for (name, value) in &mock.headers {
+        response = response.header(name.as_str(), value.as_str());
+    }
+
+    Ok(response.body(Body::from(mock.body)).expect("Build mock response"))
+}
+
+/// Response bodies recorded from the Coinbase and CoinGecko APIs.
+pub mod fixtures {
+    use chrono::{DateTime, Utc};
+    use serde_json::json;
+
+    /// Timestamp of the first point in a mocked market chart, in milliseconds.
+    pub const CHART_START: i64 = 1_590_969_600_000;
+
+    /// Spacing of the points in a mocked market chart, in milliseconds.
+    pub const CHART_STEP: i64 = 300_000;
+
+    /// Coinbase `GET /v2/prices/:pair/:kind` response.
+    pub fn price(base: &str, currency: &str, amount: f32) -> String {
+        json!({
+            "data": {
+                "base": base,
+                "currency": currency,
+                "amount": format!("{:.2}", amount)
+            }
+        })
+        .to_string()
+    }
+
+    /// CoinGecko `GET /coins/:id/market_chart/range` response with one point per price.
+    pub fn market_chart(prices: &[f32]) -> String {
+        let series = |values: &mut dyn Iterator<Item = f32>| -> Vec<serde_json::Value> {
+            values
+                .enumerate()
+                .map(|(i, v)| json!([CHART_START + CHART_STEP * i as i64, v]))
+                .collect()
+        };
+
+        json!({
+            "prices": series(&mut prices.iter().copied()),
+            "market_caps": series(&mut prices.iter().map(|p| p * 18_000_000.0)),
+            "total_volumes": series(&mut prices.iter().map(|p| p * 1_000.0))
+        })
+        .to_string()
+    }
+
+    /// CoinGecko `GET /simple/price` response with the 24 hour volume included.
+    pub fn simple_price(coin_id: &str, currency: &str, price: f32, volume: f32) -> String {
+        let mut prices = serde_json::Map::new();
+        prices.insert(currency.to_owned(), json!(price));
+        prices.insert(format!("{}_24h_vol", currency), json!(volume));
+
+        json!({ coin_id: prices }).to_string()
+    }
+
+    /// CoinGecko `GET /coins/list` response for `(id, symbol)` pairs.
+    pub fn coin_list(coins: &[(&str, &str)]) -> String {
+        let coins: Vec<_> = coins
+            .iter()
+            .map(|(id, symbol)| json!({ "id": id, "symbol": symbol, "name": id }))
+            .collect();
+
+        json!(coins).to_string()
+    }
+
+    /// Coinbase Exchange `GET /products` response for `(base, quote)` pairs.
+    pub fn products(products: &[(&str, &str)]) -> String {
+        let products: Vec<_> = products
+            .iter()
+            .map(|(base, quote)| {
+                json!({
+                    "id": format!("{}-{}", base, quote),
+                    "base_currency": base,
+                    "quote_currency": quote,
+                    "base_increment": "0.00000001",
+                    "quote_increment": "0.01",
+                    "display_name": format!("{}/{}", base, quote),
+                    "status": "online",
+                    "trading_disabled": false
+                })
+            })
+            .collect();
+
+        json!(products).to_string()
+    }
+
+    /// Coinbase Exchange `GET /products/:pair/candles` response.
+    pub fn candles(rows: &[(i64, f32, f32, f32, f32, f32)]) -> String {
+        let rows: Vec<_> = rows
+            .iter()
+            .map(|(time, low, high, open, close, volume)| json!([time, low, high, open, close, volume]))
+            .collect();
+
+        json!(rows).to_string()
+    }
+
+    /// Coinbase Exchange `GET /products/:pair/book?level=2` response.
+    pub fn book(bids: &[(f32, f32)], asks: &[(f32, f32)]) -> String {
+        let levels = |levels: &[(f32, f32)]| -> Vec<_> {
+            levels.iter().map(|(price, size)| json!([format!("{:.2}", price), size.to_string(), 1])).collect()
+        };
+
+        json!({ "sequence": 3, "bids": levels(bids), "asks": levels(asks) }).to_string()
+    }
+
+    /// Coinbase `POST /v2/accounts/:account/buys` or `sells` response.
+    ///
+    /// `side` is "buy" or "sell"; `amount` is in `currency` and `total` in USD.
+    pub fn order(side: &str, amount: f32, currency: &str, total: f32) -> String {
+        json!({ "data": order_data(side, amount, currency, total, "created", true) }).to_string()
+    }
+
+    /// Coinbase order response, as for `order`, for an order that has settled.
+    pub fn completed_order(side: &str, amount: f32, currency: &str, total: f32) -> String {
+        json!({ "data": order_data(side, amount, currency, total, "completed", true) }).to_string()
+    }
+
+    /// Coinbase `POST /v2/accounts/:account/buys` or `sells` response for an order placed
+    /// with `"commit": false`, which is left uncommitted.
+    pub fn uncommitted_order(side: &str, amount: f32, currency: &str, total: f32) -> String {
+        json!({ "data": order_data(side, amount, currency, total, "created", false) }).to_string()
+    }
+
+    /// Coinbase `GET /v2/accounts/:account/buys` or `sells` response.
+    ///
+    /// `orders` are `(id, status, total)` for orders of one unit of BTC, and `next` is the
+    /// id to continue after, if there are more pages.
+    pub fn order_list(side: &str, orders: &[(&str, &str, f32)], next: Option<&str>) -> String {
+        let data: Vec<_> = orders
+            .iter()
+            .map(|(id, status, total)| {
+                let mut order = order_data(side, 1.0, "BTC", *total, status, true);
+                order["id"] = json!(id);
+                order
+            })
+            .collect();
+
+        json!({
+            "pagination": { "limit": orders.len(), "order": "desc", "next_starting_after": next },
+            "data": data
+        })
+        .to_string()
+    }
+
+    /// Coinbase Advanced Trade `POST /api/v3/brokerage/orders` response for an order
+    /// accepted as `id`.
+    pub fn placed_order(id: &str, pair: &str, side: &str) -> String {
+        json!({
+            "success": true,
+            "success_response": {
+                "order_id": id,
+                "product_id": pair,
+                "side": side.to_uppercase(),
+                "client_order_id": "0f2c5a8e6c3b4d1e9a7f8b2c4d6e8f0a"
+            }
+        })
+        .to_string()
+    }
+
+    /// Coinbase Advanced Trade `POST /api/v3/brokerage/orders` response for an order
+    /// rejected for `reason`, such as "INVALID_LIMIT_PRICE_POST_ONLY".
+    pub fn rejected_order(reason: &str) -> String {
+        json!({
+            "success": false,
+            "failure_reason": "UNKNOWN_FAILURE_REASON",
+            "error_response": {
+                "error": "UNKNOWN_FAILURE_REASON",
+                "message": reason,
+                "error_details": "",
+                "new_order_failure_reason": reason
+            }
+        })
+        .to_string()
+    }
+
+    /// Coinbase Advanced Trade `GET /api/v3/brokerage/orders/historical/:id` response.
+    ///
+    /// `status` is as Coinbase sends it, such as "OPEN" or "FILLED", and `filled` is the
+    /// size filled so far at `price`, with a fee of 0.6%.
+    pub fn brokerage_order(id: &str, pair: &str, side: &str, status: &str, filled: f32, price: f32) -> String {
+        let value = filled * price;
+        let fee = (value * 0.006 * 100.0).round() / 100.0;
+        let total = if side == "sell" { value - fee } else { value + fee };
+
+        json!({
+            "order": {
+                "order_id": id,
+                "product_id": pair,
+                "user_id": "2bbf394c-193b-5b2a-9155-3b4732659ede",
+                "side": side.to_uppercase(),
+                "client_order_id": "0f2c5a8e6c3b4d1e9a7f8b2c4d6e8f0a",
+                "status": status,
+                "time_in_force": "GOOD_UNTIL_CANCELLED",
+                "created_time": "2020-06-01T07:00:00Z",
+                "completion_percentage": "0",
+                "filled_size": format!("{:.8}", filled),
+                "average_filled_price": format!("{:.2}", price),
+                "fee": "",
+                "number_of_fills": "1",
+                "filled_value": format!("{:.2}", value),
+                "pending_cancel": false,
+                "size_in_quote": false,
+                "total_fees": format!("{:.2}", fee),
+                "size_inclusive_of_fees": false,
+                "total_value_after_fees": format!("{:.2}", total),
+                "trigger_status": "INVALID_ORDER_TYPE",
+                "order_type": "LIMIT",
+                "last_fill_time": if filled > 0.0 { json!("2020-06-01T07:05:00Z") } else { json!(null) }
+            }
+        })
+        .to_string()
+    }
+
+    /// Coinbase Advanced Trade `POST /api/v3/brokerage/orders/batch_cancel` response for
+    /// one order.
+    pub fn cancel(id: &str, success: bool) -> String {
+        let reason = if success { "UNKNOWN_CANCEL_FAILURE_REASON" } else { "DUPLICATE_CANCEL_REQUEST" };
+        json!({ "results": [{ "success": success, "failure_reason": reason, "order_id": id }] }).to_string()
+    }
+
+    fn order_data(side: &str, amount: f32, currency: &str, total: f32, status: &str, committed: bool) -> serde_json::Value {
+        let fee = (total * 0.0149 * 100.0).round() / 100.0;
+
+        json!({
+            "id": "67e0eaec-07d7-54c4-a72c-2e92826897df",
+            "status": status,
+            "payment_method": {
+                "id": "83562370-3e5c-51db-87da-752af5ab9559",
+                "resource": "payment_method",
+                "resource_path": "/v2/payment-methods/83562370-3e5c-51db-87da-752af5ab9559"
+            },
+            "transaction": {
+                "id": "441b9494-b3f0-5b98-b9b0-4d82c21c252a",
+                "resource": "transaction",
+                "resource_path": "/v2/accounts/2bbf394c-193b-5b2a-9155-3b4732659ede/transactions/441b9494-b3f0-5b98-b9b0-4d82c21c252a"
+            },
+            "amount": { "amount": format!("{:.8}", amount), "currency": currency },
+            "total": { "amount": format!("{:.2}", total + fee), "currency": "USD" },
+            "subtotal": { "amount": format!("{:.2}", total), "currency": "USD" },
+            "created_at": "2020-06-01T00:00:00-07:00",
+            "updated_at": "2020-06-01T00:00:00-07:00",
+            "resource": side,
+            "resource_path": format!("/v2/accounts/2bbf394c-193b-5b2a-9155-3b4732659ede/{}s/67e0eaec-07d7-54c4-a72c-2e92826897df", side),
+            "committed": committed,
+            "instant": false,
+            "fee": { "amount": format!("{:.2}", fee), "currency": "USD" },
+            "payout_at": "2020-06-03T00:00:00-07:00"
+        })
+    }
+
+    /// Coinbase `GET /v2/time` response.
+    pub fn time(time: DateTime<Utc>) -> String {
+        json!({ "data": { "iso": time.to_rfc3339(), "epoch": time.timestamp() } }).to_string()
+    }
+
+    /// Coinbase error response.
+    pub fn error(id: &str, message: &str) -> String {
+        json!({ "errors": [{ "id": id, "message": message }] }).to_string()
+    }
+
+    /// Coinbase Exchange feed `ticker` message for `pair`, such as "BTC-USD", timed now.
+    pub fn ticker(pair: &str, price: f32, best_bid: f32, best_ask: f32) -> String {
+        json!({
+            "type": "ticker",
+            "sequence": 1,
+            "product_id": pair,
+            "price": format!("{:.2}", price),
+            "best_bid": format!("{:.2}", best_bid),
+            "best_ask": format!("{:.2}", best_ask),
+            "side": "buy",
+            "time": Utc::now().to_rfc3339(),
+            "trade_id": 1,
+            "last_size": "0.01"
+        })
+        .to_string()
+    }
+
+    /// Coinbase Exchange feed `match` message for a trade of `size` on `pair`, timed now.
+    pub fn trade(pair: &str, price: f32, size: f32) -> String {
+        json!({
+            "type": "match",
+            "trade_id": 1,
+            "sequence": 1,
+            "maker_order_id": "ac928c66-ca53-498f-9c13-a110027a60e8",
+            "taker_order_id": "132fb6ae-456b-4654-b4e0-d681ac05cea1",
+            "time": Utc::now().to_rfc3339(),
+            "product_id": pair,
+            "size": size.to_string(),
+            "price": format!("{:.2}", price),
+            "side": "sell"
+        })
+        .to_string()
+    }
+
+    /// Coinbase Exchange feed level2 `snapshot` message for `pair`.
+    ///
+    /// `bids` and `asks` are `(price, size)` levels, best price first.
+    pub fn book_snapshot(pair: &str, bids: &[(f32, f32)], asks: &[(f32, f32)]) -> String {
+        let levels = |levels: &[(f32, f32)]| -> Vec<_> {
+            levels.iter().map(|(price, size)| json!([format!("{:.2}", price), size.to_string()])).collect()
+        };
+
+        json!({ "type": "snapshot", "product_id": pair, "bids": levels(bids), "asks": levels(asks) }).to_string()
+    }
+
+    /// Coinbase Exchange feed `l2update` message for `pair`, timed now.
+    ///
+    /// `changes` are `(side, price, size)` with side "buy" or "sell"; a size of zero
+    /// removes the level.
+    pub fn l2update(pair: &str, changes: &[(&str, f32, f32)]) -> String {
+        let changes: Vec<_> = changes
+            .iter()
+            .map(|(side, price, size)| json!([side, format!("{:.2}", price), size.to_string()]))
+            .collect();
+
+        json!({ "type": "l2update", "product_id": pair, "time": Utc::now().to_rfc3339(), "changes": changes }).to_string()
+    }
+
+    /// Coinbase Exchange feed `subscriptions` confirmation for `pairs`.
+    pub fn subscriptions(pairs: &[&str]) -> String {
+        json!({
+            "type": "subscriptions",
+            "channels": [
+                { "name": "ticker", "product_ids": pairs },
+                { "name": "matches", "product_ids": pairs },
+                { "name": "heartbeat", "product_ids": pairs }
+            ]
+        })
+        .to_string()
+    }
+}
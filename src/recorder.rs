@@ -0,0 +1,220 @@
+//! Record market data to files that `historical` can replay
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::api::data::CoinData;
+use crate::api::money::Decimal;
+use crate::api::pair::Pair;
+use crate::error::Result;
+
+/// One recorded snapshot of a pair.
+///
+/// A snapshot is written as a candle that opened and closed at the spot price, followed by
+/// the buy and sell prices, so `historical::run` replays it at the recorded bid and ask.
+/// A snapshot covers no interval, so it has no traded volume of its own.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Record {
+    pub time: DateTime<Utc>,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    /// Volume traded over the record's interval, in the base currency. Always zero for
+    /// snapshots, as the volumes sources report cover other periods or currencies.
+    pub volume: f32,
+    pub buy: Decimal,
+    pub sell: Decimal,
+    pub spot: Decimal,
+}
+
+impl Record {
+    pub fn new(coin: &CoinData) -> Self {
+        let spot = coin.spotprice();
+
+        Record {
+            time: coin.time(),
+            open: spot,
+            high: spot,
+            low: spot,
+            close: spot,
+            volume: 0.0,
+            buy: coin.buyprice(),
+            sell: coin.sellprice(),
+            spot,
+        }
+    }
+}
+
+/// File format records are written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// CSV with a header row, one record per line.
+    Csv,
+    /// One JSON object per line.
+    Jsonl,
+}
+
+impl Format {
+    /// File extension for this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Jsonl => "jsonl",
+        }
+    }
+}
+
+enum Sink {
+    Csv(Box<csv::Writer<File>>),
+    Jsonl(BufWriter<File>),
+}
+
+struct Segment {
+    sink: Sink,
+    started: DateTime<Utc>,
+    records: usize,
+}
+
+/// Writes `CoinData` snapshots to rotating files, one directory per pair.
+///
+/// Records for a pair go to `dir/PAIR/`, in files named after the time of their first
+/// record so that they sort in order. A new file is started once the current one spans
+/// the rotation interval, a day by default, or holds the maximum number of records.
+/// Point `historical::run` at a pair's directory to replay everything recorded for it.
+///
+/// # Example
+///
+/// ```no_run
+/// use chrono::Duration;
+/// use seventh_core::api::DataAPI;
+/// use seventh_core::recorder::{Format, Recorder};
+///
+/// # async fn run() -> seventh_core::Result<()> {
+/// let mut data = DataAPI::new();
+/// let mut recorder = Recorder::new("market", Format::Csv).rotate_every(Duration::hours(1));
+///
+/// data.update("BTC").await?;
+/// recorder.record(data.last_ref().unwrap())?;
+/// recorder.flush()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Recorder {
+    dir: PathBuf,
+    format: Format,
+    interval: Duration,
+    max_records: Option<usize>,
+    segments: HashMap<Pair, Segment>,
+}
+
+impl Recorder {
+    /// Create a recorder writing `format` files under `dir`.
+    pub fn new<P: AsRef<Path>>(dir: P, format: Format) -> Self {
+        Recorder {
+            dir: dir.as_ref().to_owned(),
+            format,
+            interval: Duration::days(1),
+            max_records: None,
+            segments: HashMap::new(),
+        }
+    }
+
+    /// Start a new file once the current one spans `interval`.
+    pub fn rotate_every(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Start a new file once the current one holds `records` records.
+    pub fn rotate_after(mut self, records: usize) -> Self {
+        self.max_records = Some(records.max(1));
+        self
+    }
+
+    /// Get the directory records for `pair` are written to.
+    pub fn dir<P: Into<Pair>>(&self, pair: P) -> PathBuf {
+        self.dir.join(pair.into().to_string())
+    }
+
+    /// Append a snapshot of `coin` to the current file for its pair.
+    pub fn record(&mut self, coin: &CoinData) -> Result<()> {
+        let pair = coin.pair();
+        let record = Record::new(coin);
+
+        let expired = match self.segments.get(&pair) {
+            Some(segment) => {
+                record.time - segment.started >= self.interval
+                    || matches!(self.max_records, Some(max) if segment.records >= max)
+            }
+            None => true,
+        };
+        if expired {
+            if let Some(mut segment) = self.segments.remove(&pair) {
+                segment.flush()?;
+            }
+            let segment = self.open(&pair, record.time)?;
+            self.segments.insert(pair.clone(), segment);
+        }
+
+        let segment = self.segments.get_mut(&pair).expect("Segment was just opened");
+        match &mut segment.sink {
+            Sink::Csv(writer) => writer.serialize(&record)?,
+            Sink::Jsonl(writer) => {
+                serde_json::to_writer(&mut *writer, &record)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+        segment.records += 1;
+        Ok(())
+    }
+
+    /// Write out everything recorded so far.
+    pub fn flush(&mut self) -> Result<()> {
+        for segment in self.segments.values_mut() {
+            segment.flush()?;
+        }
+        Ok(())
+    }
+
+    fn open(&self, pair: &Pair, started: DateTime<Utc>) -> Result<Segment> {
+        let dir = self.dir(pair.clone());
+        fs::create_dir_all(&dir)?;
+
+        let stamp = started.format("%Y%m%dT%H%M%S%3fZ");
+        let extension = self.format.extension();
+        let mut path = dir.join(format!("{}.{}", stamp, extension));
+        let mut n = 1;
+        while path.exists() {
+            path = dir.join(format!("{}_{:03}.{}", stamp, n, extension));
+            n += 1;
+        }
+
+        let file = File::create(path)?;
+        let sink = match self.format {
+            Format::Csv => Sink::Csv(Box::new(csv::Writer::from_writer(file))),
+            Format::Jsonl => Sink::Jsonl(BufWriter::new(file)),
+        };
+        Ok(Segment { sink, started, records: 0 })
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.flush().ok();
+    }
+}
+
+impl Segment {
+    fn flush(&mut self) -> Result<()> {
+        match &mut self.sink {
+            Sink::Csv(writer) => writer.flush()?,
+            Sink::Jsonl(writer) => writer.flush()?,
+        }
+        Ok(())
+    }
+}
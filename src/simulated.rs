@@ -0,0 +1,179 @@
+//! Orders for the simulated traders, filled against each new price
+
+use chrono::{DateTime, Utc};
+
+use crate::api::book::Side;
+use crate::api::money::{Decimal, Increments};
+use crate::api::orders::{Order, OrderType, TimeInForce};
+use crate::error::{Error, Result};
+
+/// The prices simulated orders are matched against.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Prices {
+    pub(crate) buy: Decimal,
+    pub(crate) sell: Decimal,
+    /// When the prices were taken, if known, for expiring good-til-time orders.
+    pub(crate) time: Option<DateTime<Utc>>,
+}
+
+impl Prices {
+    /// The price a `side` order trades at straight away.
+    fn market(&self, side: Side) -> Decimal {
+        match side {
+            Side::Buy => self.buy,
+            Side::Sell => self.sell,
+        }
+    }
+
+    /// Whether a `side` limit order at `limit` can fill.
+    fn crosses(&self, side: Side, limit: Decimal) -> bool {
+        match side {
+            Side::Buy => self.buy <= limit,
+            Side::Sell => self.sell >= limit,
+        }
+    }
+
+    /// Whether the price has reached a `side` stop at `stop`.
+    fn reaches(&self, side: Side, stop: Decimal) -> bool {
+        match side {
+            Side::Buy => self.buy >= stop,
+            Side::Sell => self.sell <= stop,
+        }
+    }
+}
+
+/// An order waiting for the price to reach it.
+#[derive(Debug, Clone)]
+struct Resting {
+    order: Order,
+    /// Whether a stop-limit order's stop has been reached, leaving a limit order.
+    triggered: bool,
+}
+
+impl Resting {
+    fn is_expired(&self, prices: &Prices) -> bool {
+        match (self.order.time_in_force, prices.time) {
+            (TimeInForce::GoodTilTime(end), Some(time)) => time >= end,
+            _ => false,
+        }
+    }
+
+    /// Get the price the order fills at, if it does.
+    ///
+    /// Limit orders already on the book fill at their limit; orders that reach the book
+    /// now, such as stops that have just triggered, take the market price.
+    fn fill(&mut self, prices: &Prices) -> Option<Decimal> {
+        let side = self.order.side;
+        match self.order.kind {
+            OrderType::Market => Some(prices.market(side)),
+            OrderType::Limit { limit } => prices.crosses(side, limit).then_some(limit),
+            OrderType::Stop { stop } => prices.reaches(side, stop).then(|| prices.market(side)),
+            OrderType::StopLimit { limit, .. } if self.triggered => prices.crosses(side, limit).then_some(limit),
+            OrderType::StopLimit { stop, limit } => {
+                self.triggered = prices.reaches(side, stop);
+                (self.triggered && prices.crosses(side, limit)).then(|| prices.market(side))
+            }
+        }
+    }
+}
+
+/// The open orders of a simulated trader.
+///
+/// Orders fill whole, so fill-or-kill behaves as immediate-or-cancel.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct OpenOrders {
+    resting: Vec<Resting>,
+}
+
+impl OpenOrders {
+    /// Place `order`, returning the price it fills at straight away, if it does.
+    ///
+    /// Orders that do not fill are left open, unless they had to fill straight away.
+    /// Post-only orders that would fill fail with `Error::Order`, as Coinbase rejects them.
+    pub(crate) fn place(&mut self, order: Order, prices: &Prices) -> Result<Option<Decimal>> {
+        order.validate()?;
+        let side = order.side;
+        let market = prices.market(side);
+
+        let (price, triggered) = match order.kind {
+            OrderType::Market => (Some(market), false),
+            OrderType::Limit { limit } => (prices.crosses(side, limit).then_some(market), false),
+            OrderType::Stop { stop } => (prices.reaches(side, stop).then_some(market), false),
+            OrderType::StopLimit { stop, limit } => {
+                let triggered = prices.reaches(side, stop);
+                ((triggered && prices.crosses(side, limit)).then_some(market), triggered)
+            }
+        };
+
+        if price.is_some() && order.post_only {
+            return Err(Error::Order(format!("post-only order would fill at {}", market)));
+        }
+        if price.is_none() && !order.time_in_force.is_immediate() {
+            self.resting.push(Resting { order, triggered });
+        }
+        Ok(price)
+    }
+
+    /// Match the open orders against new `prices`, returning those that filled with the
+    /// price each filled at, and dropping any that have expired.
+    pub(crate) fn fill(&mut self, prices: &Prices) -> Vec<(Order, Decimal)> {
+        let mut filled = vec![];
+        self.resting.retain_mut(|resting| {
+            if resting.is_expired(prices) {
+                return false;
+            }
+            match resting.fill(prices) {
+                Some(price) => {
+                    filled.push((resting.order.clone(), price));
+                    false
+                }
+                None => true,
+            }
+        });
+        filled
+    }
+
+    pub(crate) fn orders(&self) -> Vec<Order> {
+        self.resting.iter().map(|resting| resting.order.clone()).collect()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.resting.is_empty()
+    }
+
+    /// Cancel every open order, returning them.
+    pub(crate) fn cancel(&mut self) -> Vec<Order> {
+        self.resting.drain(..).map(|resting| resting.order).collect()
+    }
+}
+
+/// Trade `order` at `price`, moving funds between `account`, in the quote currency, and
+/// `holdings`, in the base currency.
+///
+/// Buys are limited to the funds in `account`. Sizes and proceeds are rounded down to
+/// `increments`.
+pub(crate) fn execute(
+    order: &Order,
+    price: Decimal,
+    increments: &Increments,
+    account: &mut Decimal,
+    holdings: &mut Decimal,
+) {
+    match order.side {
+        Side::Buy if order.is_funds() => {
+            let funds = increments.funds(order.amount.min(*account));
+            *account -= funds;
+            *holdings += increments.size(funds / price);
+        }
+        Side::Buy => {
+            let size = increments.size(order.amount.min(*account / price));
+            *account -= size * price;
+            *holdings += size;
+        }
+        Side::Sell => {
+            let size = increments.size(order.amount);
+            *account += increments.funds(size * price);
+            *holdings -= size;
+        }
+    }
+}
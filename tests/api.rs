@@ -1,15 +1,256 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use async_trait::async_trait;
+use chrono::{Duration, TimeZone, Utc};
+use rust_decimal_macros::dec;
+
+use seventh_core::api::blocking;
 use seventh_core::api::data::*;
+use seventh_core::api::endpoints::Endpoints;
+use seventh_core::api::pair::Pair;
+use seventh_core::api::retention::Retention;
+use seventh_core::api::series::*;
+use seventh_core::api::source::*;
+use seventh_core::mock::MockServer;
+use seventh_core::{Decimal, Error, Result};
+
+/// Source that takes a while to answer and records how many requests overlap.
+#[derive(Default)]
+struct SlowSource {
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+}
+
+#[async_trait]
+impl PriceSource for SlowSource {
+    async fn quote(&self, _pair: &Pair) -> Result<Quote> {
+        let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_in_flight.fetch_max(now, Ordering::SeqCst);
+        tokio::time::delay_for(std::time::Duration::from_millis(20)).await;
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        Ok(Quote::new(dec!(101), dec!(99), dec!(100)))
+    }
+
+    async fn historical(&self, _pair: &Pair, _window: &Window) -> Result<History> {
+        Ok(History::default())
+    }
+}
 
 #[test]
 fn test_initialization() {
     let data_api = DataAPI::new();
-    assert_eq!(data_api.coins(), vec![]);
+    assert!(data_api.is_empty());
+}
+
+#[tokio::test]
+async fn test_update() {
+    let server = MockServer::start();
+    server.mock_quote("BTC", 9001.0, 8999.0, 9000.0);
+    server.mock_history("bitcoin", &[8900.0, 8950.0, 9000.0]);
+
+    let mut data_api = DataAPI::with_endpoints(server.endpoints()).unwrap();
+    assert!(data_api.is_empty());
+    data_api.update("BTC").await.unwrap();
+    assert_eq!(data_api.len(), 1);
+}
+
+#[tokio::test]
+async fn test_scripted_update() {
+    let source = ScriptedSource::new();
+    source.push_quote("BTC", Quote::new(dec!(101), dec!(99), dec!(100)));
+    source.push_quote("BTC", Quote::new(dec!(102), dec!(100), dec!(101)));
+    source.set_historical("BTC", vec![99.0, 100.0]);
+
+    let mut data_api = DataAPI::with_source(source);
+    data_api.update("BTC").await.unwrap();
+    assert_eq!(data_api.last_ref().unwrap().buyprice(), dec!(101));
+    assert_eq!(data_api.last_ref().unwrap().historical(), vec![99.0, 100.0]);
+
+    data_api.update("BTC").await.unwrap();
+    data_api.update("BTC").await.unwrap();
+    assert_eq!(data_api.len(), 3);
+    assert_eq!(data_api.last_ref().unwrap().sellprice(), dec!(100));
+}
+
+#[tokio::test]
+async fn test_unknown_symbol() {
+    let source = ScriptedSource::new();
+    source.push_quote("BTC", Quote::new(dec!(101), dec!(99), dec!(100)));
+    source.set_historical("BTC", vec![100.0]);
+
+    let mut data_api = DataAPI::with_source(source);
+    match data_api.update("DOGE").await {
+        Err(Error::UnknownSymbol(symbol)) => assert_eq!(symbol, "DOGE-USD"),
+        other => panic!("expected unknown symbol, got {:?}", other),
+    }
+    assert!(data_api.is_empty());
+}
+
+#[tokio::test]
+async fn test_window_resamples_history() {
+    // Six-hourly prices over two days, resampled to one close per day.
+    let start = Utc.with_ymd_and_hms(2020, 6, 1, 0, 0, 0).unwrap();
+    let prices: Vec<_> = (0..8)
+        .map(|i| Point::new(start + Duration::hours(6 * i), i as f32))
+        .collect();
+    let source = ScriptedSource::new();
+    source.push_quote("BTC", Quote::new(dec!(101), dec!(99), dec!(100)));
+    source.set_history("BTC", History::new(prices, vec![]));
+
+    let window = Window::new(Duration::days(2)).granularity(Duration::days(1));
+    let mut data_api = DataAPI::with_source(source).with_window(window);
+    data_api.update("BTC").await.unwrap();
+
+    let coin = data_api.last_ref().unwrap();
+    assert_eq!(coin.historical(), vec![3.0, 7.0]);
+    assert_eq!(coin.prices()[1].time, start + Duration::days(1));
 }
 
 #[test]
-fn test_update() {
-    let mut data_api = DataAPI::new();
-    assert_eq!(data_api.coins(), vec![]);
-    data_api.update("BTC");
-    assert_eq!(data_api.coins().len(), 1);
+fn test_resample_fills_gaps() {
+    let start = Utc.with_ymd_and_hms(2020, 6, 1, 0, 0, 0).unwrap();
+    let history = History::new(
+        vec![Point::new(start, 1.0), Point::new(start + Duration::minutes(25), 2.0)],
+        vec![],
+    );
+
+    let resampled = history.resample(Duration::minutes(10));
+    assert_eq!(resampled.values(), vec![1.0, 1.0, 2.0]);
+    assert_eq!(history.resample(Duration::zero()), history);
+}
+
+#[test]
+fn test_resample_keeps_latest_points() {
+    let start = Utc.with_ymd_and_hms(2020, 6, 1, 0, 0, 0).unwrap();
+    let history = History::new(
+        vec![Point::new(start, 1.0), Point::new(start + Duration::days(30), 2.0)],
+        vec![],
+    );
+
+    // A month at one-millisecond steps would be billions of points.
+    let resampled = history.resample(Duration::milliseconds(1));
+    assert_eq!(resampled.prices.len(), MAX_POINTS);
+    let end = start + Duration::days(30);
+    assert_eq!(resampled.prices[0], Point::new(end - Duration::milliseconds(MAX_POINTS as i64 - 1), 1.0));
+    assert_eq!(resampled.prices.last(), Some(&Point::new(end, 2.0)));
+}
+
+#[tokio::test]
+async fn test_update_many() {
+    let source = ScriptedSource::new();
+    source.push_quote("BTC", Quote::new(dec!(101), dec!(99), dec!(100)));
+    source.set_historical("BTC", vec![100.0]);
+    source.push_quote("ETH-EUR", Quote::new(dec!(11), dec!(9), dec!(10)));
+    source.set_historical("ETH-EUR", vec![10.0]);
+
+    let mut data_api = DataAPI::with_source(source);
+    let results = data_api.update_many(&["BTC", "DOGE", "ETH-EUR"]).await;
+
+    let pairs: Vec<_> = results.iter().map(|(pair, _)| pair.to_string()).collect();
+    assert_eq!(pairs, vec!["BTC-USD", "DOGE-USD", "ETH-EUR"]);
+    assert!(results[0].1.is_ok());
+    assert!(matches!(results[1].1, Err(Error::UnknownSymbol(_))));
+    assert!(results[2].1.is_ok());
+
+    let stored: Vec<_> = data_api.iter().map(|c| c.pair().to_string()).collect();
+    assert_eq!(stored, vec!["BTC-USD", "ETH-EUR"]);
+}
+
+#[tokio::test]
+async fn test_update_many_concurrency_cap() {
+    let pairs: Vec<String> = (0..10).map(|i| format!("C{}", i)).collect();
+    let mut data_api = DataAPI::with_source(SlowSource::default()).with_concurrency(3);
+
+    let results = data_api.update_many(&pairs.iter().collect::<Vec<_>>()).await;
+    assert!(results.iter().all(|(_, r)| r.is_ok()));
+    assert_eq!(data_api.source().max_in_flight.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_retention_count() {
+    let source = ScriptedSource::new();
+    for i in 0..5 {
+        source.push_quote("BTC", Quote::new(i.into(), dec!(0), dec!(0)));
+    }
+    source.push_quote("ETH", Quote::new(dec!(10), dec!(9), dec!(9.5)));
+    source.set_historical("BTC", vec![100.0]);
+    source.set_historical("ETH", vec![10.0]);
+
+    let mut data_api = DataAPI::with_source(source).with_retention(Retention::last(3));
+    for _ in 0..5 {
+        data_api.update("BTC").await.unwrap();
+    }
+    data_api.update("ETH").await.unwrap();
+
+    let buys: Vec<_> = data_api.iter().map(|c| c.buyprice()).collect();
+    assert_eq!(buys, vec![dec!(3), dec!(4), dec!(10)]);
+    assert_eq!(data_api.latest("BTC").unwrap().buyprice(), dec!(4));
+    assert_eq!(data_api.iter_pair("BTC").count(), 2);
+    assert!(data_api.latest("BTC-EUR").is_none());
+    assert_eq!(data_api.last_ref().unwrap().pair(), Pair::from("ETH"));
+}
+
+#[tokio::test]
+async fn test_retention_age_and_bytes() {
+    let source = ScriptedSource::new();
+    source.push_quote("BTC", Quote::new(dec!(1), dec!(1), dec!(1)).at(Utc::now() - Duration::hours(2)));
+    source.push_quote("BTC", Quote::new(dec!(2), dec!(2), dec!(2)));
+    source.set_historical("BTC", vec![100.0; 100]);
+
+    let retention = Retention::unbounded().max_age(std::time::Duration::from_secs(3600));
+    let mut data_api = DataAPI::with_source(source.clone()).with_retention(retention);
+    data_api.update("BTC").await.unwrap();
+    assert_eq!(data_api.len(), 1, "the newest snapshot is always kept");
+    data_api.update("BTC").await.unwrap();
+    assert_eq!(data_api.len(), 1);
+    assert_eq!(data_api.last_ref().unwrap().buyprice(), dec!(2));
+
+    let mut data_api = DataAPI::with_source(source).with_retention(Retention::unbounded().max_bytes(8_000));
+    for _ in 0..10 {
+        data_api.update("BTC").await.unwrap();
+    }
+    // Each snapshot holds 200 timestamped points, so only a couple fit.
+    assert!(data_api.len() < 10 && !data_api.is_empty());
+}
+
+#[test]
+fn test_blocking_update() {
+    let source = ScriptedSource::new();
+    source.push_quote("ETH", Quote::new(dec!(11), dec!(9), dec!(10)));
+    source.set_historical("ETH", vec![10.0]);
+
+    let mut data_api = blocking::DataAPI::with_source(source);
+    data_api.update("ETH").unwrap();
+    assert_eq!(data_api.last_ref().unwrap().buyprice(), dec!(11));
+}
+
+#[test]
+fn test_blocking_from_several_threads() {
+    let threads: Vec<_> = (0..4)
+        .map(|i| {
+            std::thread::spawn(move || {
+                let source = ScriptedSource::new();
+                source.push_quote("ETH", Quote::new(dec!(11) + Decimal::from(i), dec!(9), dec!(10)));
+                source.set_historical("ETH", vec![10.0]);
+
+                // Every wrapper runs on the one shared runtime.
+                let mut data_api = blocking::DataAPI::with_source(source);
+                data_api.update("ETH").unwrap();
+                data_api.last_ref().unwrap().buyprice()
+            })
+        })
+        .collect();
+
+    let prices: Vec<Decimal> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+    assert_eq!(prices, vec![dec!(11), dec!(12), dec!(13), dec!(14)]);
+}
+
+#[tokio::test]
+async fn test_local_endpoints() {
+    // Nothing listens on the discard port, so the request must fail locally.
+    let mut data_api = DataAPI::with_endpoints(Endpoints::local("http://127.0.0.1:9")).unwrap();
+    match data_api.update("BTC").await {
+        Err(Error::Transport(e)) => assert!(e.url().unwrap().as_str().starts_with("http://127.0.0.1:9/")),
+        other => panic!("expected transport error, got {:?}", other),
+    }
 }
@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use chrono::{TimeZone, Utc};
+use openssl::bn::BigNum;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::ecdsa::EcdsaSig;
+use openssl::nid::Nid;
+use openssl::sha::sha256;
+use rust_decimal_macros::dec;
+
+use seventh_core::api::auth::{Authenticator, HmacKey, JwtKey, Request};
+use seventh_core::api::book::Side;
+use seventh_core::api::orders::OrderQuery;
+use seventh_core::api::{BrokerAPI, Pair};
+use seventh_core::mock::{fixtures, MockResponse, MockServer};
+use seventh_core::Error;
+
+fn broker(server: &MockServer) -> BrokerAPI {
+    server.mock_order("acct", "buys", MockResponse::json(fixtures::order("buy", 0.5, "BTC", 50.0)));
+    BrokerAPI::with_endpoints(server.endpoints(), "token", "acct", "pay").unwrap()
+}
+
+#[test]
+fn test_hmac_signature() {
+    let request = Request {
+        method: "POST",
+        host: "api.coinbase.com",
+        path: "/v2/accounts/acct/buys",
+        query: None,
+        body: r#"{"amount":"50"}"#,
+        time: Utc.timestamp_opt(1_600_000_000, 0).unwrap(),
+    };
+
+    let headers = HmacKey::new("key", "secret").headers(&request).unwrap();
+    assert_eq!(headers[0], ("CB-ACCESS-KEY".to_owned(), "key".to_owned()));
+    assert_eq!(headers[1].1, "b3ee66e12c8afa3f7a2cc2504dab2aaeff6218b22929229d1537524e669a9144");
+    assert_eq!(headers[2].1, "1600000000");
+}
+
+#[tokio::test]
+async fn test_broker_signs_with_hmac() {
+    let server = MockServer::start();
+    let key = HmacKey::new("key", "secret");
+    let mut broker = broker(&server).with_authenticator(key.clone());
+    broker.buy(&Pair::from("BTC"), dec!(50)).await.unwrap();
+
+    let request = &server.requests()[0];
+    assert!(!request.headers.contains_key("authorization"));
+    assert_eq!(request.headers["cb-access-key"], "key");
+
+    let timestamp: i64 = request.headers["cb-access-timestamp"].parse().unwrap();
+    assert!((Utc::now().timestamp() - timestamp).abs() < 10);
+    let signed = Request {
+        method: "POST",
+        host: "127.0.0.1",
+        path: &request.path,
+        query: request.query.as_deref(),
+        body: &request.body,
+        time: Utc.timestamp_opt(timestamp, 0).unwrap(),
+    };
+    assert_eq!(request.headers["cb-access-sign"], key.sign(&signed));
+}
+
+#[tokio::test]
+async fn test_broker_signs_jwt() {
+    let private = EcKey::generate(&EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap()).unwrap();
+    let pem = String::from_utf8(private.private_key_to_pem().unwrap()).unwrap();
+    let key = JwtKey::from_pem("organizations/org/apiKeys/key", &pem).unwrap();
+
+    let server = MockServer::start();
+    let mut broker = broker(&server).with_authenticator(key);
+    broker.buy(&Pair::from("BTC"), dec!(50)).await.unwrap();
+
+    let authorization = &server.requests()[0].headers["authorization"];
+    let token = authorization.strip_prefix("Bearer ").unwrap();
+    let parts: Vec<&str> = token.split('.').collect();
+    assert_eq!(parts.len(), 3);
+
+    let decode = |part: &str| base64::decode_config(part, base64::URL_SAFE_NO_PAD).unwrap();
+    let header: serde_json::Value = serde_json::from_slice(&decode(parts[0])).unwrap();
+    let claims: serde_json::Value = serde_json::from_slice(&decode(parts[1])).unwrap();
+    assert_eq!(header["alg"], "ES256");
+    assert_eq!(header["kid"], "organizations/org/apiKeys/key");
+    assert_eq!(claims["uri"], "POST 127.0.0.1/v2/accounts/acct/buys");
+    assert_eq!(claims["exp"].as_i64().unwrap() - claims["nbf"].as_i64().unwrap(), 120);
+
+    let signature = decode(parts[2]);
+    let r = BigNum::from_slice(&signature[..32]).unwrap();
+    let s = BigNum::from_slice(&signature[32..]).unwrap();
+    let signature = EcdsaSig::from_private_components(r, s).unwrap();
+    let digest = sha256(format!("{}.{}", parts[0], parts[1]).as_bytes());
+    assert!(signature.verify(&digest, &private).unwrap());
+}
+
+#[tokio::test]
+async fn test_jwt_uri_leaves_out_query() {
+    let private = EcKey::generate(&EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap()).unwrap();
+    let pem = String::from_utf8(private.private_key_to_pem().unwrap()).unwrap();
+    let key = JwtKey::from_pem("organizations/org/apiKeys/key", &pem).unwrap();
+
+    let server = MockServer::start();
+    server.mock_list_orders("acct", "buys", MockResponse::json(fixtures::order_list("buy", &[], None)));
+    let broker = broker(&server).with_authenticator(key);
+    broker.list_orders(Side::Buy, &OrderQuery::default()).await.unwrap();
+
+    let request = &server.requests()[0];
+    assert!(request.query.as_deref().unwrap().starts_with("limit="));
+    let token = request.headers["authorization"].strip_prefix("Bearer ").unwrap();
+    let claims = base64::decode_config(token.split('.').nth(1).unwrap(), base64::URL_SAFE_NO_PAD).unwrap();
+    let claims: serde_json::Value = serde_json::from_slice(&claims).unwrap();
+    assert_eq!(claims["uri"], "GET 127.0.0.1/v2/accounts/acct/buys");
+}
+
+#[test]
+fn test_invalid_jwt_key() {
+    match JwtKey::from_pem("key", "not a key") {
+        Err(Error::Auth(_)) => (),
+        Err(other) => panic!("expected auth error, got {:?}", other),
+        Ok(_) => panic!("expected auth error"),
+    }
+}
+
+#[tokio::test]
+async fn test_clock_skew() {
+    let server = MockServer::start();
+    server.mock_time(Utc::now() + chrono::Duration::minutes(2));
+    server.mock_time(Utc::now());
+    let broker = broker(&server);
+
+    match broker.check_clock(Duration::from_secs(30)).await {
+        Err(Error::Auth(msg)) => assert!(msg.contains("more than the 30000ms allowed")),
+        other => panic!("expected auth error, got {:?}", other),
+    }
+
+    let skew = broker.check_clock(Duration::from_secs(30)).await.unwrap();
+    assert!(skew.num_seconds().abs() <= 1);
+}
@@ -0,0 +1,73 @@
+use rust_decimal_macros::dec;
+
+use seventh_core::api::book::Side;
+use seventh_core::api::orders::Order;
+use seventh_core::api::source::{Quote, ScriptedSource};
+use seventh_core::api::Increments;
+use seventh_core::backtrader::{Actions, Backtrader};
+use seventh_core::Error;
+
+#[test]
+fn test_scripted_round_trip() {
+    let source = ScriptedSource::new();
+    source.push_quote("BTC", Quote::new(dec!(100), dec!(100), dec!(100)));
+    source.push_quote("BTC", Quote::new(dec!(100), dec!(100), dec!(100)));
+    source.push_quote("BTC", Quote::new(dec!(200), dec!(200), dec!(200)));
+
+    let mut trader = Backtrader::with_source(dec!(1000), "BTC", source).unwrap();
+    trader.trade(Actions::Buy(dec!(500))).unwrap();
+    assert_eq!(trader.data().account, dec!(500));
+    assert_eq!(trader.data().holding, dec!(5));
+
+    trader.trade(Actions::Sell(dec!(5))).unwrap();
+    assert_eq!(trader.data().account, dec!(1500));
+    assert_eq!(trader.data().holding, dec!(0));
+    assert_eq!(trader.history().len(), 2);
+}
+
+#[test]
+fn test_amounts_rounded_per_product() {
+    let source = ScriptedSource::new();
+    source.push_quote("ETH-EUR", Quote::new(dec!(3), dec!(3), dec!(3)));
+    source.set_increments("ETH-EUR", Increments::new(dec!(0.001), dec!(0.01)));
+
+    let mut trader = Backtrader::with_source(dec!(1000), "ETH-EUR", source).unwrap();
+    trader.trade(Actions::Buy(dec!(100.005))).unwrap();
+    assert_eq!(trader.data().account, dec!(900.00));
+    assert_eq!(trader.data().holding, dec!(33.333));
+
+    // Sizes round down to the base increment and proceeds down to whole cents.
+    trader.trade(Actions::Sell(dec!(0.0019))).unwrap();
+    assert_eq!(trader.data().holding, dec!(33.332));
+    assert_eq!(trader.data().account, dec!(900.00));
+}
+
+#[test]
+fn test_limit_and_stop_orders() {
+    let source = ScriptedSource::new();
+    for price in &[dec!(100), dec!(100), dec!(94), dec!(94), dec!(88)] {
+        source.push_quote("BTC", Quote::new(*price, *price, *price));
+    }
+
+    let mut trader = Backtrader::with_source(dec!(1000), "BTC", source).unwrap();
+    trader.trade(Actions::Order(Order::limit(Side::Buy, dec!(2), dec!(95)))).unwrap();
+    assert_eq!(trader.open_orders().len(), 1);
+    assert_eq!(trader.data().account, dec!(1000));
+
+    // The price falls through the limit, which fills at the limit.
+    trader.trade(Actions::Hold).unwrap();
+    assert!(trader.open_orders().is_empty());
+    assert_eq!((trader.data().account, trader.data().holding), (dec!(810), dec!(2)));
+
+    trader.trade(Actions::Order(Order::stop(Side::Sell, dec!(2), dec!(90)))).unwrap();
+    assert_eq!(trader.open_orders().len(), 1);
+
+    // The stop is reached and sells at market.
+    trader.trade(Actions::Hold).unwrap();
+    assert_eq!((trader.data().account, trader.data().holding), (dec!(986), dec!(0)));
+
+    // Post-only orders that would fill straight away are rejected.
+    let crossing = Order::limit(Side::Buy, dec!(1), dec!(100)).post_only();
+    assert!(matches!(trader.trade(Actions::Order(crossing)), Err(Error::Order(_))));
+    assert!(trader.open_orders().is_empty());
+}
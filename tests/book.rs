@@ -0,0 +1,74 @@
+use std::time::{Duration, Instant};
+
+use rust_decimal_macros::dec;
+
+use seventh_core::api::book::{BookFeed, Level, OrderBook, Side};
+use seventh_core::api::DataAPI;
+use seventh_core::mock::{fixtures, MockFeed, MockServer};
+
+#[test]
+fn test_book_queries() {
+    let mut book = OrderBook::new("BTC-USD");
+    book.update(Side::Buy, dec!(99.0), dec!(1.0));
+    book.update(Side::Buy, dec!(98.0), dec!(3.0));
+    book.update(Side::Sell, dec!(101.0), dec!(1.0));
+    book.update(Side::Sell, dec!(103.0), dec!(2.0));
+
+    assert_eq!(book.best_bid(), Some(Level { price: dec!(99.0), size: dec!(1.0) }));
+    assert_eq!(book.depth(Side::Sell, 5).len(), 2);
+    assert_eq!((book.spread(), book.mid()), (Some(dec!(2.0)), Some(dec!(100.0))));
+    assert_eq!(book.fill_price(Side::Buy, dec!(2.0)), Some(dec!(102.0)));
+    assert_eq!(book.fill_price(Side::Sell, dec!(2.0)), Some(dec!(98.5)));
+    assert_eq!(book.fill_price(Side::Buy, dec!(4.0)), None);
+
+    book.update(Side::Sell, dec!(101.0), dec!(0.0));
+    assert_eq!(book.best_ask(), Some(Level { price: dec!(103.0), size: dec!(2.0) }));
+}
+
+#[test]
+fn test_book_keeps_neighbouring_cents_apart() {
+    let mut book = OrderBook::new("BTC-USD");
+    book.update(Side::Sell, dec!(150000.01), dec!(1));
+    book.update(Side::Sell, dec!(150000.02), dec!(2));
+    assert_eq!(book.depth(Side::Sell, 5).len(), 2);
+
+    // Removing one level leaves its neighbour alone.
+    book.update(Side::Sell, dec!(150000.010), dec!(0));
+    assert_eq!(book.best_ask(), Some(Level { price: dec!(150000.02), size: dec!(2) }));
+}
+
+#[tokio::test]
+async fn test_book_from_mock() {
+    let server = MockServer::start();
+    server.mock_book("BTC-USD", &[(99.5, 2.0), (99.0, 1.0)], &[(100.5, 0.5)]);
+
+    let data_api = DataAPI::with_endpoints(server.endpoints()).unwrap();
+    let book = data_api.order_book("BTC").await.unwrap();
+    assert_eq!(book.sequence(), Some(3));
+    assert_eq!(book.spread(), Some(dec!(1.0)));
+    assert_eq!(book.depth(Side::Buy, 2)[1], Level { price: dec!(99.0), size: dec!(1.0) });
+    assert_eq!(server.requests()[0].query.as_deref(), Some("level=2"));
+}
+
+#[tokio::test]
+async fn test_book_feed_applies_updates() {
+    let mock = MockFeed::start();
+    mock.session(vec![
+        fixtures::l2update("BTC-USD", &[("buy", 98.0, 1.0)]),
+        fixtures::book_snapshot("BTC-USD", &[(99.0, 1.0)], &[(101.0, 1.0)]),
+        fixtures::l2update("BTC-USD", &[("sell", 101.0, 0.0), ("sell", 102.0, 4.0)]),
+    ]);
+
+    let feed = BookFeed::subscribe(&mock.url(), &["BTC-USD"]);
+    let start = Instant::now();
+    while feed.book("BTC-USD").and_then(|b| b.best_ask()).map(|l| l.price) != Some(dec!(102.0)) {
+        assert!(start.elapsed() < Duration::from_secs(5), "book never updated");
+        tokio::time::delay_for(Duration::from_millis(10)).await;
+    }
+
+    let book = feed.book("BTC-USD").unwrap();
+    // The update sent before the snapshot was ignored.
+    assert_eq!(book.depth(Side::Buy, 5), vec![Level { price: dec!(99.0), size: dec!(1.0) }]);
+    let subscribe: serde_json::Value = serde_json::from_str(&mock.received()[0]).unwrap();
+    assert_eq!(subscribe["channels"][0], "level2");
+}
@@ -0,0 +1,103 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use rust_decimal_macros::dec;
+
+use seventh_core::api::cache::Cache;
+use seventh_core::api::endpoints::Endpoints;
+use seventh_core::api::{DataAPI, Quote, ScriptedSource};
+use seventh_core::mock::{MockResponse, MockServer};
+
+fn stored_entries(path: &Path) -> usize {
+    let stored: serde_json::Value = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+    stored["quotes"].as_object().unwrap().len() + stored["history"].as_object().unwrap().len()
+}
+
+#[tokio::test]
+async fn test_quote_cached_within_ttl() {
+    let source = ScriptedSource::new();
+    source.push_quote("BTC", Quote::new(dec!(101), dec!(99), dec!(100)));
+    source.push_quote("BTC", Quote::new(dec!(102), dec!(100), dec!(101)));
+    source.set_historical("BTC", vec![100.0]);
+
+    let cache = Cache::new().quote_ttl(Duration::from_secs(3600));
+    let mut data_api = DataAPI::with_source(source).with_cache(cache);
+    data_api.update("BTC").await.unwrap();
+    data_api.update("BTC").await.unwrap();
+    assert_eq!(data_api.len(), 2);
+    assert_eq!(data_api.last_ref().unwrap().buyprice(), dec!(101));
+
+    data_api.cache().unwrap().clear().unwrap();
+    data_api.update("BTC").await.unwrap();
+    assert_eq!(data_api.last_ref().unwrap().buyprice(), dec!(102));
+}
+
+#[tokio::test]
+async fn test_stale_served_when_source_fails() {
+    let server = MockServer::start();
+    server.mock_quote("BTC", 101.0, 99.0, 100.0);
+    server.mock_history("bitcoin", &[98.0, 99.0, 100.0]);
+    server.mock("GET", "/coins/bitcoin/market_chart/range", MockResponse::status(500));
+
+    let cache = Cache::new().history_ttl(Duration::from_secs(0));
+    let mut data_api = DataAPI::with_endpoints(server.endpoints()).unwrap().with_cache(cache);
+    data_api.update("BTC").await.unwrap();
+    data_api.update("BTC").await.unwrap();
+    assert_eq!(data_api.last_ref().unwrap().historical(), vec![98.0, 99.0, 100.0]);
+
+    let history = server.requests().iter().filter(|r| r.path.starts_with("/coins/")).count();
+    assert_eq!(history, 2);
+
+    let cache = Cache::new().history_ttl(Duration::from_secs(0)).max_stale(Duration::from_secs(0));
+    let mut data_api = DataAPI::with_endpoints(server.endpoints()).unwrap().with_cache(cache);
+    assert!(data_api.update("BTC").await.is_err());
+}
+
+#[tokio::test]
+async fn test_disk_store() {
+    let dir = env::temp_dir().join(format!("seventh_cache_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("market.json");
+
+    let server = MockServer::start();
+    server.mock_quote("ETH-EUR", 11.0, 9.0, 10.0);
+    server.mock_history("ethereum", &[9.0, 10.0]);
+
+    let cache = Cache::new().save_interval(Duration::from_secs(3600)).store(&path).unwrap();
+    let mut data_api = DataAPI::with_endpoints(server.endpoints()).unwrap().with_cache(cache);
+    data_api.update("ETH-EUR").await.unwrap();
+    let fetched = data_api.last_ref().unwrap().clone();
+
+    // The first fetch is saved straight away, and the second waits for the next save.
+    assert_eq!(stored_entries(&path), 1);
+    data_api.cache().unwrap().flush().unwrap();
+    assert_eq!(stored_entries(&path), 2);
+    assert!(!dir.join("market.json.tmp").exists());
+
+    // Nothing listens on the discard port, so only the stored entries can answer.
+    let cache = Cache::new().quote_ttl(Duration::from_secs(3600)).store(&path).unwrap();
+    let mut offline = DataAPI::with_endpoints(Endpoints::local("http://127.0.0.1:9")).unwrap().with_cache(cache);
+    offline.update("ETH-EUR").await.unwrap();
+    assert_eq!(offline.last_ref(), Some(&fetched));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_failed_save_does_not_fail_update() {
+    let dir = env::temp_dir().join(format!("seventh_cache_gone_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let cache = Cache::new().store(dir.join("market.json")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    let source = ScriptedSource::new();
+    source.push_quote("BTC", Quote::new(dec!(101), dec!(99), dec!(100)));
+    source.set_historical("BTC", vec![100.0]);
+    let mut data_api = DataAPI::with_source(source).with_cache(cache);
+    data_api.update("BTC").await.unwrap();
+
+    // The entries are still waiting to be saved.
+    assert!(data_api.cache().unwrap().flush().is_err());
+}
@@ -0,0 +1,102 @@
+use rust_decimal_macros::dec;
+
+use seventh_core::api::consensus::{Consensus, Method};
+use seventh_core::api::{DataAPI, GeckoSource, Pair, PriceSource, Quote, ScriptedSource, WebSource};
+use seventh_core::mock::MockServer;
+use seventh_core::Error;
+
+fn scripted(buy: i64, sell: i64, spot: i64) -> ScriptedSource {
+    let source = ScriptedSource::new();
+    source.push_quote("BTC", Quote::new(buy.into(), sell.into(), spot.into()));
+    source.set_historical("BTC", vec![100.0]);
+    source
+}
+
+#[tokio::test]
+async fn test_median_and_divergence() {
+    let consensus = Consensus::new()
+        .source("a", scripted(101, 99, 100))
+        .source("b", scripted(102, 100, 101))
+        .source("bad", scripted(131, 129, 130));
+    let mut divergences = consensus.divergences();
+
+    let mut data_api = DataAPI::with_source(consensus);
+    data_api.update("BTC").await.unwrap();
+    let coin = data_api.last_ref().unwrap();
+    assert_eq!((coin.buyprice(), coin.sellprice(), coin.spotprice()), (dec!(102), dec!(100), dec!(101)));
+
+    let divergence = divergences.try_recv().unwrap();
+    assert_eq!(divergence.source, "bad");
+    assert_eq!((divergence.spot, divergence.consensus), (dec!(130), dec!(101)));
+    assert!(divergences.try_recv().is_err());
+
+    let report = data_api.source().last_report(&Pair::from("BTC")).unwrap();
+    let deviations: Vec<_> = report.sources.iter().map(|d| (d.source.as_str(), d.deviation.round_dp(4))).collect();
+    assert_eq!(deviations, vec![("a", dec!(-0.0099)), ("b", dec!(0)), ("bad", dec!(0.2871))]);
+}
+
+#[tokio::test]
+async fn test_volume_weighted() {
+    let heavy = ScriptedSource::new();
+    heavy.push_quote("BTC", Quote::new(dec!(100), dec!(100), dec!(100)).with_volume(dec!(3)));
+    let light = ScriptedSource::new();
+    light.push_quote("BTC", Quote::new(dec!(104), dec!(104), dec!(104)).with_volume(dec!(1)));
+
+    let consensus = Consensus::new()
+        .source("heavy", heavy)
+        .source("light", light)
+        .source("unweighted", scripted(200, 200, 200))
+        .method(Method::VolumeWeighted)
+        .threshold(dec!(0.5));
+    let mut divergences = consensus.divergences();
+
+    let quote = consensus.quote(&Pair::from("BTC")).await.unwrap();
+    assert_eq!(quote.spot, dec!(101));
+    assert_eq!(quote.volume, Some(dec!(4)));
+    assert_eq!(divergences.try_recv().unwrap().source, "unweighted");
+}
+
+#[tokio::test]
+async fn test_failed_sources() {
+    let consensus = Consensus::new()
+        .source("a", scripted(101, 99, 100))
+        .source("empty", ScriptedSource::new());
+
+    let report = consensus.report(&Pair::from("BTC")).await.unwrap();
+    assert_eq!(report.quote.spot, dec!(100));
+    assert_eq!(report.failed[0].source, "empty");
+
+    let consensus = consensus.min_sources(2);
+    match consensus.quote(&Pair::from("BTC")).await {
+        Err(Error::NoConsensus(msg)) => assert!(msg.contains("empty: unknown symbol")),
+        other => panic!("expected no consensus, got {:?}", other),
+    }
+    assert!(consensus.last_report(&Pair::from("BTC")).is_none());
+}
+
+#[tokio::test]
+async fn test_coinbase_against_coingecko() {
+    let server = MockServer::start();
+    server.mock_quote("BTC", 101.0, 99.0, 100.0);
+    server.mock_gecko_price("bitcoin", "usd", 102.0, 5000.0);
+    server.mock_history("bitcoin", &[98.0, 99.0, 100.0]);
+
+    let consensus = Consensus::new()
+        .source("coinbase", WebSource::with_endpoints(server.endpoints()).unwrap())
+        .source("coingecko", GeckoSource::with_endpoints(server.endpoints()).unwrap())
+        .threshold(dec!(0.005));
+    let mut divergences = consensus.divergences();
+
+    let report = consensus.report(&Pair::from("BTC")).await.unwrap();
+    assert_eq!(report.sources[1].quote.volume, Some(dec!(5000)));
+    assert_eq!(report.quote.spot, dec!(101));
+
+    let mut data_api = DataAPI::with_source(consensus);
+    data_api.update("BTC").await.unwrap();
+    assert_eq!(data_api.last_ref().unwrap().historical(), vec![98.0, 99.0, 100.0]);
+    assert_eq!(divergences.try_recv().unwrap().source, "coinbase");
+    assert_eq!(divergences.try_recv().unwrap().source, "coingecko");
+
+    let gecko = server.requests().into_iter().find(|r| r.path == "/simple/price").unwrap();
+    assert!(gecko.query.unwrap().contains("ids=bitcoin&vs_currencies=usd"));
+}
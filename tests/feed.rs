@@ -0,0 +1,87 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::StreamExt;
+use rust_decimal_macros::dec;
+use tokio::time::timeout;
+
+use seventh_core::api::feed::TickerFeed;
+use seventh_core::api::CoinData;
+use seventh_core::backtrader::Actions;
+use seventh_core::livetrader::Livetrader;
+use seventh_core::mock::{fixtures, MockFeed, MockResponse, MockServer};
+
+async fn next(feed: &mut TickerFeed) -> CoinData {
+    timeout(Duration::from_secs(5), feed.next()).await.expect("Feed update").unwrap()
+}
+
+#[tokio::test]
+async fn test_feed_streams_ticker() {
+    let mock = MockFeed::start();
+    mock.session(vec![
+        fixtures::subscriptions(&["BTC-USD"]),
+        fixtures::trade("BTC-USD", 100.0, 0.5),
+        fixtures::ticker("BTC-USD", 100.0, 99.0, 101.0),
+    ]);
+
+    let mut feed = TickerFeed::subscribe(&mock.url(), &["BTC"]);
+    let coin = next(&mut feed).await;
+    assert_eq!((coin.buyprice(), coin.sellprice()), (dec!(101), dec!(99)));
+    assert_eq!(coin.historical(), vec![100.0]);
+    assert_eq!(coin.volumes()[0].value, 0.5);
+    assert_eq!(feed.latest("BTC-USD"), Some(coin));
+
+    let subscribe: serde_json::Value = serde_json::from_str(&mock.received()[0]).unwrap();
+    assert_eq!(subscribe["type"], "subscribe");
+    assert_eq!(subscribe["product_ids"][0], "BTC-USD");
+}
+
+#[tokio::test]
+async fn test_feed_skips_bad_messages() {
+    let mock = MockFeed::start();
+    mock.session(vec!["not json".to_owned(), fixtures::ticker("BTC-USD", 100.0, 99.0, 101.0)]);
+
+    let mut feed = TickerFeed::subscribe(&mock.url(), &["BTC"]);
+    assert_eq!(next(&mut feed).await.buyprice(), dec!(101));
+    assert_eq!(mock.connections(), 1);
+    assert!(feed.last_error().is_some());
+}
+
+#[tokio::test]
+async fn test_feed_reconnects() {
+    let mock = MockFeed::start();
+    mock.dropped_session(vec![fixtures::ticker("ETH-EUR", 10.0, 9.0, 11.0)]);
+    mock.session(vec![fixtures::ticker("ETH-EUR", 20.0, 19.0, 21.0)]);
+
+    let mut feed = TickerFeed::subscribe(&mock.url(), &["ETH-EUR"]);
+    assert_eq!(next(&mut feed).await.buyprice(), dec!(11));
+    assert_eq!(next(&mut feed).await.buyprice(), dec!(21));
+    assert_eq!(mock.connections(), 2);
+    assert_eq!(mock.received().len(), 2);
+}
+
+#[test]
+fn test_livetrader_runs_off_feed() {
+    let server = MockServer::start();
+    server.mock_quote("BTC", 101.0, 99.0, 100.0);
+    server.mock_history("bitcoin", &[100.0]);
+    server.mock_order("acct", "buys", MockResponse::json(fixtures::completed_order("buy", 1.0, "BTC", 201.0)));
+    let mock = MockFeed::start();
+    mock.session(vec![fixtures::ticker("BTC-USD", 200.0, 199.0, 201.0)]);
+
+    let mut trader = Livetrader::with_endpoints(dec!(1000), "BTC", server.endpoints(), "token", "acct", "pay")
+        .unwrap()
+        .with_feed(&mock.url());
+
+    let start = Instant::now();
+    while trader.data().buy != dec!(201) {
+        assert!(start.elapsed() < Duration::from_secs(5), "feed never delivered");
+        thread::sleep(Duration::from_millis(10));
+    }
+    let polled = server.requests().len();
+
+    trader.trade(Actions::Buy(dec!(201))).unwrap();
+    assert_eq!(trader.data().holding, dec!(1));
+    // Only the order was sent; the prices came from the feed.
+    assert_eq!(server.requests().len(), polled + 1);
+}
@@ -0,0 +1,74 @@
+use rust_decimal_macros::dec;
+
+use seventh_core::api::book::Side;
+use seventh_core::api::orders::{Order, TimeInForce};
+use seventh_core::backtrader::Actions;
+use seventh_core::historical::{Histtrader, RawData};
+use seventh_core::Error;
+
+const CSV: &str = "\
+date,open,high,low,close
+1,10.0,12.0,9.0,11.0
+2,11.0,13.0,10.0,12.0
+3,12.0,14.0,11.0,13.0
+4,13.0,15.0,12.0,14.0
+5,14.0,16.0,13.0,15.0
+";
+
+#[test]
+fn test_histtrader_steps_through_data() {
+    let data = RawData::from_reader(CSV.as_bytes()).unwrap();
+    let mut trader = Histtrader::new(data, "TEST", dec!(100), 1);
+    assert_eq!(trader.data().historical, vec![11.0, 12.0]);
+
+    trader.trade(Actions::Buy(dec!(22))).unwrap();
+    assert_eq!(trader.data().historical, vec![12.0, 13.0]);
+    assert_eq!(trader.data().account, dec!(78));
+    assert_eq!(trader.data().holding, dec!(2));
+
+    trader.trade(Actions::Sell(dec!(2))).unwrap();
+    assert_eq!(trader.data().account, dec!(108));
+    assert_eq!(trader.data().holding, dec!(0));
+
+    // Post-only orders that would fill straight away are rejected.
+    let crossing = Order::limit(Side::Buy, dec!(1), dec!(20)).post_only();
+    assert!(matches!(trader.trade(Actions::Order(crossing)), Err(Error::Order(_))));
+    assert_eq!(trader.data().account, dec!(108));
+    assert!(trader.open_orders().is_empty());
+}
+
+#[test]
+fn test_histtrader_stop_limit_orders() {
+    let data = RawData::from_reader(CSV.as_bytes()).unwrap();
+    let mut trader = Histtrader::new(data, "TEST", dec!(100), 1);
+
+    // Buys at 11, below the stop.
+    trader.trade(Actions::Order(Order::stop_limit(Side::Buy, dec!(2), dec!(12), dec!(12.5)))).unwrap();
+    assert_eq!(trader.open_orders().len(), 1);
+    assert_eq!(trader.data().account, dec!(100));
+
+    // Reaching the stop places the limit, which fills at market.
+    trader.trade(Actions::Hold).unwrap();
+    assert!(trader.open_orders().is_empty());
+    assert_eq!((trader.data().account, trader.data().holding), (dec!(76), dec!(2)));
+
+    // Immediate orders that cannot fill are dropped.
+    let order = Order::limit(Side::Sell, dec!(2), dec!(20)).time_in_force(TimeInForce::ImmediateOrCancel);
+    trader.trade(Actions::Order(order)).unwrap();
+    assert!(trader.open_orders().is_empty());
+    assert_eq!(trader.data().holding, dec!(2));
+}
+
+#[test]
+fn test_malformed_csv_is_an_error() {
+    let csv = "date,open,high,low,close\n1,10.0,twelve,9.0,11.0\n";
+    assert!(RawData::from_reader(csv.as_bytes()).is_err());
+
+    let csv = "date,open,high,low,close\n1,10.0,12.0,9.0,\n";
+    assert!(RawData::from_reader(csv.as_bytes()).is_err());
+
+    // Prices written in exponent form are read exactly.
+    let csv = "date,open,high,low,close,volume,buy,sell\n1,10.0,12.0,9.0,11.0,5.0,1.2e1,9E0\n";
+    let data = RawData::from_reader(csv.as_bytes()).unwrap();
+    assert_eq!((data.buy(), data.sell()), (&[dec!(12)][..], &[dec!(9)][..]));
+}
@@ -0,0 +1,289 @@
+use std::time::Duration;
+
+use chrono::{TimeZone, Utc};
+use rust_decimal_macros::dec;
+
+use seventh_core::api::book::Side;
+use seventh_core::api::transactions::OrderStatus;
+use seventh_core::api::{BrokerAPI, DataAPI, Granularity, Pair, Window};
+use seventh_core::backtrader::{Actions, Backtrader};
+use seventh_core::livetrader::Livetrader;
+use seventh_core::mock::{fixtures, MockResponse, MockServer};
+use seventh_core::Error;
+
+fn market(server: &MockServer) {
+    server.mock_quote("BTC", 101.0, 99.0, 100.0);
+    server.mock_history("bitcoin", &[98.0, 99.0, 100.0]);
+}
+
+#[tokio::test]
+async fn test_data_from_mock() {
+    let server = MockServer::start();
+    market(&server);
+
+    let mut data_api = DataAPI::with_endpoints(server.endpoints()).unwrap();
+    data_api.update("BTC").await.unwrap();
+
+    let coin = data_api.last_ref().unwrap();
+    assert_eq!(coin.buyprice(), dec!(101));
+    assert_eq!(coin.sellprice(), dec!(99));
+    assert_eq!(coin.historical(), vec![98.0, 99.0, 100.0]);
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 4);
+    let history = requests.iter().find(|r| r.path.starts_with("/coins/")).unwrap();
+    assert!(history.query.as_ref().unwrap().contains("vs_currency=usd"));
+}
+
+#[tokio::test]
+async fn test_timestamps_and_volumes() {
+    let server = MockServer::start();
+    market(&server);
+
+    let before = Utc::now();
+    let mut data_api = DataAPI::with_endpoints(server.endpoints()).unwrap();
+    data_api.update("BTC").await.unwrap();
+
+    let coin = data_api.last_ref().unwrap();
+    assert!(coin.time() >= before && coin.time() <= Utc::now());
+
+    let start = Utc.timestamp_millis_opt(fixtures::CHART_START).unwrap();
+    let step = chrono::Duration::milliseconds(fixtures::CHART_STEP);
+    let times: Vec<_> = coin.prices().iter().map(|p| p.time).collect();
+    assert_eq!(times, vec![start, start + step, start + step * 2]);
+
+    let volumes: Vec<_> = coin.volumes().iter().map(|p| (p.time, p.value)).collect();
+    assert_eq!(volumes, vec![(start, 98_000.0), (start + step, 99_000.0), (start + step * 2, 100_000.0)]);
+}
+
+#[tokio::test]
+async fn test_history_lookback() {
+    let server = MockServer::start();
+    market(&server);
+
+    let window = Window::new(chrono::Duration::days(30));
+    let mut data_api = DataAPI::with_endpoints(server.endpoints()).unwrap().with_window(window);
+    data_api.update("BTC").await.unwrap();
+
+    let requests = server.requests();
+    let history = requests.iter().find(|r| r.path.starts_with("/coins/")).unwrap();
+    let param = |name: &str| -> i64 {
+        let query = history.query.as_ref().unwrap();
+        let pair = query.split('&').find(|p| p.starts_with(name)).unwrap();
+        pair[name.len() + 1..].parse().unwrap()
+    };
+    assert_eq!(param("to") - param("from"), 30 * 86400);
+}
+
+#[tokio::test]
+async fn test_candles_paginate() {
+    let server = MockServer::start();
+    let start = Utc.with_ymd_and_hms(2020, 6, 1, 0, 0, 0).unwrap();
+    let row = |i: i64| (start.timestamp() + 60 * i, 1.0, 3.0, 2.0, i as f32, 10.0);
+    // Coinbase sends newest first and includes the candle at the end of each page.
+    server.mock_candles("BTC", &(0..=300).rev().map(row).collect::<Vec<_>>());
+    server.mock_candles("BTC", &(300..=400).rev().map(row).collect::<Vec<_>>());
+
+    let data_api = DataAPI::with_endpoints(server.endpoints()).unwrap();
+    let end = start + chrono::Duration::minutes(400);
+    let candles = data_api.candles("BTC", Granularity::OneMinute, start..end).await.unwrap();
+
+    assert_eq!(candles.len(), 400);
+    assert_eq!(candles.close()[0], 0.0);
+    assert_eq!(candles.close()[399], 399.0);
+    assert_eq!((candles.open()[0], candles.high()[0], candles.low()[0]), (2.0, 3.0, 1.0));
+    assert_eq!(candles.volume()[0], 10.0);
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].path, "/products/BTC-USD/candles");
+    assert_eq!(
+        requests[1].query.as_deref(),
+        Some("granularity=60&start=2020-06-01T05:00:00Z&end=2020-06-01T06:40:00Z")
+    );
+}
+
+#[tokio::test]
+async fn test_http_status_error() {
+    let server = MockServer::start();
+    server.mock(
+        "GET",
+        "/coins/bitcoin/market_chart/range",
+        MockResponse::status(500).body(fixtures::error("internal_server_error", "Boom")),
+    );
+    market(&server);
+
+    let mut data_api = DataAPI::with_endpoints(server.endpoints()).unwrap();
+    match data_api.update("BTC").await {
+        Err(Error::Status { status, .. }) => assert_eq!(status, 500),
+        other => panic!("expected status error, got {:?}", other),
+    }
+    assert_eq!(data_api.len(), 0);
+}
+
+#[tokio::test]
+async fn test_rate_limited() {
+    let server = MockServer::start();
+    server.mock("GET", "/v2/prices/BTC-USD/spot", MockResponse::rate_limited(30));
+    market(&server);
+
+    let mut data_api = DataAPI::with_endpoints(server.endpoints()).unwrap();
+    match data_api.update("BTC").await {
+        Err(Error::RateLimited { retry_after }) => {
+            assert_eq!(retry_after, Some(Duration::from_secs(30)))
+        }
+        other => panic!("expected rate limit, got {:?}", other),
+    }
+
+    // The rate limit response is used up, so the next update succeeds.
+    data_api.update("BTC").await.unwrap();
+}
+
+#[tokio::test]
+async fn test_decode_error() {
+    let server = MockServer::start();
+    server.mock("GET", "/v2/prices/BTC-USD/buy", MockResponse::json("<html>".to_owned()));
+    market(&server);
+
+    let mut data_api = DataAPI::with_endpoints(server.endpoints()).unwrap();
+    match data_api.update("BTC").await {
+        Err(Error::Decode(_)) => (),
+        other => panic!("expected decode error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_price_in_wrong_currency() {
+    let server = MockServer::start();
+    server.mock("GET", "/v2/prices/BTC-USD/buy", MockResponse::json(fixtures::price("BTC", "EUR", 101.0)));
+    market(&server);
+
+    let mut data_api = DataAPI::with_endpoints(server.endpoints()).unwrap();
+    match data_api.update("BTC").await {
+        Err(Error::Decode(msg)) => assert!(msg.contains("EUR")),
+        other => panic!("expected decode error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_broker_buy() {
+    let server = MockServer::start();
+    server.mock_order("acct", "buys", MockResponse::json(fixtures::order("buy", 0.5, "BTC", 50.0)));
+
+    let mut broker = BrokerAPI::with_endpoints(server.endpoints(), "token", "acct", "pay").unwrap();
+    let receipt = broker.buy(&Pair::from("BTC-USD"), dec!(50)).await.unwrap();
+    assert_eq!(receipt.side, Side::Buy);
+    assert_eq!(receipt.status, OrderStatus::Created);
+    assert_eq!(receipt.amount.amount, dec!(0.5));
+    assert_eq!(receipt.amount.currency, "BTC");
+    assert_eq!((receipt.subtotal.amount, receipt.fee.amount, receipt.total.amount), (dec!(50), dec!(0.75), dec!(50.75)));
+    assert_eq!(receipt.created_at, Utc.with_ymd_and_hms(2020, 6, 1, 7, 0, 0).unwrap());
+    assert_eq!(broker.history(), vec![receipt]);
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].method, "POST");
+    assert_eq!(requests[0].path, "/v2/accounts/acct/buys");
+    assert_eq!(requests[0].headers["authorization"], "Bearer token");
+
+    let body: serde_json::Value = serde_json::from_str(&requests[0].body).unwrap();
+    assert_eq!(body["amount"], "50");
+    assert_eq!(body["currency"], "USD");
+    assert_eq!(body["payment_method"], "pay");
+}
+
+#[tokio::test]
+async fn test_broker_auth_error() {
+    let server = MockServer::start();
+    server.mock_order(
+        "acct",
+        "sells",
+        MockResponse::status(401).body(fixtures::error("invalid_token", "The access token is invalid")),
+    );
+
+    let mut broker = BrokerAPI::with_endpoints(server.endpoints(), "expired", "acct", "pay").unwrap();
+    match broker.sell(&Pair::from("BTC"), dec!(1)).await {
+        Err(Error::Auth(_)) => (),
+        other => panic!("expected auth error, got {:?}", other),
+    }
+    assert!(broker.history().is_empty());
+}
+
+#[test]
+fn test_backtrader_from_mock() {
+    let server = MockServer::start();
+    market(&server);
+
+    let mut trader = Backtrader::with_endpoints(dec!(1000), "BTC", server.endpoints()).unwrap();
+    trader.trade(Actions::Buy(dec!(101))).unwrap();
+    assert_eq!(trader.data().account, dec!(899));
+    assert_eq!(trader.data().holding, dec!(1));
+}
+
+#[test]
+fn test_livetrader_places_orders() {
+    let server = MockServer::start();
+    market(&server);
+    server.mock_order("acct", "buys", MockResponse::json(fixtures::completed_order("buy", 1.0, "BTC", 101.0)));
+    server.mock_order("acct", "sells", MockResponse::status(401));
+
+    let mut trader =
+        Livetrader::with_endpoints(dec!(1000), "BTC", server.endpoints(), "token", "acct", "pay").unwrap();
+    trader.trade(Actions::Buy(dec!(101))).unwrap();
+    // The total paid includes the fee.
+    assert_eq!(trader.data().account, dec!(897.50));
+    assert_eq!(trader.data().holding, dec!(1));
+
+    // A rejected order leaves the balances untouched.
+    assert!(trader.trade(Actions::Sell(dec!(1))).is_err());
+    assert_eq!(trader.data().account, dec!(897.50));
+    assert_eq!(trader.data().holding, dec!(1));
+
+    let orders: Vec<_> = server.requests().into_iter().filter(|r| r.method == "POST").collect();
+    assert_eq!(orders.len(), 2);
+    assert_eq!(trader.receipts().len(), 1);
+}
+
+#[tokio::test]
+async fn test_quote_currency() {
+    let server = MockServer::start();
+    server.mock_quote("ETH-EUR", 201.0, 199.0, 200.0);
+    server.mock_history("ethereum", &[198.0, 200.0]);
+
+    let mut data_api = DataAPI::with_endpoints(server.endpoints()).unwrap();
+    data_api.update("ETH-EUR").await.unwrap();
+
+    let coin = data_api.last_ref().unwrap();
+    assert_eq!(coin.pair(), Pair::new("ETH", "EUR"));
+    let json = serde_json::to_value(coin).unwrap();
+    assert_eq!(json["ticker"], "ETH");
+    assert_eq!(json["currency"], "EUR");
+
+    let history = server.requests().into_iter().find(|r| r.path.starts_with("/coins/")).unwrap();
+    assert!(history.query.unwrap().contains("vs_currency=eur"));
+}
+
+#[test]
+fn test_livetrader_gbp_orders() {
+    let server = MockServer::start();
+    server.mock_quote("BTC-GBP", 101.0, 99.0, 100.0);
+    server.mock_history("bitcoin", &[100.0]);
+    server.mock_order("acct", "buys", MockResponse::json(fixtures::completed_order("buy", 1.0, "BTC", 101.0)));
+    server.mock_order("acct", "sells", MockResponse::json(fixtures::completed_order("sell", 0.5, "BTC", 49.5)));
+
+    let mut trader =
+        Livetrader::with_endpoints(dec!(500), "BTC-GBP", server.endpoints(), "token", "acct", "pay").unwrap();
+    trader.trade(Actions::Buy(dec!(101))).unwrap();
+    trader.trade(Actions::Sell(dec!(0.5))).unwrap();
+    assert_eq!(trader.data().currency, "GBP");
+
+    let orders: Vec<serde_json::Value> = server
+        .requests()
+        .into_iter()
+        .filter(|r| r.method == "POST")
+        .map(|r| serde_json::from_str(&r.body).unwrap())
+        .collect();
+    // Buys are sized in the quote currency, sells in the base currency.
+    assert_eq!(orders[0]["currency"], "GBP");
+    assert_eq!(orders[1]["currency"], "BTC");
+}
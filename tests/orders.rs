@@ -0,0 +1,324 @@
+use std::time::{Duration, Instant};
+
+use rust_decimal_macros::dec;
+
+use seventh_core::api::book::Side;
+use seventh_core::api::orders::{Order, OrderQuery, OrderType, TimeInForce};
+use seventh_core::api::transactions::OrderStatus;
+use seventh_core::api::{BrokerAPI, Decimal, Pair};
+use seventh_core::backtrader::Actions;
+use seventh_core::livetrader::Livetrader;
+use seventh_core::mock::{fixtures, MockResponse, MockServer};
+use seventh_core::Error;
+
+const ORDER_ID: &str = "67e0eaec-07d7-54c4-a72c-2e92826897df";
+
+fn broker(server: &MockServer) -> BrokerAPI {
+    BrokerAPI::with_endpoints(server.endpoints(), "token", "acct", "pay").unwrap()
+}
+
+#[tokio::test]
+async fn test_quote_then_commit() {
+    let server = MockServer::start();
+    server.mock_order("acct", "buys", MockResponse::json(fixtures::uncommitted_order("buy", 0.5, "BTC", 50.0)));
+    server.mock_commit("acct", "buys", ORDER_ID, MockResponse::json(fixtures::order("buy", 0.5, "BTC", 50.0)));
+
+    let mut broker = broker(&server);
+    let quote = broker.quote_buy(&Pair::from("BTC"), dec!(50)).await.unwrap();
+    assert_eq!(quote.side, Side::Buy);
+    assert_eq!(quote.price, dec!(100));
+    assert_eq!((quote.fee.amount, quote.total.amount), (dec!(0.75), dec!(50.75)));
+    assert!(!quote.is_expired());
+    assert!(broker.history().is_empty());
+    assert_eq!(broker.quotes().count(), 1);
+
+    let receipt = broker.commit(&quote.id).await.unwrap();
+    assert!(receipt.committed);
+    assert_eq!(broker.history(), vec![receipt]);
+    assert_eq!(broker.quotes().count(), 0);
+
+    let requests = server.requests();
+    let body: serde_json::Value = serde_json::from_str(&requests[0].body).unwrap();
+    assert_eq!(body["commit"], false);
+    assert_eq!(requests[1].path, format!("/v2/accounts/acct/buys/{}/commit", ORDER_ID));
+}
+
+#[tokio::test]
+async fn test_commit_unknown_or_expired() {
+    let server = MockServer::start();
+    server.mock_order("acct", "sells", MockResponse::json(fixtures::uncommitted_order("sell", 1.0, "BTC", 100.0)));
+
+    let mut broker = broker(&server).with_quote_ttl(Duration::from_secs(0));
+    match broker.commit("missing").await {
+        Err(Error::Order(msg)) => assert!(msg.contains("no quote missing")),
+        other => panic!("expected order error, got {:?}", other),
+    }
+
+    let quote = broker.quote_sell(&Pair::from("BTC"), dec!(1)).await.unwrap();
+    assert_eq!(quote.side, Side::Sell);
+    match broker.commit(&quote.id).await {
+        Err(Error::Order(msg)) => assert!(msg.contains("expired")),
+        other => panic!("expected order error, got {:?}", other),
+    }
+
+    broker.quote_sell(&Pair::from("BTC"), dec!(1)).await.unwrap();
+    assert_eq!(broker.quotes().count(), 0);
+    assert_eq!(broker.expire().len(), 1);
+    assert!(broker.history().is_empty());
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn test_commit_keeps_quote_after_failure() {
+    let server = MockServer::start();
+    server.mock_order("acct", "buys", MockResponse::json(fixtures::uncommitted_order("buy", 0.5, "BTC", 50.0)));
+    server.mock_commit("acct", "buys", ORDER_ID, MockResponse::status(400));
+    server.mock_commit("acct", "buys", ORDER_ID, MockResponse::json(fixtures::order("buy", 0.5, "BTC", 50.0)));
+
+    let mut broker = broker(&server);
+    let quote = broker.quote_buy(&Pair::from("BTC"), dec!(50)).await.unwrap();
+    assert!(broker.commit(&quote.id).await.is_err());
+    assert_eq!(broker.quotes().count(), 1);
+    assert!(broker.history().is_empty());
+
+    let receipt = broker.commit(&quote.id).await.unwrap();
+    assert_eq!(broker.history(), vec![receipt]);
+    assert_eq!(broker.quotes().count(), 0);
+}
+
+#[tokio::test]
+async fn test_list_and_get_orders() {
+    let server = MockServer::start();
+    let page = [("a", "completed", 100.0), ("b", "canceled", 200.0), ("c", "completed", 300.0)];
+    server.mock_list_orders("acct", "buys", MockResponse::json(fixtures::order_list("buy", &page, Some("c"))));
+    server.mock_get_order("acct", "sells", "d", MockResponse::json(fixtures::completed_order("sell", 1.0, "BTC", 99.0)));
+
+    let broker = broker(&server);
+    let query = OrderQuery::new().limit(3).after("z").status(OrderStatus::Completed);
+    let page = broker.list_orders(Side::Buy, &query).await.unwrap();
+    let ids: Vec<_> = page.orders.iter().map(|o| o.id.as_str()).collect();
+    assert_eq!(ids, vec!["a", "c"]);
+    assert_eq!(page.next.as_deref(), Some("c"));
+
+    let order = broker.get_order(Side::Sell, "d").await.unwrap();
+    assert_eq!((order.side, order.status), (Side::Sell, OrderStatus::Completed));
+
+    let requests = server.requests();
+    assert_eq!(requests[0].query.as_deref(), Some("limit=3&starting_after=z"));
+    assert_eq!(requests[1].path, "/v2/accounts/acct/sells/d");
+    // Orders placed elsewhere are not added to the history.
+    assert!(broker.history().is_empty());
+}
+
+#[tokio::test]
+async fn test_cancel_order() {
+    let server = MockServer::start();
+    server.mock_order("acct", "buys", MockResponse::json(fixtures::order("buy", 0.5, "BTC", 50.0)));
+    server.mock_cancel(MockResponse::json(fixtures::cancel(ORDER_ID, true)));
+    server.mock_cancel(MockResponse::json(fixtures::cancel(ORDER_ID, false)));
+
+    let mut broker = broker(&server);
+    let mut events = broker.events();
+    broker.buy(&Pair::from("BTC"), dec!(50)).await.unwrap();
+    broker.cancel_order(ORDER_ID).await.unwrap();
+    assert_eq!(broker.receipt(ORDER_ID).unwrap().status, OrderStatus::Canceled);
+
+    let placed = events.try_recv().unwrap();
+    assert_eq!((placed.previous, placed.order.status), (None, OrderStatus::Created));
+    let canceled = events.try_recv().unwrap();
+    assert_eq!((canceled.previous, canceled.order.status), (Some(OrderStatus::Created), OrderStatus::Canceled));
+
+    match broker.cancel_order(ORDER_ID).await {
+        Err(Error::Order(msg)) => assert!(msg.contains("DUPLICATE_CANCEL_REQUEST")),
+        other => panic!("expected order error, got {:?}", other),
+    }
+    let body: serde_json::Value = serde_json::from_str(&server.requests()[1].body).unwrap();
+    assert_eq!(body["order_ids"][0], ORDER_ID);
+}
+
+#[tokio::test]
+async fn test_poller_settles_orders() {
+    let server = MockServer::start();
+    server.mock_order("acct", "buys", MockResponse::json(fixtures::order("buy", 0.5, "BTC", 50.0)));
+    server.mock_get_order("acct", "buys", ORDER_ID, MockResponse::json(fixtures::order("buy", 0.5, "BTC", 50.0)));
+    server.mock_get_order("acct", "buys", ORDER_ID, MockResponse::json(fixtures::completed_order("buy", 0.5, "BTC", 50.0)));
+
+    let mut broker = broker(&server);
+    broker.buy(&Pair::from("BTC"), dec!(50)).await.unwrap();
+    let mut events = broker.events();
+    let poller = broker.poll(Duration::from_millis(10));
+
+    let event = tokio::time::timeout(Duration::from_secs(5), events.recv()).await.unwrap().unwrap();
+    assert_eq!((event.previous, event.order.status), (Some(OrderStatus::Created), OrderStatus::Completed));
+    assert_eq!(broker.history()[0].status, OrderStatus::Completed);
+    assert!(poller.last_error().is_none());
+
+    // Settled orders are not looked up again.
+    tokio::time::delay_for(Duration::from_millis(50)).await;
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[test]
+fn test_livetrader_waits_for_completion() {
+    let server = MockServer::start();
+    server.mock_quote("BTC", 101.0, 99.0, 100.0);
+    server.mock_history("bitcoin", &[100.0]);
+    server.mock_order("acct", "buys", MockResponse::json(fixtures::order("buy", 1.0, "BTC", 101.0)));
+    server.mock_get_order("acct", "buys", ORDER_ID, MockResponse::json(fixtures::completed_order("buy", 1.0, "BTC", 101.0)));
+
+    let mut trader =
+        Livetrader::with_endpoints(dec!(1000), "BTC", server.endpoints(), "token", "acct", "pay").unwrap();
+    trader.trade(Actions::Buy(dec!(101))).unwrap();
+    assert_eq!(trader.pending(), &[ORDER_ID.to_owned()]);
+    assert_eq!((trader.data().account, trader.data().holding), (dec!(1000), dec!(0)));
+
+    let start = Instant::now();
+    trader.settle().unwrap();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(trader.pending().is_empty());
+    assert_eq!((trader.data().account, trader.data().holding), (dec!(897.50), dec!(1)));
+}
+
+#[tokio::test]
+async fn test_place_limit_order() {
+    let server = MockServer::start();
+    server.mock_place_order(MockResponse::json(fixtures::placed_order("limit-1", "BTC-USD", "buy")));
+    server.mock_brokerage_order("limit-1", MockResponse::json(fixtures::brokerage_order("limit-1", "BTC-USD", "buy", "OPEN", 0.0, 95.0)));
+    server.mock_brokerage_order("limit-1", MockResponse::json(fixtures::brokerage_order("limit-1", "BTC-USD", "buy", "FILLED", 0.5, 95.0)));
+
+    let mut broker = broker(&server);
+    let order = Order::limit(Side::Buy, dec!(0.5), dec!(95)).post_only();
+    let receipt = broker.place_order(&Pair::from("BTC"), &order).await.unwrap();
+    assert_eq!((receipt.id.as_str(), receipt.status), ("limit-1", OrderStatus::Created));
+    assert_eq!(receipt.amount.amount, dec!(0));
+    assert_eq!(receipt.order.as_ref(), Some(&order));
+
+    assert!(broker.refresh().await.unwrap().is_empty());
+    let events = broker.refresh().await.unwrap();
+    assert_eq!(events.len(), 1);
+    let filled = &events[0].order;
+    assert_eq!((filled.status, filled.amount.amount, filled.total.amount), (OrderStatus::Completed, dec!(0.5), dec!(47.79)));
+    assert_eq!(filled.order.as_ref(), Some(&order));
+    assert!(filled.has_fills());
+
+    let requests = server.requests();
+    assert_eq!(requests[1].path, "/api/v3/brokerage/orders/historical/limit-1");
+    let body: serde_json::Value = serde_json::from_str(&requests[0].body).unwrap();
+    assert_eq!((body["product_id"].as_str(), body["side"].as_str()), (Some("BTC-USD"), Some("BUY")));
+    let config = &body["order_configuration"]["limit_limit_gtc"];
+    assert_eq!((config["base_size"].as_str(), config["limit_price"].as_str()), (Some("0.5"), Some("95")));
+    assert_eq!(config["post_only"], true);
+}
+
+#[tokio::test]
+async fn test_order_configurations() {
+    let server = MockServer::start();
+    server.mock_place_order(MockResponse::json(fixtures::placed_order("a", "BTC-USD", "sell")));
+    server.mock_place_order(MockResponse::json(fixtures::placed_order("b", "BTC-USD", "buy")));
+    server.mock_place_order(MockResponse::json(fixtures::rejected_order("INVALID_LIMIT_PRICE_POST_ONLY")));
+
+    let mut broker = broker(&server);
+    let pair = Pair::from("BTC");
+    let end = "2020-06-02T00:00:00Z".parse().unwrap();
+    let stop_limit = Order::stop_limit(Side::Sell, dec!(1), dec!(90), dec!(89)).time_in_force(TimeInForce::GoodTilTime(end));
+    broker.place_order(&pair, &stop_limit).await.unwrap();
+    broker.place_order(&pair, &Order::market(Side::Buy, dec!(100))).await.unwrap();
+
+    match broker.place_order(&pair, &Order::limit(Side::Buy, dec!(1), dec!(101)).post_only()).await {
+        Err(Error::Order(msg)) => assert!(msg.contains("INVALID_LIMIT_PRICE_POST_ONLY")),
+        other => panic!("expected order error, got {:?}", other),
+    }
+    // Neither of these reach Coinbase.
+    assert!(matches!(broker.place_order(&pair, &Order::stop(Side::Sell, dec!(1), dec!(90))).await, Err(Error::Order(_))));
+    let resting_market = Order::market(Side::Buy, dec!(100)).time_in_force(TimeInForce::GoodTilCancelled);
+    assert!(matches!(broker.place_order(&pair, &resting_market).await, Err(Error::Order(_))));
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    let body: serde_json::Value = serde_json::from_str(&requests[0].body).unwrap();
+    let config = &body["order_configuration"]["stop_limit_stop_limit_gtd"];
+    assert_eq!((config["stop_price"].as_str(), config["limit_price"].as_str()), (Some("90"), Some("89")));
+    assert_eq!(config["stop_direction"], "STOP_DIRECTION_STOP_DOWN");
+    assert_eq!(config["end_time"], "2020-06-02T00:00:00Z");
+    let body: serde_json::Value = serde_json::from_str(&requests[1].body).unwrap();
+    assert_eq!(body["order_configuration"]["market_market_ioc"]["quote_size"], "100");
+    assert_eq!(broker.history()[0].order.as_ref().map(|o| o.kind), Some(OrderType::StopLimit { stop: dec!(90), limit: dec!(89) }));
+}
+
+#[test]
+fn test_livetrader_cancels_open_orders() {
+    let server = MockServer::start();
+    server.mock_quote("BTC", 101.0, 99.0, 100.0);
+    server.mock_history("bitcoin", &[100.0]);
+    server.mock_place_order(MockResponse::json(fixtures::placed_order("limit-1", "BTC-USD", "buy")));
+    server.mock_brokerage_order("limit-1", MockResponse::json(fixtures::brokerage_order("limit-1", "BTC-USD", "buy", "OPEN", 0.0, 100.0)));
+    server.mock_brokerage_order("limit-1", MockResponse::json(fixtures::brokerage_order("limit-1", "BTC-USD", "buy", "CANCELLED", 0.5, 100.0)));
+    server.mock_cancel(MockResponse::json(fixtures::cancel("limit-1", true)));
+
+    let mut trader =
+        Livetrader::with_endpoints(dec!(1000), "BTC", server.endpoints(), "token", "acct", "pay").unwrap();
+    trader.trade(Actions::Order(Order::limit(Side::Buy, dec!(1), dec!(100)))).unwrap();
+    trader.trade(Actions::Hold).unwrap();
+    assert_eq!(trader.pending(), &["limit-1".to_owned()]);
+
+    // Half filled before it was cancelled, which is what the balances show.
+    trader.cancel_orders().unwrap();
+    assert!(trader.pending().is_empty());
+    assert_eq!((trader.data().account, trader.data().holding), (dec!(949.70), dec!(0.5)));
+    assert_eq!(trader.receipts()[0].status, OrderStatus::Canceled);
+}
+
+#[test]
+fn test_livetrader_holds_funds_for_pending_orders() {
+    let server = MockServer::start();
+    server.mock_quote("BTC", 101.0, 99.0, 100.0);
+    server.mock_history("bitcoin", &[100.0]);
+    server.mock_place_order(MockResponse::json(fixtures::placed_order("limit-1", "BTC-USD", "buy")));
+    server.mock_place_order(MockResponse::json(fixtures::placed_order("limit-2", "BTC-USD", "buy")));
+    for id in &["limit-1", "limit-2"] {
+        server.mock_brokerage_order(id, MockResponse::json(fixtures::brokerage_order(id, "BTC-USD", "buy", "OPEN", 0.0, 100.0)));
+    }
+
+    let mut trader = Livetrader::with_endpoints(dec!(1000), "BTC", server.endpoints(), "token", "acct", "pay")
+        .unwrap()
+        .with_fee_rate(dec!(0.25));
+    trader.trade(Actions::Order(Order::limit(Side::Buy, dec!(6), dec!(100)))).unwrap();
+    // Only the 250 not held for the first order and its fee can be spent, at 125 a unit.
+    trader.trade(Actions::Order(Order::limit(Side::Buy, dec!(6), dec!(100)))).unwrap();
+    assert_eq!(trader.pending().len(), 2);
+
+    // With everything held, or nothing held to sell, no order is sent at all.
+    assert!(matches!(trader.trade(Actions::Buy(dec!(100))), Err(Error::Order(_))));
+    assert!(matches!(trader.trade(Actions::Sell(dec!(1))), Err(Error::Order(_))));
+    assert_eq!(trader.pending().len(), 2);
+
+    let sizes: Vec<Decimal> = server
+        .requests()
+        .into_iter()
+        .filter(|r| r.method == "POST")
+        .map(|r| serde_json::from_str::<serde_json::Value>(&r.body).unwrap())
+        .map(|body| body["order_configuration"]["limit_limit_gtc"]["base_size"].as_str().unwrap().parse().unwrap())
+        .collect();
+    assert_eq!(sizes, vec![dec!(6), dec!(2)]);
+}
+
+#[test]
+fn test_livetrader_settles_past_failed_lookups() {
+    let server = MockServer::start();
+    server.mock_quote("BTC", 101.0, 99.0, 100.0);
+    server.mock_history("bitcoin", &[100.0]);
+    server.mock_place_order(MockResponse::json(fixtures::placed_order("missing", "BTC-USD", "buy")));
+    server.mock_place_order(MockResponse::json(fixtures::placed_order("limit-2", "BTC-USD", "buy")));
+    server.mock_brokerage_order("limit-2", MockResponse::json(fixtures::brokerage_order("limit-2", "BTC-USD", "buy", "FILLED", 1.0, 100.0)));
+
+    let mut trader =
+        Livetrader::with_endpoints(dec!(1000), "BTC", server.endpoints(), "token", "acct", "pay").unwrap();
+    trader.trade(Actions::Order(Order::limit(Side::Buy, dec!(1), dec!(100)))).unwrap();
+    trader.trade(Actions::Order(Order::limit(Side::Buy, dec!(1), dec!(100)))).unwrap();
+
+    // The order that cannot be found does not hold up the one that filled.
+    assert!(trader.settle().is_err());
+    assert_eq!(trader.pending(), &["missing".to_owned()]);
+    assert_eq!((trader.data().account, trader.data().holding), (dec!(899.40), dec!(1)));
+    trader.trade(Actions::Hold).unwrap();
+}
@@ -0,0 +1,88 @@
+use std::env;
+use std::fs;
+
+use chrono::{Duration, Utc};
+use rust_decimal_macros::dec;
+
+use seventh_core::api::{DataAPI, Quote, ScriptedSource};
+use seventh_core::backtrader::Actions;
+use seventh_core::historical::{Histtrader, RawData};
+use seventh_core::recorder::{Format, Record, Recorder};
+use seventh_core::Decimal;
+
+async fn record(dir: &std::path::Path, format: Format) -> Recorder {
+    let source = ScriptedSource::new();
+    let start = Utc::now() - Duration::minutes(3);
+    for i in 0..3 {
+        let step = Decimal::from(i);
+        let quote = Quote::new(dec!(101) + step, dec!(99) + step, dec!(100) + step).at(start + Duration::minutes(i));
+        source.push_quote("BTC-EUR", quote);
+    }
+    source.set_historical("BTC-EUR", vec![100.0]);
+
+    let mut data_api = DataAPI::with_source(source);
+    let mut recorder = Recorder::new(dir, format).rotate_after(2);
+    for _ in 0..3 {
+        data_api.update("BTC-EUR").await.unwrap();
+        recorder.record(data_api.last_ref().unwrap()).unwrap();
+    }
+    recorder.flush().unwrap();
+    recorder
+}
+
+#[tokio::test]
+async fn test_recording_rotates_and_replays() {
+    let dir = env::temp_dir().join(format!("seventh_record_csv_{}", std::process::id()));
+    let recorder = record(&dir, Format::Csv).await;
+
+    let pair_dir = recorder.dir("BTC-EUR");
+    assert_eq!(fs::read_dir(&pair_dir).unwrap().count(), 2);
+
+    let data = RawData::load(&pair_dir).unwrap();
+    assert_eq!(data.close(), &[100.0, 101.0, 102.0]);
+    assert_eq!(data.buy(), &[dec!(101), dec!(102), dec!(103)]);
+    assert_eq!(data.sell(), &[dec!(99), dec!(100), dec!(101)]);
+
+    // Replays buy at the recorded ask and sell at the recorded bid.
+    let mut trader = Histtrader::new(data, "BTC-EUR", dec!(1000), 0);
+    trader.trade(Actions::Buy(dec!(102))).unwrap();
+    assert_eq!(trader.data().holding, dec!(1));
+    trader.trade(Actions::Sell(dec!(1))).unwrap();
+    assert_eq!(trader.data().account, dec!(999));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_jsonl_recording() {
+    let dir = env::temp_dir().join(format!("seventh_record_jsonl_{}", std::process::id()));
+    let recorder = record(&dir, Format::Jsonl).await;
+
+    let data = RawData::load(recorder.dir("BTC-EUR")).unwrap();
+    assert_eq!(data.len(), 3);
+    assert_eq!(data.buy()[2], dec!(103));
+    // Snapshots cover no interval, so record no volume.
+    assert_eq!(data.volume(), &[0.0, 0.0, 0.0]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_jsonl_keeps_prices_exact() {
+    let record = Record {
+        time: Utc::now(),
+        open: dec!(150000.015),
+        high: dec!(150000.015),
+        low: dec!(150000.015),
+        close: dec!(150000.015),
+        volume: 0.0,
+        buy: dec!(150000.02),
+        sell: dec!(150000.01),
+        spot: dec!(150000.015),
+    };
+    let line = serde_json::to_string(&record).unwrap();
+
+    let data = RawData::from_jsonl(line.as_bytes()).unwrap();
+    assert_eq!(data.buy(), &[dec!(150000.02)]);
+    assert_eq!(data.sell(), &[dec!(150000.01)]);
+}
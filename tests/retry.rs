@@ -0,0 +1,125 @@
+use std::time::{Duration, Instant};
+
+use rust_decimal_macros::dec;
+
+use seventh_core::api::auth::{Authenticator, Request};
+use seventh_core::api::book::Side;
+use seventh_core::api::endpoints::Endpoints;
+use seventh_core::api::ratelimit::RateLimit;
+use seventh_core::api::retry::RetryPolicy;
+use seventh_core::api::{BrokerAPI, DataAPI, Pair};
+use seventh_core::mock::{fixtures, MockResponse, MockServer};
+use seventh_core::{Error, Result};
+
+fn retrying(server: &MockServer) -> Endpoints {
+    let mut endpoints = server.endpoints();
+    let retry = RetryPolicy::new(2).base_delay(Duration::from_millis(1));
+    endpoints.coinbase = endpoints.coinbase.retry(retry);
+    endpoints.coingecko = endpoints.coingecko.retry(retry);
+    endpoints
+}
+
+fn count(server: &MockServer, path: &str) -> usize {
+    server.requests().iter().filter(|r| r.path == path).count()
+}
+
+#[tokio::test]
+async fn test_retry_after_rate_limit() {
+    let server = MockServer::start();
+    server.mock("GET", "/v2/prices/BTC-USD/spot", MockResponse::rate_limited(0));
+    server.mock(
+        "GET",
+        "/coins/bitcoin/market_chart/range",
+        MockResponse::status(503).body(fixtures::error("unavailable", "Try again")),
+    );
+    server.mock_quote("BTC", 101.0, 99.0, 100.0);
+    server.mock_history("bitcoin", &[98.0, 99.0, 100.0]);
+
+    let mut data_api = DataAPI::with_endpoints(retrying(&server)).unwrap();
+    data_api.update("BTC").await.unwrap();
+
+    assert_eq!(data_api.last_ref().unwrap().historical(), vec![98.0, 99.0, 100.0]);
+    assert_eq!(count(&server, "/v2/prices/BTC-USD/spot"), 2);
+    assert_eq!(count(&server, "/coins/bitcoin/market_chart/range"), 2);
+}
+
+#[tokio::test]
+async fn test_retries_exhausted() {
+    let server = MockServer::start();
+    server.mock_quote("BTC", 101.0, 99.0, 100.0);
+    server.mock("GET", "/coins/bitcoin/market_chart/range", MockResponse::status(500));
+
+    let mut data_api = DataAPI::with_endpoints(retrying(&server)).unwrap();
+    match data_api.update("BTC").await {
+        Err(Error::Status { status, .. }) => assert_eq!(status, 500),
+        other => panic!("expected status error, got {:?}", other),
+    }
+    assert_eq!(count(&server, "/coins/bitcoin/market_chart/range"), 3);
+}
+
+#[tokio::test]
+async fn test_long_retry_after_not_waited() {
+    let server = MockServer::start();
+    server.mock("GET", "/v2/prices/BTC-USD/spot", MockResponse::rate_limited(3600));
+    server.mock_quote("BTC", 101.0, 99.0, 100.0);
+    server.mock_history("bitcoin", &[100.0]);
+
+    let mut data_api = DataAPI::with_endpoints(retrying(&server)).unwrap();
+    match data_api.update("BTC").await {
+        Err(Error::RateLimited { retry_after }) => assert_eq!(retry_after, Some(Duration::from_secs(3600))),
+        other => panic!("expected rate limit, got {:?}", other),
+    }
+    assert_eq!(count(&server, "/v2/prices/BTC-USD/spot"), 1);
+}
+
+#[tokio::test]
+async fn test_orders_not_retried() {
+    let server = MockServer::start();
+    server.mock_order("acct", "buys", MockResponse::status(503));
+    server.mock_order("acct", "buys", MockResponse::json(fixtures::order("buy", 0.5, "BTC", 50.0)));
+
+    let mut broker = BrokerAPI::with_endpoints(retrying(&server), "token", "acct", "pay").unwrap();
+    assert!(broker.buy(&Pair::from("BTC"), dec!(50)).await.is_err());
+    assert_eq!(count(&server, "/v2/accounts/acct/buys"), 1);
+}
+
+/// Sends the time each request was signed at.
+struct SignedAt;
+
+impl Authenticator for SignedAt {
+    fn headers(&self, request: &Request) -> Result<Vec<(String, String)>> {
+        Ok(vec![("X-Signed-At".to_owned(), request.time.to_rfc3339())])
+    }
+}
+
+#[tokio::test]
+async fn test_retries_signed_afresh() {
+    let server = MockServer::start();
+    server.mock_get_order("acct", "buys", "order", MockResponse::status(503));
+    server.mock_get_order("acct", "buys", "order", MockResponse::json(fixtures::order("buy", 0.5, "BTC", 50.0)));
+
+    let broker = BrokerAPI::with_endpoints(retrying(&server), "token", "acct", "pay").unwrap().with_authenticator(SignedAt);
+    broker.get_order(Side::Buy, "order").await.unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_ne!(requests[0].headers["x-signed-at"], requests[1].headers["x-signed-at"]);
+}
+
+#[tokio::test]
+async fn test_rate_limit_spaces_requests() {
+    let server = MockServer::start();
+    server.mock_quote("BTC", 101.0, 99.0, 100.0);
+    server.mock_history("bitcoin", &[100.0]);
+
+    let mut endpoints = server.endpoints();
+    endpoints.coinbase = endpoints.coinbase.rate_limit(RateLimit::new(1, Duration::from_millis(50)));
+
+    let start = Instant::now();
+    let mut data_api = DataAPI::with_endpoints(endpoints).unwrap();
+    data_api.update("BTC").await.unwrap();
+    data_api.update("BTC").await.unwrap();
+
+    // Six quote requests with one token to start and one more every 50ms.
+    assert!(start.elapsed() >= Duration::from_millis(250));
+}
@@ -0,0 +1,86 @@
+use std::env;
+use std::fs;
+use std::time::Duration;
+
+use seventh_core::api::symbols::{Symbol, SymbolRegistry};
+use seventh_core::api::{DataAPI, WebSource};
+use seventh_core::mock::MockServer;
+use seventh_core::Error;
+
+#[tokio::test]
+async fn test_load_listings() {
+    let server = MockServer::start();
+    server.mock_listings(
+        &[("solana", "sol"), ("bitcoin", "btc"), ("dog-one", "dog"), ("dog-two", "dog")],
+        &[("SOL", "USD"), ("BTC", "USD"), ("XLM", "EUR")],
+    );
+
+    let endpoints = server.endpoints();
+    let mut symbols = SymbolRegistry::new();
+    symbols.load_listings(&endpoints.client().unwrap(), &endpoints).await.unwrap();
+
+    assert_eq!(symbols.coingecko_id("SOL").unwrap(), "solana");
+    assert!(symbols.on_coinbase("sol"));
+    assert!(symbols.on_coinbase("XLM"));
+    assert!(!symbols.on_coinbase("DOG"));
+
+    // Ambiguous CoinGecko tickers are left without an id.
+    match symbols.coingecko_id("DOG") {
+        Err(Error::UnknownSymbol(_)) => (),
+        other => panic!("expected unknown symbol, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_listed_symbol_updates() {
+    let server = MockServer::start();
+    server.mock_listings(&[("solana", "sol")], &[("SOL", "USD")]);
+    server.mock_quote("SOL", 21.0, 19.0, 20.0);
+    server.mock_history("solana", &[19.0, 20.0]);
+
+    let mut source = WebSource::with_endpoints(server.endpoints()).unwrap();
+    source.load_symbols().await.unwrap();
+
+    let mut data_api = DataAPI::with_source(source);
+    data_api.update("SOL").await.unwrap();
+    assert_eq!(data_api.last_ref().unwrap().historical(), vec![19.0, 20.0]);
+}
+
+#[tokio::test]
+async fn test_unknown_symbol_sends_no_requests() {
+    let server = MockServer::start();
+    let mut data_api = DataAPI::with_endpoints(server.endpoints()).unwrap();
+
+    match data_api.update("NOPE").await {
+        Err(Error::UnknownSymbol(symbol)) => assert_eq!(symbol, "NOPE"),
+        other => panic!("expected unknown symbol, got {:?}", other),
+    }
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn test_config_and_cache() {
+    let dir = env::temp_dir().join(format!("seventh_symbols_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let config = dir.join("Coins.toml");
+    fs::write(
+        &config,
+        "[coins.SOL]\ncoingecko = \"solana\"\n\n[coins.WBTC]\ncoingecko = \"wrapped-bitcoin\"\ncoinbase = false\n",
+    )
+    .unwrap();
+
+    let mut symbols = SymbolRegistry::new();
+    symbols.load_config(&config).unwrap();
+    assert_eq!(symbols.get("SOL").unwrap(), &Symbol::new("solana"));
+    assert!(!symbols.on_coinbase("WBTC"));
+    assert_eq!(symbols.len(), 6);
+
+    let cache = dir.join("symbols.json");
+    assert!(SymbolRegistry::load_cache(&cache, Duration::from_secs(60)).unwrap().is_none());
+    symbols.save_cache(&cache).unwrap();
+    let cached = SymbolRegistry::load_cache(&cache, Duration::from_secs(60)).unwrap();
+    assert_eq!(cached, Some(symbols));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,79 @@
+use std::time::Duration as StdDuration;
+
+use chrono::{Duration, Utc};
+use rust_decimal_macros::dec;
+
+use seventh_core::api::validation::{Anomaly, Validation};
+use seventh_core::api::{DataAPI, Quote, ScriptedSource};
+use seventh_core::backtrader::{Actions, Backtrader};
+use seventh_core::livetrader::Livetrader;
+use seventh_core::Error;
+
+#[tokio::test]
+async fn test_anomalies_flagged() {
+    let source = ScriptedSource::new();
+    source.push_quote("BTC", Quote::new(dec!(99), dec!(101), dec!(0)).at(Utc::now() - Duration::hours(1)));
+    source.set_historical("BTC", vec![100.0]);
+
+    let mut data_api = DataAPI::with_source(source);
+    data_api.update("BTC").await.unwrap();
+
+    let coin = data_api.last_ref().unwrap();
+    assert!(!coin.is_valid());
+    assert_eq!(coin.anomalies()[0], Anomaly::Crossed { buy: dec!(99), sell: dec!(101) });
+    assert_eq!(coin.anomalies()[1], Anomaly::NonPositive { price: dec!(0) });
+    assert!(matches!(coin.anomalies()[2], Anomaly::Stale { age } if age >= StdDuration::from_secs(3600)));
+
+    let json = serde_json::to_value(coin).unwrap();
+    assert_eq!(json["anomalies"][0]["kind"], "crossed");
+}
+
+#[tokio::test]
+async fn test_jump_rejected() {
+    let source = ScriptedSource::new();
+    source.push_quote("ETH", Quote::new(dec!(101), dec!(99), dec!(100)));
+    source.push_quote("ETH", Quote::new(dec!(161), dec!(159), dec!(160)));
+    source.push_quote("ETH", Quote::new(dec!(111), dec!(109), dec!(110)));
+    source.set_historical("ETH", vec![100.0]);
+
+    let validation = Validation::new().max_jump(dec!(0.5)).reject();
+    let mut data_api = DataAPI::with_source(source).with_validation(validation);
+    data_api.update("ETH").await.unwrap();
+    match data_api.update("ETH").await {
+        Err(Error::Invalid(anomalies)) => {
+            assert_eq!(anomalies, vec![Anomaly::Jump { previous: dec!(100), current: dec!(160) }])
+        }
+        other => panic!("expected invalid data, got {:?}", other),
+    }
+    assert_eq!(data_api.len(), 1);
+
+    data_api.update("ETH").await.unwrap();
+    assert_eq!(data_api.last_ref().unwrap().spotprice(), dec!(110));
+}
+
+#[test]
+fn test_traders_refuse_invalid_data() {
+    let source = ScriptedSource::new();
+    source.push_quote("BTC", Quote::new(dec!(100), dec!(100), dec!(100)));
+    source.push_quote("BTC", Quote::new(dec!(90), dec!(110), dec!(100)));
+    source.set_historical("BTC", vec![100.0]);
+
+    let mut trader = Backtrader::with_source(dec!(1000), "BTC", source.clone()).unwrap();
+    assert!(matches!(trader.trade(Actions::Buy(dec!(100))), Err(Error::Invalid(_))));
+    assert_eq!(trader.data().account, dec!(1000));
+    assert!(trader.history().is_empty());
+    trader.trade(Actions::Hold).unwrap();
+
+    // The broker points at the real Coinbase API, so reaching it would fail differently.
+    let stale = ScriptedSource::new();
+    stale.push_quote("BTC", Quote::new(dec!(101), dec!(99), dec!(100)).at(Utc::now() - Duration::minutes(2)));
+    stale.set_historical("BTC", vec![100.0]);
+    let mut trader = Livetrader::with_source(dec!(1000), "BTC", stale, "token", "acct", "pay")
+        .unwrap()
+        .with_validation(Validation::new().max_age(StdDuration::from_secs(60)));
+    match trader.trade(Actions::Buy(dec!(100))) {
+        Err(Error::Invalid(anomalies)) => assert!(matches!(anomalies[0], Anomaly::Stale { .. })),
+        other => panic!("expected invalid data, got {:?}", other),
+    }
+    assert_eq!(trader.data().holding, dec!(0));
+}